@@ -1,8 +1,7 @@
-use core::{util::Game, GameData};
 use std::{env::var, fs, io::Write, path::PathBuf};
 
+// game data is bundled by its own crate, the CLI uses it too
 fn main() {
-    dotenv::dotenv().ok();
     let out_dir = var("OUT_DIR").unwrap();
     let out = &mut fs::File::options()
         .write(true)
@@ -18,20 +17,6 @@ fn main() {
     };
     let options = zip::write::FileOptions::default().compression_method(method);
 
-    zip.start_file("gamedata.bin", options).unwrap();
-    let steam_dir: PathBuf = var("STEAM_APPS").unwrap().into();
-    let mut common = steam_dir.clone();
-    common.push("common");
-    let game_dirs = Game::LIST.map(|game| {
-        let mut dir = common.clone();
-        dir.push(game.steam_dir());
-        assert!(dir.exists(), "game directory missing at {dir:#?}");
-        dir
-    });
-    let game_data = Game::LIST
-        .map(|game| GameData::read(game, &game_dirs[game.idx()], Some(&steam_dir)).unwrap());
-    bincode::serialize_into(&mut zip, &game_data).unwrap();
-
     zip.start_file("icons.ttf", options).unwrap();
     let icons = fs::read(PathBuf::from_iter(["assets", "fa-solid-900.ttf"])).unwrap();
     zip.write_all(&icons).unwrap();
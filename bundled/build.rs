@@ -0,0 +1,36 @@
+use core::{util::Game, GameData};
+use std::{env::var, fs, path::PathBuf};
+
+fn main() {
+    dotenv::dotenv().ok();
+    let out_dir = var("OUT_DIR").unwrap();
+    let out = &mut fs::File::options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(PathBuf::from_iter([&out_dir, "gamedata.zip"]))
+        .unwrap();
+    let mut zip = zip::ZipWriter::new(out);
+    let method = if var("TARGET").unwrap() == "wasm32-unknown-unknown" {
+        zip::CompressionMethod::Deflated
+    } else {
+        zip::CompressionMethod::Bzip2
+    };
+    let options = zip::write::FileOptions::default().compression_method(method);
+
+    zip.start_file("gamedata.bin", options).unwrap();
+    let steam_dir: PathBuf = var("STEAM_APPS").unwrap().into();
+    let mut common = steam_dir.clone();
+    common.push("common");
+    let game_dirs = Game::LIST.map(|game| {
+        let mut dir = common.clone();
+        dir.push(game.steam_dir());
+        assert!(dir.exists(), "game directory missing at {}", dir.display());
+        dir
+    });
+    let game_data = Game::LIST
+        .map(|game| GameData::read(game, &game_dirs[game.idx()], Some(&steam_dir)).unwrap());
+    bincode::serialize_into(&mut zip, &game_data).unwrap();
+
+    zip.finish().unwrap();
+}
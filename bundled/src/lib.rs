@@ -0,0 +1,13 @@
+use core::{
+    util::{bytes::Cursor, Game},
+    GameData,
+};
+
+// read from the installed games when building, so the GUI and the CLI work without them
+static GAME_DATA: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/gamedata.zip"));
+
+pub fn game_data() -> [GameData; Game::COUNT] {
+    let mut archive = zip::ZipArchive::new(Cursor::new(GAME_DATA)).unwrap();
+    let bin = archive.by_name("gamedata.bin").unwrap();
+    bincode::deserialize_from(bin).unwrap()
+}
@@ -0,0 +1,341 @@
+use core::{util::SResult, Data as _, GameDataMapped};
+use save::{Character, GlobalValue, Item, JournalEntry, Save};
+use serde_json::{json, Value};
+use std::fs;
+
+fn parse<T: std::str::FromStr>(value: &str, what: &str) -> SResult<T> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {what}: {value}"))
+}
+
+fn global_value(value: &GlobalValue) -> Value {
+    match value {
+        GlobalValue::Boolean(value) => json!(value),
+        GlobalValue::Number(value) => json!(value),
+    }
+}
+
+fn item(item: &Item) -> Value {
+    json!({
+        "tag": item.tag,
+        "name": item.name,
+        "base_item": item.base_item,
+        "stack_size": item.stack_size,
+        "charges": item.charges,
+        "max_charges": item.max_charges,
+    })
+}
+
+fn character(char: &Character, data: &GameDataMapped) -> Value {
+    let feat_name = |id| data.feats.get(id).map(|feat| feat.name.as_str());
+    let class_name = |id| data.classes.get(id).map(|class| class.name.as_str());
+
+    let feats: Vec<_> = char
+        .feats
+        .iter()
+        .map(|id| json!({ "id": id, "name": feat_name(id) }))
+        .collect();
+    let classes: Vec<_> = char
+        .classes
+        .iter()
+        .map(|class| json!({ "id": class.id, "name": class_name(&class.id), "level": class.level }))
+        .collect();
+    let equipment: Vec<_> = char.equipment.iter().flatten().map(item).collect();
+
+    json!({
+        "idx": char.idx,
+        "name": char.get_name(),
+        "tag": char.tag,
+        "hp": char.hp,
+        "hp_max": char.hp_max,
+        "fp": char.fp,
+        "fp_max": char.fp_max,
+        "experience": char.experience,
+        "good_evil": char.good_evil,
+        "attributes": char.attributes,
+        "skills": char.skills,
+        "classes": classes,
+        "feats": feats,
+        "equipment": equipment,
+    })
+}
+
+pub fn info(save: &Save) -> Value {
+    let nfo = &save.nfo;
+    let party = &save.party_table;
+
+    json!({
+        "game": save.game.to_string(),
+        "save_name": nfo.save_name,
+        "pc_name": nfo.pc_name,
+        "area_name": nfo.area_name,
+        "last_module": nfo.last_module,
+        "time_played": nfo.time_played,
+        "cheat_used": nfo.cheat_used || party.cheat_used,
+        "credits": party.credits,
+        "party_xp": party.party_xp,
+        "components": party.components,
+        "chemicals": party.chemicals,
+        "has_image": save.image.is_some(),
+    })
+}
+
+pub fn party(save: &Save, data: &GameDataMapped) -> Value {
+    let party = &save.party_table;
+    let members: Vec<_> = party
+        .members
+        .iter()
+        .map(|member| json!({ "idx": member.idx, "leader": member.leader }))
+        .collect();
+    let available: Vec<_> = party
+        .available_members
+        .iter()
+        .enumerate()
+        .map(|(idx, member)| {
+            json!({
+                "idx": idx,
+                "available": member.available,
+                "selectable": member.selectable,
+                "influence": party.influence.as_ref().and_then(|inf| inf.get(idx)),
+            })
+        })
+        .collect();
+    let characters: Vec<_> = save
+        .characters
+        .iter()
+        .map(|char| character(char, data))
+        .collect();
+
+    json!({
+        "members": members,
+        "available_members": available,
+        "characters": characters,
+    })
+}
+
+pub fn globals(save: &Save) -> Value {
+    let globals: serde_json::Map<_, _> = save
+        .globals
+        .iter()
+        .map(|global| (global.name.clone(), global_value(&global.value)))
+        .collect();
+
+    Value::Object(globals)
+}
+
+pub fn journal(save: &Save, data: &GameDataMapped) -> Value {
+    let entries: Vec<_> = save
+        .party_table
+        .journal
+        .iter()
+        .map(|entry| {
+            let quest = data.quests.get(&entry.id);
+            let stage = quest.and_then(|quest| quest.stages.get(&entry.stage));
+            json!({
+                "id": entry.id,
+                "name": quest.map(|quest| &quest.name),
+                "stage": entry.stage,
+                "description": stage.map(|stage| &stage.description),
+                "completed": stage.map(|stage| stage.end),
+                "date": entry.date,
+                "time": entry.time,
+            })
+        })
+        .collect();
+
+    Value::Array(entries)
+}
+
+pub fn inventory(save: &Save) -> Value {
+    Value::Array(save.inventory.iter().map(item).collect())
+}
+
+pub fn export(save: &Save) -> SResult<Value> {
+    let json = save::to_json(save)?;
+    serde_json::from_str(&json).map_err(|err| err.to_string())
+}
+
+pub fn apply(save: &mut Save, data: &GameDataMapped, path: &str) -> SResult<Value> {
+    let json = fs::read_to_string(path).map_err(|err| format!("couldn't read {path}: {err}"))?;
+    save::apply_json(save, &json, data)?;
+
+    Ok(json!({ "applied": path }))
+}
+
+pub fn set_credits(save: &mut Save, amount: &str) -> SResult<Value> {
+    save.party_table.credits = parse(amount, "amount of credits")?;
+
+    Ok(json!({ "credits": save.party_table.credits }))
+}
+
+pub fn set_global(save: &mut Save, name: &str, value: &str) -> SResult<Value> {
+    let global = save
+        .globals
+        .iter_mut()
+        .find(|global| global.name.eq_ignore_ascii_case(name))
+        .ok_or(format!("no global named {name}"))?;
+
+    // the type stays the same, saves don't store globals of other types
+    global.value = match global.value {
+        GlobalValue::Boolean(_) => GlobalValue::Boolean(match value {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            _ => return Err(format!("{} is a boolean, got {value}", global.name)),
+        }),
+        GlobalValue::Number(_) => GlobalValue::Number(parse(value, "number")?),
+    };
+
+    Ok(json!({ global.name.clone(): global_value(&global.value) }))
+}
+
+pub fn add_item(save: &mut Save, data: &GameDataMapped, tag: &str) -> SResult<Value> {
+    // resref first, it's unique unlike the tag
+    let tag = tag.to_lowercase();
+    let data_item = data.items.get(&tag).or_else(|| {
+        data.items
+            .values()
+            .find(|item| item.tag.eq_ignore_ascii_case(&tag))
+    });
+    let data_item = data_item.ok_or(format!("no item {tag} in the game data"))?;
+
+    let new_item = Item::from(data_item);
+    let output = item(&new_item);
+    save.inventory.push(new_item);
+
+    Ok(output)
+}
+
+pub fn set_quest(save: &mut Save, data: &GameDataMapped, id: &str, stage: &str) -> SResult<Value> {
+    let stage = parse(stage, "stage")?;
+    // quests from mods may not be in the data, only stages of known ones are checked
+    if let Some(quest) = data.quests.get(id) {
+        if !quest.stages.contains_key(&stage) {
+            return Err(format!("quest {id} doesn't have stage {stage}"));
+        }
+    }
+
+    let journal = &mut save.party_table.journal;
+    if let Some(entry) = journal.iter_mut().find(|entry| entry.id == id) {
+        entry.stage = stage;
+    } else {
+        let last = journal.last();
+        journal.push(JournalEntry {
+            id: id.to_owned(),
+            stage,
+            date: last.map_or(0, |e| e.date),
+            time: last.map_or(0, |e| e.time + 1),
+        });
+    }
+
+    Ok(json!({ "id": id, "stage": stage }))
+}
+
+pub fn add_feat(save: &mut Save, data: &GameDataMapped, char: &str, feat: &str) -> SResult<Value> {
+    // index in the save or name/tag
+    let char = save
+        .characters
+        .iter_mut()
+        .find(|c| {
+            c.idx.to_string() == char
+                || c.tag.eq_ignore_ascii_case(char)
+                || c.name.eq_ignore_ascii_case(char)
+        })
+        .ok_or(format!("no character {char}"))?;
+
+    // ID or name
+    let feat = match feat.parse() {
+        Ok(id) => data.feats.get(&id),
+        Err(_) => data
+            .feats
+            .values()
+            .find(|f| f.get_name().eq_ignore_ascii_case(feat)),
+    }
+    .ok_or(format!("no feat {feat} in the game data"))?;
+
+    let added = !char.feats.contains(&feat.id);
+    if added {
+        char.feats.push(feat.id);
+    }
+
+    Ok(json!({
+        "character": char.get_name(),
+        "feat": { "id": feat.id, "name": feat.name },
+        "added": added,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::{add_item, set_global, set_quest};
+    use save::{
+        test_support::{make_data, make_save},
+        GlobalValue,
+    };
+
+    #[test]
+    fn globals() {
+        let mut save = make_save();
+        let output = set_global(&mut save, "k_number", "12").unwrap();
+        assert_eq!(output.to_string(), r#"{"K_NUMBER":12}"#);
+        set_global(&mut save, "K_BOOLEAN", "0").unwrap();
+        let values: Vec<_> = save
+            .globals
+            .iter()
+            .map(|g| (g.name.as_str(), &g.value))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("K_BOOLEAN", &GlobalValue::Boolean(false)),
+                ("K_NUMBER", &GlobalValue::Number(12)),
+            ]
+        );
+
+        // the type of a global doesn't change
+        assert!(set_global(&mut save, "K_NUMBER", "true").is_err());
+        let err = set_global(&mut save, "K_BOOLEAN", "2").unwrap_err();
+        assert_eq!(err, "K_BOOLEAN is a boolean, got 2");
+        assert!(set_global(&mut save, "K_MISSING", "1").is_err());
+    }
+
+    #[test]
+    fn items() {
+        let mut save = make_save();
+        let data = make_data();
+        save.inventory.clear();
+        add_item(&mut save, &data, "G_W_BLSTRPSTL001").unwrap();
+        add_item(&mut save, &data, "g_w_blstrpstl01").unwrap();
+        let tags: Vec<_> = save
+            .inventory
+            .iter()
+            .map(|item| item.tag.as_str())
+            .collect();
+        assert_eq!(tags, ["G_W_BLSTRPSTL01", "G_W_BLSTRPSTL01"]);
+
+        let err = add_item(&mut save, &data, "g_w_missing").unwrap_err();
+        assert_eq!(err, "no item g_w_missing in the game data");
+        assert_eq!(save.inventory.len(), 2);
+    }
+
+    #[test]
+    fn quests() {
+        let mut save = make_save();
+        let data = make_data();
+        set_quest(&mut save, &data, "k_swg_helena", "10").unwrap();
+        set_quest(&mut save, &data, "k_swg_helena", "20").unwrap();
+        let err = set_quest(&mut save, &data, "k_swg_helena", "30").unwrap_err();
+        assert_eq!(err, "quest k_swg_helena doesn't have stage 30");
+        assert!(set_quest(&mut save, &data, "k_swg_helena", "ten").is_err());
+        // quests the data doesn't know about can have any stage
+        set_quest(&mut save, &data, "mod_quest", "3").unwrap();
+
+        let journal: Vec<_> = save
+            .party_table
+            .journal
+            .iter()
+            .map(|entry| (entry.id.as_str(), entry.stage))
+            .collect();
+        assert_eq!(journal, [("k_swg_helena", 20), ("mod_quest", 3)]);
+    }
+}
@@ -0,0 +1,251 @@
+use core::{
+    tlk,
+    util::{encoding::CodePage, Game, SResult},
+    GameData, GameDataMapped, ResourceManager,
+};
+use save::Save;
+use serde_json::{json, Value};
+use std::{env, fs, process::ExitCode};
+
+mod commands;
+
+const USAGE: &str =
+    "usage: sotor-cli <command> <save dir> [args] [--game-dir <dir>] [--steam-dir <dir>]
+       [--code-page <name>]
+
+text is read in the code page of the game's language, translations that use
+the English one need --code-page, e.g. windows-1251
+
+inspection:
+  info
+  party
+  globals
+  journal
+  inventory
+  export
+
+the game data comes with the editor, --game-dir reads it from an install
+instead, e.g. to have items and quests from mods
+
+editing:
+  apply <json file>
+  set-credits <amount>
+  set-global <name> <value>
+  add-item <tag>
+  set-quest <id> <stage>
+  add-feat <character> <feat>";
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    command: String,
+    save_dir: String,
+    params: Vec<String>,
+    game_dir: Option<String>,
+    steam_dir: Option<String>,
+    code_page: Option<CodePage>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> SResult<Args> {
+    let mut positional = vec![];
+    let mut game_dir = None;
+    let mut steam_dir = None;
+    let mut code_page = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let option = match arg.as_str() {
+            "--game-dir" => &mut game_dir,
+            "--steam-dir" => &mut steam_dir,
+            "--code-page" => &mut code_page,
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ => {
+                positional.push(arg);
+                continue;
+            }
+        };
+        *option = Some(args.next().ok_or(format!("{arg} needs a value"))?);
+    }
+
+    let mut positional = positional.into_iter();
+    let (Some(command), Some(save_dir)) = (positional.next(), positional.next()) else {
+        return Err("a command and a save dir are required".to_owned());
+    };
+    let code_page = code_page
+        .map(|name| CodePage::from_name(&name).ok_or(format!("unknown code page {name}")))
+        .transpose()?;
+
+    Ok(Args {
+        command,
+        save_dir,
+        params: positional.collect(),
+        game_dir,
+        steam_dir,
+        code_page,
+    })
+}
+
+fn read_code_page(args: &Args, resources: Option<&ResourceManager>) -> SResult<CodePage> {
+    if let Some(code_page) = args.code_page {
+        return Ok(code_page);
+    }
+    let Some(resources) = resources else {
+        return Ok(CodePage::default());
+    };
+    // the one the game data uses, TSL can have it in steamassets or replaced by a workshop mod
+    let dialog = resources.dialog();
+    let bytes =
+        fs::read(dialog).map_err(|err| format!("couldn't read {}: {err}", dialog.display()))?;
+
+    Ok(tlk::read_header(&bytes)?.code_page)
+}
+
+fn read_game_data(resources: Option<&ResourceManager>, game: Game) -> SResult<GameDataMapped> {
+    let data = if let Some(resources) = resources {
+        GameData::from_resources(resources, &|_| ())?
+    } else {
+        bundled::game_data().into_iter().nth(game.idx()).unwrap()
+    };
+
+    Ok(data.into())
+}
+
+fn run(args: &Args) -> SResult<Value> {
+    // the game is known only after reading the save, but telling which one it is doesn't need
+    // the right code page, so the save is read again only if the install's one is different
+    let first_code_page = args.code_page.unwrap_or_default();
+    let mut save = Save::read_from_directory(&args.save_dir, first_code_page)?;
+    let resources = args
+        .game_dir
+        .as_ref()
+        .map(|dir| ResourceManager::new(save.game, dir, args.steam_dir.as_ref()))
+        .transpose()?;
+    let code_page = read_code_page(args, resources.as_ref())?;
+    if code_page != first_code_page {
+        save = Save::read_from_directory(&args.save_dir, code_page)?;
+    }
+    let data = read_game_data(resources.as_ref(), save.game)?;
+    let params: Vec<_> = args.params.iter().map(String::as_str).collect();
+
+    let inspection = match args.command.as_str() {
+        "info" => Some(commands::info(&save)),
+        "party" => Some(commands::party(&save, &data)),
+        "globals" => Some(commands::globals(&save)),
+        "journal" => Some(commands::journal(&save, &data)),
+        "inventory" => Some(commands::inventory(&save)),
+        "export" => Some(commands::export(&save)?),
+        _ => None,
+    };
+    if let Some(output) = inspection {
+        if let Some(param) = params.first() {
+            return Err(format!("unexpected argument {param}"));
+        }
+        return Ok(output);
+    }
+
+    let output = match (args.command.as_str(), params.as_slice()) {
+        ("apply", [path]) => commands::apply(&mut save, &data, path)?,
+        ("set-credits", [amount]) => commands::set_credits(&mut save, amount)?,
+        ("set-global", [name, value]) => commands::set_global(&mut save, name, value)?,
+        ("add-item", [tag]) => commands::add_item(&mut save, &data, tag)?,
+        ("set-quest", [id, stage]) => commands::set_quest(&mut save, &data, id, stage)?,
+        ("add-feat", [char, feat]) => commands::add_feat(&mut save, &data, char, feat)?,
+        ("apply" | "set-credits" | "set-global" | "add-item" | "set-quest" | "add-feat", _) => {
+            return Err(format!("wrong number of arguments for {}", args.command))
+        }
+        (command, _) => return Err(format!("unknown command {command}")),
+    };
+    Save::save_to_directory(&args.save_dir, &mut save, &data, code_page)?;
+
+    Ok(output)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<_> = env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    // errors are JSON as well, so scripts only have to parse one thing
+    let (output, code) = match parse_args(args).and_then(|args| run(&args)) {
+        Ok(output) => (output, ExitCode::SUCCESS),
+        Err(err) => (json!({ "error": err }), ExitCode::FAILURE),
+    };
+    println!("{}", serde_json::to_string_pretty(&output).unwrap());
+
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_args, read_code_page, Args};
+    use core::{
+        key::{self, Key},
+        tlk::{self, Tlk},
+        util::{encoding::CodePage, Game},
+        ResourceManager,
+    };
+    use std::fs;
+
+    fn parse(args: &str) -> Result<Args, String> {
+        parse_args(args.split(' ').map(str::to_owned))
+    }
+
+    #[test]
+    fn args() {
+        let args = parse("set-global SAVE K_SWG_HELENA 1 --game-dir GAME").unwrap();
+        assert_eq!(
+            args,
+            Args {
+                command: "set-global".to_owned(),
+                save_dir: "SAVE".to_owned(),
+                params: vec!["K_SWG_HELENA".to_owned(), "1".to_owned()],
+                game_dir: Some("GAME".to_owned()),
+                steam_dir: None,
+                code_page: None,
+            }
+        );
+
+        // options can go anywhere
+        let args = parse("--steam-dir STEAM info --game-dir GAME SAVE").unwrap();
+        assert_eq!(args.save_dir, "SAVE");
+        assert_eq!(args.steam_dir.as_deref(), Some("STEAM"));
+        assert!(args.params.is_empty());
+
+        let args = parse("info SAVE --code-page Windows-1251").unwrap();
+        assert_eq!(args.code_page, Some(CodePage::Windows1251));
+        assert!(parse("info SAVE --code-page koi8-r").is_err());
+
+        assert!(parse("info").is_err());
+        assert!(parse("info SAVE --game-dir").is_err());
+        assert!(parse("info SAVE --verbose").is_err());
+    }
+
+    #[test]
+    fn code_page() {
+        // updated steam TSL, everything is in steamassets
+        let dir = std::env::temp_dir().join(format!("sotor_cli_{}", std::process::id()));
+        let assets = dir.join("steamassets");
+        fs::create_dir_all(&assets).unwrap();
+        let key = Key {
+            files: vec![],
+            resources: ahash::HashMap::default(),
+            build_year: 0,
+            build_day: 0,
+        };
+        fs::write(assets.join("chitin.key"), key::write(key)).unwrap();
+        // Polish
+        fs::write(assets.join("dialog.tlk"), tlk::write(Tlk::new(5))).unwrap();
+
+        let resources = ResourceManager::new(Game::Two, &dir, None).unwrap();
+        let args = parse("info SAVE").unwrap();
+        let code_page = read_code_page(&args, Some(&resources)).unwrap();
+        assert_eq!(code_page, CodePage::Windows1250);
+        let args = parse("info SAVE --code-page windows-1251").unwrap();
+        let code_page = read_code_page(&args, Some(&resources)).unwrap();
+        assert_eq!(code_page, CodePage::Windows1251);
+        assert_eq!(read_code_page(&args, None).unwrap(), CodePage::Windows1251);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,7 +1,51 @@
+use crate::formats::{key::KeyResRef, ResourceType};
+
 mod read;
+mod write;
+
 pub use read::*;
+pub use write::*;
+
+// 3 DWORD fields after the file head
+const HEADER_SIZE: usize = 3;
+// 4 DWORDs
+const RESOURCE_SIZE: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BifResource {
+    // same as in the Key, file_idx << 20 | resource_idx
+    pub id: u32,
+    pub tp: ResourceType,
+    pub content: Vec<u8>,
+}
 
+// a resource's place in the file, for reading it without copying
 #[derive(Debug, Clone, PartialEq)]
+pub struct BifEntry {
+    pub id: u32,
+    pub tp: ResourceType,
+    pub offset: usize,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Bif {
-    pub resources: Vec<Vec<u8>>,
+    pub resources: Vec<BifResource>,
+}
+
+impl Bif {
+    // file_idx is the index of this BIF in the Key
+    pub fn push(&mut self, file_idx: u32, tp: ResourceType, content: Vec<u8>) -> KeyResRef {
+        let res_ref = KeyResRef {
+            file_idx,
+            resource_idx: self.resources.len() as u32,
+        };
+        self.resources.push(BifResource {
+            id: res_ref.id(),
+            tp,
+            content,
+        });
+
+        res_ref
+    }
 }
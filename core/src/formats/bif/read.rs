@@ -1,64 +1,126 @@
 use crate::{
-    formats::{bif::Bif, impl_read_resource, ReadResource, ResourceType},
-    util::{
-        bytes::{take, take_bytes, take_head, Cursor, SeekExt as _, DWORD_SIZE},
-        SResult,
+    formats::{
+        bif::{Bif, BifEntry, BifResource, HEADER_SIZE, RESOURCE_SIZE},
+        impl_read_resource, ErrorKind, FResult, FormatError, ReadResource, ReadResult,
+        ResourceType,
     },
+    util::bytes::{take, take_bytes, take_head, Budget, Cursor, SeekExt as _, DWORD_SIZE},
 };
-use std::io::BufRead;
 
 struct Reader<'a> {
     c: &'a mut Cursor<'a>,
     required_indices: &'a [usize],
+    budget: Budget,
 }
 
 impl<'a> Reader<'a> {
     fn new(c: &'a mut Cursor<'a>, required_indices: &'a [usize]) -> Self {
+        let budget = Budget::new(c.get_ref().len());
         Self {
             c,
             required_indices,
+            budget,
         }
     }
 
-    fn read(mut self) -> SResult<Bif> {
-        let offset = self.read_header()?;
-        let resources = self.read_resources(offset)?;
+    fn read(&mut self) -> ReadResult<Bif> {
+        let (count, offset) = self.read_header()?;
+        let resources = self.read_resources(count, offset)?;
 
         Ok(Bif { resources })
     }
 
-    fn read_header(&mut self) -> SResult<usize> {
-        let file_head = take_head(self.c).ok_or("couldn't read file head")?;
+    fn read_header(&mut self) -> ReadResult<(usize, usize)> {
+        let file_head = take_head(self.c)?;
 
         if file_head.tp != "BIFF" || file_head.version != "V1  " {
-            return Err(format!("invalid file type or version {file_head:?}"));
+            return Err(ErrorKind::Header(file_head));
         }
-        self.c.consume(DWORD_SIZE * 2);
-        let offset = take::<u32>(self.c).ok_or("couldn't read header contents")?;
+        // fixed resources aren't used by the game
+        let [count, _, offset] = take::<[u32; HEADER_SIZE]>(self.c)
+            .ok_or_else(|| ErrorKind::Truncated("header contents".to_owned()))?;
 
-        Ok(offset as usize)
+        Ok((count as usize, offset as usize))
     }
 
-    fn read_resources(&mut self, offset: usize) -> SResult<Vec<Vec<u8>>> {
-        const RESOURCE_SIZE: usize = 4;
+    fn read_resources(&mut self, count: usize, offset: usize) -> ReadResult<Vec<BifResource>> {
         let mut resources = Vec::with_capacity(self.required_indices.len());
 
         for idx in self.required_indices {
-            self.c.seek_to(offset + idx * RESOURCE_SIZE * DWORD_SIZE)?;
-            // skip the id
-            self.c.consume(DWORD_SIZE);
-            let [offset, size] = take::<[u32; 2]>(self.c)
-                .ok_or_else(|| format!("couldn't read resource {idx} offset"))?;
+            if *idx >= count {
+                return Err(format!("resource {idx} requested, BIF contains {count}").into());
+            }
+            let entry_offset = idx
+                .checked_mul(RESOURCE_SIZE * DWORD_SIZE)
+                .and_then(|entry| entry.checked_add(offset))
+                .ok_or_else(|| ErrorKind::Truncated(format!("resource {idx} offset")))?;
+            self.c.seek_to(entry_offset)?;
+            let [id, offset, size, tp] = take::<[u32; RESOURCE_SIZE]>(self.c)
+                .ok_or_else(|| ErrorKind::Truncated(format!("resource {idx} offset")))?;
             self.c.seek_to(offset)?;
             let content = take_bytes(self.c, size as usize)
-                .ok_or_else(|| format!("couldn't read resource {idx} content at offset {offset}"))?
+                .ok_or_else(|| {
+                    ErrorKind::Truncated(format!("resource {idx} content at offset {offset}"))
+                })?
                 .to_vec();
+            self.budget
+                .spend(content.len())
+                .ok_or_else(|| ErrorKind::TooLarge(format!("resource {idx}")))?;
 
-            resources.push(content);
+            resources.push(BifResource {
+                id,
+                tp: ResourceType::from_id(tp as u16),
+                content,
+            });
         }
 
         Ok(resources)
     }
+
+    fn read_entries(&mut self) -> ReadResult<Vec<BifEntry>> {
+        let (count, offset) = self.read_header()?;
+        self.c.seek_to(offset)?;
+        let bytes = count
+            .checked_mul(RESOURCE_SIZE * DWORD_SIZE)
+            .and_then(|len| take_bytes(self.c, len))
+            .ok_or_else(|| ErrorKind::Truncated("resource table".to_owned()))?;
+        let c = &mut Cursor::new(bytes);
+        let len = self.c.get_ref().len();
+        let mut entries = Vec::with_capacity(count);
+
+        for idx in 0..count {
+            let [id, offset, size, tp] = take::<[u32; RESOURCE_SIZE]>(c).unwrap();
+            let (offset, size) = (offset as usize, size as usize);
+            // checked here so the contents can be sliced without any checks later
+            let end = offset.checked_add(size).filter(|end| *end <= len);
+            if end.is_none() {
+                return Err(ErrorKind::Truncated(format!(
+                    "resource {idx} content at offset {offset}"
+                )));
+            }
+
+            entries.push(BifEntry {
+                id,
+                tp: ResourceType::from_id(tp as u16),
+                offset,
+                size,
+            });
+        }
+
+        Ok(entries)
+    }
 }
 
-impl_read_resource!(Bif, Reader, &'a [usize]);
+impl_read_resource!(Bif, Reader, &'a [usize], ResourceType::Bif);
+
+// only the resource table, contents are left in place
+pub fn read_entries(bytes: &[u8]) -> FResult<Vec<BifEntry>> {
+    let c = &mut Cursor::new(bytes);
+    let mut reader = Reader::new(c, &[]);
+    reader.read_entries().map_err(|kind| FormatError {
+        format: ResourceType::Bif,
+        resource: None,
+        offset: Some(reader.c.position()),
+        kind,
+    })
+}
@@ -0,0 +1,51 @@
+use crate::{
+    formats::bif::{Bif, HEADER_SIZE, RESOURCE_SIZE},
+    util::bytes::{IntoByteSlice as _, DWORD_SIZE},
+};
+use std::io::{Cursor, Write};
+
+pub struct Writer {
+    bif: Bif,
+}
+
+impl Writer {
+    fn new(bif: Bif) -> Self {
+        Self { bif }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let resources = self.bif.resources;
+        let count = resources.len();
+        let table_offset = 8 + HEADER_SIZE * DWORD_SIZE;
+        let data_offset = table_offset + count * RESOURCE_SIZE * DWORD_SIZE;
+        let data_len: usize = resources.iter().map(|r| r.content.len()).sum();
+        let mut cursor = Cursor::new(Vec::with_capacity(data_offset + data_len));
+
+        // HEADER
+        cursor.write_all(b"BIFFV1  ").unwrap();
+        // fixed resources are never used
+        cursor
+            .write_all([count as u32, 0, table_offset as u32].into_byte_slice())
+            .unwrap();
+        // VARIABLE RESOURCES
+        let mut data = Vec::with_capacity(data_len);
+        for r in resources {
+            let offset = (data_offset + data.len()) as u32;
+            let size = r.content.len() as u32;
+            data.extend(r.content);
+
+            cursor
+                .write_all([r.id, offset, size, r.tp.to_id() as u32].into_byte_slice())
+                .unwrap();
+        }
+        // DATA
+        cursor.write_all(&data).unwrap();
+
+        cursor.into_inner()
+    }
+}
+
+pub fn write(bif: Bif) -> Vec<u8> {
+    let writer = Writer::new(bif);
+    writer.into_bytes()
+}
@@ -1,10 +1,13 @@
 use crate::formats::{FileHead, LocString, ResourceKey, ResourceType};
+use ahash::RandomState;
+use indexmap::IndexMap;
 use std::fmt;
+use time::OffsetDateTime;
 
 mod read;
 mod write;
 
-use ahash::HashMap;
+pub use read::read_entries;
 pub use write::*;
 
 // 11 DWORD fields + 116 bytes reserved
@@ -32,22 +35,61 @@ impl fmt::Debug for Resource {
     }
 }
 
+// a resource's place in the file, for reading it without copying
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErfEntry {
+    // lowercase
+    pub key: ResourceKey,
+    pub offset: usize,
+    pub size: usize,
+}
+
+// resources are kept in the order of the key list so that writing an unmodified ERF reproduces the original
+pub type Resources = IndexMap<ResourceKey, Resource, RandomState>;
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Erf {
     pub file_head: FileHead,
 
-    pub resources: HashMap<ResourceKey, Resource>,
+    pub resources: Resources,
     pub loc_strings: Vec<LocString>,
     pub description_str_ref: usize,
+    // years since 1900 and days since January 1st, writing keeps them so unmodified ERFs come out the
+    // same, set_build_date is for when the game would update them
+    pub build_year: u32,
+    pub build_day: u32,
+    pub reserved: Vec<u8>,
 }
 
 impl Erf {
+    pub fn new(file_head: FileHead) -> Self {
+        let mut erf = Self {
+            file_head,
+
+            resources: Resources::default(),
+            loc_strings: vec![],
+            description_str_ref: 0,
+            build_year: 0,
+            build_day: 0,
+            reserved: vec![0; HEADER_PADDING_SIZE_BYTES],
+        };
+        erf.set_build_date();
+
+        erf
+    }
+
+    pub fn set_build_date(&mut self) {
+        let now = OffsetDateTime::now_utc();
+        self.build_year = (now.year() - 1900) as u32;
+        self.build_day = now.ordinal() as u32 - 1;
+    }
+
     pub fn get(&self, name: &str, tp: ResourceType) -> Option<&Resource> {
-        self.resources.get(&(name, tp).into())
+        self.resources.get(&ResourceKey::from((name, tp)))
     }
 
     pub fn get_mut(&mut self, name: &str, tp: ResourceType) -> Option<&mut Resource> {
-        self.resources.get_mut(&(name, tp).into())
+        self.resources.get_mut(&ResourceKey::from((name, tp)))
     }
 }
 
@@ -57,41 +99,82 @@ mod tests {
         erf::{write, Erf, Resource},
         LocString, ReadResourceNoArg as _, ResourceType,
     };
-    use ahash::HashMap;
 
     #[test]
     fn read_write() {
-        let erf = Erf {
-            file_head: ("TST ", "V0.0").into(),
-
-            resources: HashMap::from_iter([
-                (
-                    ("pc", ResourceType::Txt).into(),
-                    Resource {
-                        name: "PC".to_owned(),
-                        id: 0,
-                        content: (*b"pc").into(),
-                    },
-                ),
-                (
-                    ("inventory", ResourceType::Txt).into(),
-                    Resource {
-                        name: "INVENTORY".to_owned(),
-                        id: 1,
-                        content: (*b"inventory").into(),
-                    },
-                ),
-            ]),
-
-            loc_strings: vec![LocString {
-                id: 0,
-                content: "LocString".to_owned(),
-            }],
-            description_str_ref: 0,
-        };
+        let mut erf = Erf::new(("TST ", "V0.0").into());
+        // ids and order intentionally don't match, unknown types have to survive too
+        for (name, id, tp) in [
+            ("PC", 1, ResourceType::Txt),
+            ("INVENTORY", 0, ResourceType::Res),
+            ("ZZZ", 5, ResourceType::Unknown(1234)),
+        ] {
+            erf.resources.insert(
+                (name.to_lowercase(), tp).into(),
+                Resource {
+                    name: name.to_owned(),
+                    id,
+                    content: name.as_bytes().into(),
+                },
+            );
+        }
+        erf.loc_strings.push(LocString {
+            id: 0,
+            content: "LocString".to_owned(),
+        });
+        erf.reserved[0] = 1;
+
         let bytes = write(erf.clone());
         let new_erf = Erf::read(&bytes).unwrap();
-
         assert_eq!(erf, new_erf);
+        // maps don't care about the order when compared
+        assert!(erf.resources.keys().eq(new_erf.resources.keys()));
+        assert!(new_erf.get("zzz", ResourceType::from_id(1234)).is_some());
+
+        let new_bytes = write(new_erf);
+        assert_eq!(bytes, new_bytes);
+    }
+
+    #[test]
+    fn source_bytes() {
+        // laid out like the module of a save: no localized strings, data right after the entries
+        let mut bytes = vec![];
+        bytes.extend(b"SAV V1.0");
+        // string count and size, entry count, string, key and resource offsets
+        for dword in [0u32, 0, 2, 0xA0, 0xA0, 0xD0] {
+            bytes.extend(dword.to_le_bytes());
+        }
+        // 2024, October 10th, no description
+        for dword in [124u32, 283, u32::MAX] {
+            bytes.extend(dword.to_le_bytes());
+        }
+        bytes.extend([0; 116]);
+        // keys in id order, which isn't the alphabetical one
+        for (name, id, tp) in [(&b"module"[..], 0u32, 2014u16), (b"end_m01aa", 1, 2023)] {
+            let mut key = [0; 16];
+            key[..name.len()].copy_from_slice(name);
+            bytes.extend(key);
+            bytes.extend(id.to_le_bytes());
+            bytes.extend(tp.to_le_bytes());
+            bytes.extend([0; 2]);
+        }
+        for dword in [0xE0u32, 8, 0xE8, 8] {
+            bytes.extend(dword.to_le_bytes());
+        }
+        bytes.extend(b"IFO V3.2GIT V3.2");
+
+        let mut erf = Erf::read(&bytes).unwrap();
+        assert_eq!((erf.build_year, erf.build_day), (124, 283));
+        assert_eq!(erf.description_str_ref, u32::MAX as usize);
+        let names: Vec<_> = erf.resources.values().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["module", "end_m01aa"]);
+        assert_eq!(write(erf.clone()), bytes);
+
+        // only the date changes
+        erf.set_build_date();
+        let new_bytes = write(erf);
+        assert_ne!(new_bytes[32..40], bytes[32..40]);
+        assert_eq!(new_bytes[..32], bytes[..32]);
+        assert_eq!(new_bytes[40..], bytes[40..]);
     }
 }
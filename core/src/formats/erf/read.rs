@@ -1,18 +1,22 @@
 use crate::{
     formats::{
-        erf::{Erf, Resource, HEADER_SIZE, KEY_NAME_LEN, KEY_SIZE_BYTES},
-        impl_read_resource, FileHead, LocString, ReadResource, ResourceType,
+        erf::{
+            Erf, ErfEntry, Resource, Resources, HEADER_PADDING_SIZE_BYTES, HEADER_SIZE,
+            KEY_NAME_LEN, KEY_SIZE_BYTES,
+        },
+        impl_read_resource, ErrorKind, FResult, FileHead, FormatError, LocString, ReadResource,
+        ReadResult, ResourceType,
     },
     util::{
         bytes::{
-            take, take_bytes, take_head, take_slice, take_string, take_string_trimmed, Cursor,
-            IntoUsizeArray as _, IntoUsizeVec as _, SeekExt as _,
+            capacity, take, take_bytes, take_head, take_slice, take_string_trimmed, take_text,
+            Budget, Cursor, IntoUsizeArray as _, IntoUsizeVec as _, SeekExt as _,
         },
-        SResult,
+        encoding::CodePage,
     },
 };
-use ahash::{HashMap, HashMapExt as _};
-use std::io::BufRead;
+use ahash::RandomState;
+use std::{io::BufRead, mem};
 
 #[derive(Debug, Default)]
 struct Header {
@@ -23,6 +27,9 @@ struct Header {
     keys_offset: usize,
     resources_offset: usize,
     description_str_ref: usize,
+    build_year: u32,
+    build_day: u32,
+    reserved: Vec<u8>,
 }
 
 struct KeyRead {
@@ -39,22 +46,28 @@ struct ResourceRead {
 struct Reader<'a> {
     c: &'a mut Cursor<'a>,
     h: Header,
+    budget: Budget,
 }
 
 impl<'a> Reader<'a> {
     fn new(c: &'a mut Cursor<'a>, _: ()) -> Self {
+        let budget = Budget::new(c.get_ref().len());
         Self {
             c,
             h: Header::default(),
+            budget,
         }
     }
 
-    fn read_header(&mut self) -> SResult<Header> {
-        let head = take_head(self.c).ok_or("couldn't read file head")?;
-        let slice =
-            take_slice::<u32>(self.c, HEADER_SIZE - 2).ok_or("couldn't read header data")?;
-        let [loc_string_count, _, entry_count, loc_string_offset, keys_offset, resources_offset, _, _, description_str_ref] =
+    fn read_header(&mut self) -> ReadResult<Header> {
+        let head = take_head(self.c)?;
+        let slice = take_slice::<u32>(self.c, HEADER_SIZE - 2)
+            .ok_or_else(|| ErrorKind::Truncated("header data".to_owned()))?;
+        let [loc_string_count, _, entry_count, loc_string_offset, keys_offset, resources_offset, build_year, build_day, description_str_ref] =
             slice.into_usize_vec().try_into().unwrap();
+        let reserved = take_bytes(self.c, HEADER_PADDING_SIZE_BYTES)
+            .ok_or_else(|| ErrorKind::Truncated("reserved header bytes".to_owned()))?
+            .to_vec();
 
         Ok(Header {
             file_head: head,
@@ -65,10 +78,13 @@ impl<'a> Reader<'a> {
             keys_offset,
             resources_offset,
             description_str_ref,
+            build_year: build_year as u32,
+            build_day: build_day as u32,
+            reserved,
         })
     }
 
-    fn read(mut self) -> SResult<Erf> {
+    fn read(&mut self) -> ReadResult<Erf> {
         self.h = self.read_header()?;
         let loc_strings = self.read_loc_strings()?;
         let key_list = self.read_key_list()?;
@@ -77,18 +93,50 @@ impl<'a> Reader<'a> {
         self.transform(key_list, &resources, loc_strings)
     }
 
-    fn read_loc_strings(&mut self) -> SResult<Vec<LocString>> {
+    fn read_entries(&mut self) -> ReadResult<Vec<ErfEntry>> {
+        self.h = self.read_header()?;
+        let keys = self.read_key_list()?;
+        let resources = self.read_resources()?;
+        let len = self.c.get_ref().len();
+
+        let mut entries = Vec::with_capacity(keys.len());
+        for (idx, (key, res)) in keys.into_iter().zip(resources).enumerate() {
+            // checked here so the contents can be sliced without any checks later
+            let end = res.offset.checked_add(res.size).filter(|end| *end <= len);
+            if end.is_none() {
+                return Err(ErrorKind::Truncated(format!(
+                    "resource content {idx} at {}",
+                    res.offset
+                )));
+            }
+
+            entries.push(ErfEntry {
+                key: (key.name.to_lowercase(), key.res_type).into(),
+                offset: res.offset,
+                size: res.size,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    fn read_loc_strings(&mut self) -> ReadResult<Vec<LocString>> {
         self.c.seek_to(self.h.loc_string_offset)?;
         let target_count = self.h.loc_string_count;
-        let mut loc_strings = Vec::with_capacity(target_count);
+        // id and length
+        let mut loc_strings = Vec::with_capacity(capacity(self.c, target_count, 8));
 
         let mut count = 0;
         while count < target_count {
             let [id, len] = take::<[u32; 2]>(self.c)
-                .ok_or_else(|| format!("Couldn't read LocStr head {count}"))?;
+                .ok_or_else(|| ErrorKind::Truncated(format!("LocStr head {count}")))?;
 
-            let content = take_string(self.c, len as usize)
-                .ok_or_else(|| format!("Couldn't read LocStr {count}"))?;
+            let content = take_text(
+                self.c,
+                len as usize,
+                CodePage::from_string_id(id, CodePage::default()),
+            )
+            .ok_or_else(|| ErrorKind::Truncated(format!("LocStr {count}")))?;
 
             count += 1;
             loc_strings.push(LocString { id, content });
@@ -97,14 +145,18 @@ impl<'a> Reader<'a> {
         Ok(loc_strings)
     }
 
-    fn read_key_list(&mut self) -> SResult<Vec<KeyRead>> {
+    fn read_key_list(&mut self) -> ReadResult<Vec<KeyRead>> {
         self.c.seek_to(self.h.keys_offset)?;
-        let mut keys = Vec::with_capacity(self.h.entry_count);
-        let bytes = take_bytes(self.c, self.h.entry_count * KEY_SIZE_BYTES)
-            .ok_or("couldn't read key list")?;
+        let bytes = self
+            .h
+            .entry_count
+            .checked_mul(KEY_SIZE_BYTES)
+            .and_then(|len| take_bytes(self.c, len))
+            .ok_or_else(|| ErrorKind::Truncated("key list".to_owned()))?;
         let c = &mut Cursor::new(bytes);
+        let mut keys = Vec::with_capacity(self.h.entry_count);
 
-        for idx in 0..self.h.entry_count {
+        for _ in 0..self.h.entry_count {
             let name = take_string_trimmed(c, KEY_NAME_LEN).unwrap();
             let id = take::<u32>(c).unwrap();
             let res_type = take::<u16>(c).unwrap();
@@ -114,20 +166,18 @@ impl<'a> Reader<'a> {
             keys.push(KeyRead {
                 name,
                 id,
-                res_type: res_type
-                    .try_into()
-                    .map_err(|_| format!("invalid resource type {res_type} in key {idx}"))?,
+                res_type: ResourceType::from_id(res_type),
             });
         }
 
         Ok(keys)
     }
 
-    fn read_resources(&mut self) -> SResult<Vec<ResourceRead>> {
+    fn read_resources(&mut self) -> ReadResult<Vec<ResourceRead>> {
         self.c.seek_to(self.h.resources_offset)?;
+        let resource_dwords = take_slice::<[u32; 2]>(self.c, self.h.entry_count)
+            .ok_or_else(|| ErrorKind::Truncated("resources".to_owned()))?;
         let mut resources = Vec::with_capacity(self.h.entry_count);
-        let resource_dwords =
-            take_slice::<[u32; 2]>(self.c, self.h.entry_count).ok_or("couldn't read resources")?;
 
         for dwords in resource_dwords.iter() {
             let [offset, size] = dwords.into_usize_array();
@@ -138,19 +188,25 @@ impl<'a> Reader<'a> {
     }
 
     fn transform(
-        self,
+        &mut self,
         keys: Vec<KeyRead>,
         resources: &[ResourceRead],
         loc_strings: Vec<LocString>,
-    ) -> SResult<Erf> {
-        let mut result = HashMap::with_capacity(self.h.entry_count);
+    ) -> ReadResult<Erf> {
+        let mut result =
+            Resources::with_capacity_and_hasher(self.h.entry_count, RandomState::new());
 
         for (idx, key) in keys.into_iter().enumerate() {
             let res = &resources[idx];
             self.c.seek_to(res.offset)?;
             let content = take_bytes(self.c, res.size)
-                .ok_or_else(|| format!("Couldn't read resource content {idx} at {}", res.offset))?
+                .ok_or_else(|| {
+                    ErrorKind::Truncated(format!("resource content {idx} at {}", res.offset))
+                })?
                 .to_vec();
+            self.budget
+                .spend(content.len())
+                .ok_or_else(|| ErrorKind::TooLarge(format!("resource content {idx}")))?;
 
             result.insert(
                 (key.name.to_lowercase(), key.res_type).into(),
@@ -162,14 +218,30 @@ impl<'a> Reader<'a> {
             );
         }
 
+        let h = mem::take(&mut self.h);
         Ok(Erf {
-            file_head: self.h.file_head,
+            file_head: h.file_head,
             loc_strings,
-            description_str_ref: self.h.description_str_ref,
+            description_str_ref: h.description_str_ref,
+            build_year: h.build_year,
+            build_day: h.build_day,
+            reserved: h.reserved,
 
             resources: result,
         })
     }
 }
 
-impl_read_resource!(Erf, Reader);
+impl_read_resource!(Erf, Reader, (), ResourceType::Erf);
+
+// only the key list, contents are left in place
+pub fn read_entries(bytes: &[u8]) -> FResult<Vec<ErfEntry>> {
+    let c = &mut Cursor::new(bytes);
+    let mut reader = Reader::new(c, ());
+    reader.read_entries().map_err(|kind| FormatError {
+        format: ResourceType::Erf,
+        resource: None,
+        offset: Some(reader.c.position()),
+        kind,
+    })
+}
@@ -1,15 +1,17 @@
 use crate::{
     formats::{
         erf::{
-            Erf, Resource, HEADER_PADDING_SIZE_BYTES, HEADER_SIZE, KEY_NAME_LEN, KEY_SIZE_BYTES,
+            Erf, HEADER_PADDING_SIZE_BYTES, HEADER_SIZE, KEY_NAME_LEN, KEY_SIZE_BYTES,
             RESOURCE_SIZE,
         },
-        FileHead, ResourceKey, ResourceType,
+        FileHead, ResourceType,
+    },
+    util::{
+        bytes::{bytes_to_sized_bytes, nullpad_string, IntoByteSlice as _, DWORD_SIZE},
+        encoding::CodePage,
     },
-    util::bytes::{bytes_to_sized_bytes, nullpad_string, IntoByteSlice as _, DWORD_SIZE},
 };
 use std::io::{Cursor, Seek, Write};
-use time::{macros::datetime, OffsetDateTime};
 
 struct KeyWrite {
     name: String,
@@ -25,6 +27,9 @@ struct ResourceWrite {
 pub struct Writer {
     file_head: FileHead,
     description_str_ref: u32,
+    build_year: u32,
+    build_day: u32,
+    reserved: Vec<u8>,
 
     loc_strings: Vec<u8>,
     loc_strings_count: u32,
@@ -47,11 +52,7 @@ impl Writer {
         };
         let mut data = Vec::with_capacity(data_len);
 
-        // resort it properly by ids before saving
-        let mut sorted: Vec<(ResourceKey, Resource)> = erf.resources.into_iter().collect();
-        sorted.sort_by_key(|(_, r)| r.id);
-
-        for (key, r) in sorted {
+        for (key, r) in erf.resources {
             keys.push(KeyWrite {
                 name: nullpad_string(r.name, KEY_NAME_LEN),
                 id: r.id,
@@ -68,12 +69,17 @@ impl Writer {
         let loc_strings_count = erf.loc_strings.len() as u32;
         for str in erf.loc_strings {
             loc_strings.extend(str.id.to_le_bytes());
-            loc_strings.extend(bytes_to_sized_bytes::<DWORD_SIZE>(str.content.as_bytes()));
+            let content =
+                CodePage::from_string_id(str.id, CodePage::default()).encode(&str.content);
+            loc_strings.extend(bytes_to_sized_bytes::<DWORD_SIZE>(&content));
         }
 
         Self {
             file_head: erf.file_head,
             description_str_ref: erf.description_str_ref as u32,
+            build_year: erf.build_year,
+            build_day: erf.build_day,
+            reserved: erf.reserved,
 
             loc_strings,
             loc_strings_count,
@@ -109,7 +115,7 @@ impl Writer {
         for key in self.keys {
             cursor.write_all(key.name.as_bytes()).unwrap();
             cursor.write_all(&key.id.to_le_bytes()).unwrap();
-            cursor.write_all(&(key.tp as u16).to_le_bytes()).unwrap();
+            cursor.write_all(&key.tp.to_id().to_le_bytes()).unwrap();
             cursor.write_all(&[0; 2]).unwrap();
         }
         // RESOURCES
@@ -127,13 +133,8 @@ impl Writer {
         // HEADER
         let file_head = self.file_head;
         let description_str_ref = self.description_str_ref;
-
-        // build date
-        let now = OffsetDateTime::now_utc();
-        let past = datetime!(1900 - 01 - 01 0:00 UTC);
-        let build_year = now.year() - past.year();
-        past.replace_year(now.year()).unwrap();
-        let build_day = (now - past).whole_days();
+        let mut reserved = self.reserved;
+        reserved.resize(HEADER_PADDING_SIZE_BYTES, 0);
 
         cursor.rewind().unwrap();
         cursor.write_all(file_head.tp.as_bytes()).unwrap();
@@ -147,13 +148,14 @@ impl Writer {
                     loc_string_offset as u32,
                     keys_offset as u32,
                     resources_offset as u32,
-                    build_year as u32,
-                    build_day as u32,
+                    self.build_year,
+                    self.build_day,
                     description_str_ref,
                 ]
                 .into_byte_slice(),
             )
             .unwrap();
+        cursor.write_all(&reserved).unwrap();
 
         cursor.into_inner()
     }
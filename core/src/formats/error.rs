@@ -0,0 +1,124 @@
+use crate::formats::{FileHead, ResourceType};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    // the file type or version isn't what the format expects
+    Header(FileHead),
+    // the data ends before the named part, or an offset points past it
+    Truncated(String),
+    // the data is there but doesn't make sense
+    Invalid(String),
+    // reading the named part would take more memory or nesting than the file could reasonably need
+    TooLarge(String),
+    // a resource that's required couldn't be found
+    Missing,
+    // the file itself couldn't be read
+    Io(String),
+    // a resource contained in this one couldn't be read
+    Nested(Box<FormatError>),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Header(head) => write!(
+                f,
+                "invalid file type or version {:?} {:?}",
+                head.tp, head.version
+            ),
+            Self::Truncated(part) => write!(f, "couldn't read {part}"),
+            Self::TooLarge(part) => write!(f, "{part} is too large"),
+            Self::Invalid(reason) | Self::Io(reason) => f.write_str(reason),
+            Self::Missing => f.write_str("not found"),
+            Self::Nested(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+// readers describe problems with plain strings where there's nothing more specific to say
+impl From<String> for ErrorKind {
+    fn from(reason: String) -> Self {
+        Self::Invalid(reason)
+    }
+}
+
+impl From<&str> for ErrorKind {
+    fn from(reason: &str) -> Self {
+        Self::Invalid(reason.to_owned())
+    }
+}
+
+impl From<FormatError> for ErrorKind {
+    fn from(err: FormatError) -> Self {
+        Self::Nested(Box::new(err))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatError {
+    pub format: ResourceType,
+    // file path or resource name, when it's known
+    pub resource: Option<String>,
+    // cursor position when reading stopped, close to the problem but not necessarily on it
+    pub offset: Option<u64>,
+    pub kind: ErrorKind,
+}
+
+pub type FResult<T> = Result<T, FormatError>;
+pub(crate) type ReadResult<T> = Result<T, ErrorKind>;
+
+impl FormatError {
+    pub fn new(format: ResourceType, kind: impl Into<ErrorKind>) -> Self {
+        Self {
+            format,
+            resource: None,
+            offset: None,
+            kind: kind.into(),
+        }
+    }
+
+    pub fn missing(format: ResourceType, resource: impl Into<String>) -> Self {
+        Self::new(format, ErrorKind::Missing).with_resource(resource)
+    }
+
+    // the data was read but its contents aren't what's expected
+    pub fn invalid(format: ResourceType, resource: impl Into<String>, reason: String) -> Self {
+        Self::new(format, ErrorKind::Invalid(reason)).with_resource(resource)
+    }
+
+    #[must_use]
+    pub fn with_resource(mut self, resource: impl Into<String>) -> Self {
+        self.resource = Some(resource.into());
+        self
+    }
+
+    // the error at the bottom of the chain of nested resources
+    pub fn root(&self) -> &Self {
+        match &self.kind {
+            ErrorKind::Nested(err) => err.root(),
+            _ => self,
+        }
+    }
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format.to_extension().to_uppercase())?;
+        if let Some(resource) = &self.resource {
+            write!(f, " {resource}")?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {offset}")?;
+        }
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<FormatError> for String {
+    fn from(err: FormatError) -> Self {
+        err.to_string()
+    }
+}
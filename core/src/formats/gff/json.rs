@@ -0,0 +1,303 @@
+use crate::{
+    formats::{
+        gff::{Field, Fields, Gff, Orientation, Struct, Vector},
+        FileHead, LocString,
+    },
+    util::{encoding::CodePage, SResult},
+};
+use ahash::RandomState;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+
+// The text form of a GFF, meant to be read, diffed and edited by hand:
+//
+// {
+//   "file_type": "IFO ",
+//   "file_version": "V3.2",
+//   "code_page": "Windows1252",
+//   "content": { "type": 4294967295, "fields": { "Mod_Name": { "type": "LocString", "value": ... } } }
+// }
+//
+// Every field is a {"type", "value"} pair, the type being the Field variant name, except for
+// BStruct which is just Struct:
+// - Byte, Char, Word, Short, Dword, Int, Dword64, Int64: plain numbers
+// - Float, Double: numbers, non-finite ones are the hex of their bits ("0x7fc00000")
+// - String, ResRef: strings
+// - LocString: {"str_ref": 4294967295, "strings": [{"id": 0, "content": "..."}]},
+//   where id is language * 2 + gender
+// - Void: lowercase hex string
+// - Struct: {"type": 0, "fields": {...}}, List: array of those
+// - Orientation: {"w", "x", "y", "z"}, Vector: {"x", "y", "z"}, both made of floats
+//
+// Field order is kept, so converting back and writing gives the original bytes.
+
+#[derive(Serialize, Deserialize)]
+struct JsonGff {
+    file_type: String,
+    file_version: String,
+    // plain strings are decoded with it, it has to match to get the same bytes back
+    #[serde(default)]
+    code_page: CodePage,
+    content: JsonStruct,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonStruct {
+    #[serde(rename = "type")]
+    tp: u32,
+    fields: IndexMap<String, JsonField, RandomState>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonLocString {
+    str_ref: u32,
+    strings: Vec<LocString>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JsonFloat {
+    Number(f32),
+    Bits(String),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JsonDouble {
+    Number(f64),
+    Bits(String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonOrientation {
+    w: JsonFloat,
+    x: JsonFloat,
+    y: JsonFloat,
+    z: JsonFloat,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonVector {
+    x: JsonFloat,
+    y: JsonFloat,
+    z: JsonFloat,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
+enum JsonField {
+    Byte(u8),
+    Char(i8),
+    Word(u16),
+    Short(i16),
+    Dword(u32),
+    Int(i32),
+    Dword64(u64),
+    Int64(i64),
+    Float(JsonFloat),
+    Double(JsonDouble),
+    String(String),
+    ResRef(String),
+    LocString(JsonLocString),
+    Void(String),
+    Struct(JsonStruct),
+    List(Vec<JsonStruct>),
+    Orientation(JsonOrientation),
+    Vector(JsonVector),
+}
+
+impl JsonFloat {
+    fn new(v: f32) -> Self {
+        if v.is_finite() {
+            Self::Number(v)
+        } else {
+            Self::Bits(format!("{:#010x}", v.to_bits()))
+        }
+    }
+
+    fn into_value(self) -> SResult<f32> {
+        match self {
+            Self::Number(v) => Ok(v),
+            Self::Bits(bits) => parse_bits(&bits, u32::from_str_radix).map(f32::from_bits),
+        }
+    }
+}
+
+impl JsonDouble {
+    fn new(v: f64) -> Self {
+        if v.is_finite() {
+            Self::Number(v)
+        } else {
+            Self::Bits(format!("{:#018x}", v.to_bits()))
+        }
+    }
+
+    fn into_value(self) -> SResult<f64> {
+        match self {
+            Self::Number(v) => Ok(v),
+            Self::Bits(bits) => parse_bits(&bits, u64::from_str_radix).map(f64::from_bits),
+        }
+    }
+}
+
+fn parse_bits<T, E>(bits: &str, parse: impl Fn(&str, u32) -> Result<T, E>) -> SResult<T> {
+    bits.strip_prefix("0x")
+        .and_then(|b| parse(b, 16).ok())
+        .ok_or_else(|| format!("invalid float bits {bits}"))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        write!(hex, "{b:02x}").unwrap();
+    }
+    hex
+}
+
+fn from_hex(hex: &str) -> SResult<Vec<u8>> {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| format!("invalid hex string {hex}"))
+        })
+        .collect()
+}
+
+impl JsonStruct {
+    fn new(s: &Struct) -> SResult<Self> {
+        let mut fields = IndexMap::with_capacity_and_hasher(s.fields.len(), RandomState::new());
+        for (label, field) in &s.fields {
+            let field = JsonField::new(field).map_err(|err| format!("{label}: {err}"))?;
+            fields.insert(label.clone(), field);
+        }
+
+        Ok(Self { tp: s.tp, fields })
+    }
+
+    fn into_struct(self) -> SResult<Struct> {
+        let mut fields = Fields::with_capacity_and_hasher(self.fields.len(), RandomState::new());
+        for (label, field) in self.fields {
+            let field = field
+                .into_field()
+                .map_err(|err| format!("{label}: {err}"))?;
+            fields.insert(label, field);
+        }
+
+        Ok(Struct {
+            tp: self.tp,
+            fields,
+        })
+    }
+}
+
+impl JsonField {
+    fn new(field: &Field) -> SResult<Self> {
+        Ok(match field {
+            Field::Byte(v) => Self::Byte(*v),
+            Field::Char(v) => Self::Char(*v),
+            Field::Word(v) => Self::Word(*v),
+            Field::Short(v) => Self::Short(*v),
+            Field::Dword(v) => Self::Dword(*v),
+            Field::Int(v) => Self::Int(*v),
+            Field::Dword64(v) => Self::Dword64(*v),
+            Field::Int64(v) => Self::Int64(*v),
+            Field::Float(v) => Self::Float(JsonFloat::new(*v)),
+            Field::Double(v) => Self::Double(JsonDouble::new(*v)),
+            Field::String(v) => Self::String(v.clone()),
+            Field::ResRef(v) => Self::ResRef(v.clone()),
+            Field::LocString((str_ref, strings)) => Self::LocString(JsonLocString {
+                str_ref: *str_ref,
+                strings: strings.clone(),
+            }),
+            Field::Void(v) => Self::Void(to_hex(v)),
+            Field::BStruct(s) => Self::Struct(JsonStruct::new(s)?),
+            Field::List(list) => {
+                Self::List(list.iter().map(JsonStruct::new).collect::<Result<_, _>>()?)
+            }
+            Field::Orientation(Orientation { w, x, y, z }) => Self::Orientation(JsonOrientation {
+                w: JsonFloat::new(*w),
+                x: JsonFloat::new(*x),
+                y: JsonFloat::new(*y),
+                z: JsonFloat::new(*z),
+            }),
+            Field::Vector(Vector { x, y, z }) => Self::Vector(JsonVector {
+                x: JsonFloat::new(*x),
+                y: JsonFloat::new(*y),
+                z: JsonFloat::new(*z),
+            }),
+            Field::Invalid => return Err("field has no value".to_owned()),
+        })
+    }
+
+    fn into_field(self) -> SResult<Field> {
+        Ok(match self {
+            Self::Byte(v) => Field::Byte(v),
+            Self::Char(v) => Field::Char(v),
+            Self::Word(v) => Field::Word(v),
+            Self::Short(v) => Field::Short(v),
+            Self::Dword(v) => Field::Dword(v),
+            Self::Int(v) => Field::Int(v),
+            Self::Dword64(v) => Field::Dword64(v),
+            Self::Int64(v) => Field::Int64(v),
+            Self::Float(v) => Field::Float(v.into_value()?),
+            Self::Double(v) => Field::Double(v.into_value()?),
+            Self::String(v) => Field::String(v),
+            Self::ResRef(v) => Field::ResRef(v),
+            Self::LocString(JsonLocString { str_ref, strings }) => {
+                Field::LocString((str_ref, strings))
+            }
+            Self::Void(v) => Field::Void(from_hex(&v)?),
+            Self::Struct(s) => Field::BStruct(Box::new(s.into_struct()?)),
+            Self::List(list) => Field::List(
+                list.into_iter()
+                    .enumerate()
+                    .map(|(idx, s)| s.into_struct().map_err(|err| format!("[{idx}] {err}")))
+                    .collect::<SResult<_>>()?,
+            ),
+            Self::Orientation(JsonOrientation { w, x, y, z }) => Field::Orientation(Orientation {
+                w: w.into_value()?,
+                x: x.into_value()?,
+                y: y.into_value()?,
+                z: z.into_value()?,
+            }),
+            Self::Vector(JsonVector { x, y, z }) => Field::Vector(Vector {
+                x: x.into_value()?,
+                y: y.into_value()?,
+                z: z.into_value()?,
+            }),
+        })
+    }
+}
+
+pub fn to_json(gff: &Gff) -> SResult<String> {
+    let json = JsonGff {
+        file_type: gff.file_head.tp.clone(),
+        file_version: gff.file_head.version.clone(),
+        code_page: gff.code_page,
+        content: JsonStruct::new(&gff.content).map_err(|err| format!("Gff::to_json| {err}"))?,
+    };
+
+    serde_json::to_string_pretty(&json).map_err(|err| format!("Gff::to_json| {err}"))
+}
+
+pub fn from_json(json: &str) -> SResult<Gff> {
+    let json: JsonGff =
+        serde_json::from_str(json).map_err(|err| format!("Gff::from_json| {err}"))?;
+
+    Ok(Gff {
+        file_head: FileHead {
+            tp: json.file_type,
+            version: json.file_version,
+        },
+        content: json
+            .content
+            .into_struct()
+            .map_err(|err| format!("Gff::from_json| {err}"))?,
+        code_page: json.code_page,
+    })
+}
@@ -1,6 +1,7 @@
 use super::{FileHead, LocString};
-use crate::util::SResult;
-use ahash::HashMap;
+use crate::util::{encoding::CodePage, SResult};
+use ahash::RandomState;
+use indexmap::IndexMap;
 use macros::{EnumToInt, EnumToString, UnwrapVariant};
 use serde::{Deserialize, Serialize};
 use std::{
@@ -8,9 +9,15 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
+mod json;
+mod path;
 mod read;
+mod validate;
 mod write;
 
+pub use json::*;
+pub use path::*;
+pub use validate::*;
 pub use write::*;
 
 // 7 pairs of DWORDS
@@ -20,6 +27,9 @@ const STRUCT_SIZE: usize = 3;
 // 3 DWORDS
 const FIELD_SIZE: usize = 3;
 
+// fields are kept in source order so that writing an unmodified struct reproduces the original
+pub type Fields = IndexMap<String, Field, RandomState>;
+
 #[derive(Debug)]
 enum FieldTmp {
     // simple value
@@ -75,7 +85,7 @@ pub enum Field {
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Struct {
     pub tp: u32,
-    pub fields: HashMap<String, Field>,
+    pub fields: Fields,
 }
 impl Struct {
     pub fn new(fields: Vec<(&str, Field)>) -> Self {
@@ -129,6 +139,18 @@ impl Struct {
 pub struct Gff {
     pub file_head: FileHead,
     pub content: Struct,
+    // for plain strings, LocStrings are encoded according to their language
+    pub code_page: CodePage,
+}
+
+impl Gff {
+    pub fn new(file_head: FileHead, content: Struct) -> Self {
+        Self {
+            file_head,
+            content,
+            code_page: CodePage::default(),
+        }
+    }
 }
 
 impl Deref for Gff {
@@ -146,11 +168,16 @@ impl DerefMut for Gff {
 
 #[cfg(test)]
 mod tests {
-    use crate::formats::{
-        gff::{write, Field, Gff, LocString, Struct},
-        ReadResourceNoArg,
+    use crate::{
+        formats::{
+            gff::{
+                from_json, to_json, validate, write, Field, Gff, Issue, LocString, Orientation,
+                PathError, Reference, Struct, Table, Vector,
+            },
+            ErrorKind, ReadResourceNoArg as _, ResourceType,
+        },
+        util::encoding::CodePage,
     };
-    use ahash::HashMap;
 
     #[test]
     fn field_value_type() {
@@ -161,9 +188,9 @@ mod tests {
 
     #[test]
     fn read_write() {
-        let gff = Gff {
-            file_head: ("TST ", "V0.0").into(),
-            content: Struct::new(vec![
+        let gff = Gff::new(
+            ("TST ", "V0.0").into(),
+            Struct::new(vec![
                 ("Byte", Field::Byte(u8::MAX)),
                 ("Char", Field::Char(i8::MIN)),
                 ("Word", Field::Word(u16::MAX)),
@@ -189,22 +216,417 @@ mod tests {
                 ("Void", Field::Void(vec![0, 1, 2, 3])),
                 (
                     "Struct",
-                    Field::BStruct(Box::new(Struct {
-                        tp: 2,
-                        fields: HashMap::from_iter([("NESTED".to_owned(), Field::Byte(0))]),
-                    })),
+                    Field::BStruct(Box::new(Struct::with_type(
+                        2,
+                        vec![
+                            ("NESTED", Field::Byte(0)),
+                            (
+                                "NESTED_EMPTY",
+                                Field::BStruct(Box::new(Struct::new(vec![]))),
+                            ),
+                        ],
+                    ))),
                 ),
                 (
                     "List",
-                    Field::List(vec![Struct {
-                        tp: 0,
-                        fields: HashMap::from_iter([("LIST_NESTED".to_owned(), Field::Byte(0))]),
-                    }]),
+                    Field::List(vec![
+                        Struct::new(vec![("LIST_NESTED", Field::Byte(0))]),
+                        Struct::with_type(
+                            3,
+                            vec![("LIST_NESTED", Field::Byte(1)), ("Byte", Field::Byte(2))],
+                        ),
+                    ]),
                 ),
+                ("After", Field::Word(1)),
             ]),
-        };
+        );
         let bytes = write(gff.clone());
         let new_gff = Gff::read(&bytes).unwrap();
         assert_eq!(gff, new_gff);
+        // maps don't care about the order when compared
+        assert!(gff.fields.keys().eq(new_gff.fields.keys()));
+
+        let new_bytes = write(new_gff);
+        assert_eq!(bytes, new_bytes);
+    }
+
+    #[test]
+    fn field_order() {
+        let mut s = Struct::new(vec![("B", Field::Byte(0)), ("A", Field::Byte(1))]);
+        s.insert("C", Field::Byte(2));
+        // replacing keeps the original position
+        s.insert("B", Field::Byte(3));
+
+        let bytes = write(Gff::new(("TST ", "V0.0").into(), s));
+        let gff = Gff::read(&bytes).unwrap();
+        assert!(gff.fields.keys().eq(["B", "A", "C"]));
+        assert_eq!(gff.fields["B"], Field::Byte(3));
+    }
+
+    #[test]
+    fn code_pages() {
+        use crate::formats::ReadResource;
+
+        let mut gff = Gff::new(
+            ("TST ", "V0.0").into(),
+            Struct::new(vec![
+                ("Name", Field::String("Бастила".to_owned())),
+                (
+                    "Descr",
+                    Field::LocString((
+                        u32::MAX,
+                        vec![
+                            // Polish
+                            LocString {
+                                id: 10,
+                                content: "Żółć".to_owned(),
+                            },
+                            // English, but translated
+                            LocString {
+                                id: 0,
+                                content: "Меч".to_owned(),
+                            },
+                        ],
+                    )),
+                ),
+            ]),
+        );
+        // no language ID maps to it, it has to be picked by name
+        gff.code_page = CodePage::from_name("WINDOWS-1251").unwrap();
+        assert_eq!(gff.code_page.to_string(), "windows-1251");
+
+        let bytes = write(gff.clone());
+        // single byte per character
+        assert!(bytes
+            .windows(7)
+            .any(|w| w == CodePage::Windows1251.encode("Бастила")));
+        assert!(bytes.windows(4).any(|w| w == [0xAF, 0xF3, 0xB3, 0xE6]));
+        assert!(bytes.windows(3).any(|w| w == [0xCC, 0xE5, 0xF7]));
+        let read = <Gff as ReadResource<_>>::read;
+        assert_eq!(gff, read(&bytes, CodePage::Windows1251).unwrap());
+
+        // characters the code page doesn't have can't survive
+        gff.code_page = CodePage::Windows1252;
+        let gff = read(&write(gff), CodePage::Windows1252).unwrap();
+        assert_eq!(gff.fields["Name"], Field::String("???????".to_owned()));
+    }
+
+    #[test]
+    fn json() {
+        let mut gff = Gff::new(
+            ("TST ", "V0.0").into(),
+            Struct::with_type(
+                u32::MAX,
+                vec![
+                    ("Z_First", Field::Int64(i64::MIN)),
+                    ("Dword64", Field::Dword64(u64::MAX)),
+                    ("Float", Field::Float(0.1)),
+                    ("NaN", Field::Float(f32::NAN)),
+                    ("Inf", Field::Double(f64::NEG_INFINITY)),
+                    ("Name", Field::String("Бастила".to_owned())),
+                    (
+                        "LocString",
+                        Field::LocString((
+                            12,
+                            vec![LocString {
+                                id: 10,
+                                content: "Żółć".to_owned(),
+                            }],
+                        )),
+                    ),
+                    ("Void", Field::Void(vec![0, 0xAB, 0xFF])),
+                    (
+                        "Orientation",
+                        Field::Orientation(Orientation {
+                            w: 1.,
+                            x: -0.,
+                            y: 0.5,
+                            z: f32::INFINITY,
+                        }),
+                    ),
+                    (
+                        "List",
+                        Field::List(vec![Struct::with_type(
+                            3,
+                            vec![(
+                                "Nested",
+                                Field::BStruct(Box::new(Struct::new(vec![(
+                                    "Position",
+                                    Field::Vector(Vector {
+                                        x: 1.,
+                                        y: 2.,
+                                        z: 3.,
+                                    }),
+                                )]))),
+                            )],
+                        )]),
+                    ),
+                ],
+            ),
+        );
+        gff.code_page = CodePage::Windows1251;
+        let bytes = write(gff.clone());
+
+        let json = to_json(&gff).unwrap();
+        assert!(json.contains(r#""value": "00abff""#));
+        assert!(json.contains(r#""value": 0.1"#));
+        assert!(json.contains(r#""value": "0x7fc00000""#));
+        // the variant name, but without the box
+        assert!(json.contains(r#""type": "Struct""#));
+        assert!(!json.contains("BStruct"));
+        let new_gff = from_json(&json).unwrap();
+        assert!(new_gff.fields.keys().eq(gff.fields.keys()));
+        assert_eq!(new_gff.code_page, CodePage::Windows1251);
+        // NaN isn't equal to itself, the bytes have to be
+        assert_eq!(write(new_gff.clone()), bytes);
+        assert_eq!(to_json(&new_gff).unwrap(), json);
+
+        let broken = json.replace("00abff", "00abf");
+        let err = from_json(&broken).unwrap_err();
+        assert!(err.contains("Void: invalid hex string"), "{err}");
+
+        // written by hand
+        let nested = r#"{ "type": "Struct", "value": { "type": 1, "fields": {} } }"#;
+        let nested_json = format!(
+            r#"{{ "file_type": "TST ", "file_version": "V0.0", "content": {{ "type": 0, "fields": {{ "Nested": {nested} }} }} }}"#
+        );
+        let nested_gff = from_json(&nested_json).unwrap();
+        assert_eq!(
+            nested_gff.fields["Nested"],
+            Field::BStruct(Box::new(Struct::with_type(1, vec![])))
+        );
+        assert!(from_json(&nested_json.replace("Struct", "BStruct")).is_err());
+    }
+
+    #[test]
+    fn paths() {
+        let class = |id| Struct::new(vec![("Class", Field::Int(id))]);
+        let mut s = Struct::new(vec![(
+            "Mod_PlayerList",
+            Field::List(vec![Struct::new(vec![
+                ("ClassList", Field::List(vec![class(0), class(4)])),
+                ("Door List", Field::BStruct(Box::new(class(1)))),
+            ])]),
+        )]);
+
+        assert_eq!(
+            s.get_path("Mod_PlayerList[0].ClassList[1].Class"),
+            Ok(&Field::Int(4))
+        );
+        assert_eq!(
+            s.get_path("Mod_PlayerList[0].Door List.Class"),
+            Ok(&Field::Int(1))
+        );
+        assert_eq!(
+            s.get_struct_path("Mod_PlayerList[0].ClassList[0]"),
+            Ok(&class(0))
+        );
+
+        let err = |path| s.get_path(path).unwrap_err();
+        assert_eq!(
+            err("Mod_PlayerList[0].ClassList[2].Class"),
+            PathError::OutOfBounds {
+                segment: "Mod_PlayerList[0].ClassList[2]".to_owned(),
+                len: 2
+            }
+        );
+        assert_eq!(
+            err("Mod_PlayerList[0].FeatList"),
+            PathError::MissingField {
+                segment: "Mod_PlayerList[0].FeatList".to_owned()
+            }
+        );
+        assert_eq!(
+            err("Mod_PlayerList[0].ClassList[0].Class.Inner"),
+            PathError::NotAStruct {
+                segment: "Mod_PlayerList[0].ClassList[0].Class".to_owned()
+            }
+        );
+        assert_eq!(
+            err("Mod_PlayerList.ClassList"),
+            PathError::NotAStruct {
+                segment: "Mod_PlayerList".to_owned()
+            }
+        );
+        assert_eq!(
+            err("Mod_PlayerList[0]"),
+            PathError::NotAField {
+                segment: "Mod_PlayerList[0]".to_owned()
+            }
+        );
+        for (path, position) in [("", 0), ("A.", 1), ("A[x]", 2), ("A[0", 1), ("A..B", 2)] {
+            assert_eq!(
+                err(path),
+                PathError::Syntax {
+                    path: path.to_owned(),
+                    position
+                }
+            );
+        }
+        assert_eq!(
+            err("Mod_PlayerList[0].ClassList[5]").to_string(),
+            "Mod_PlayerList[0].ClassList[5] is out of bounds, the list has 2 elements"
+        );
+
+        let old = s
+            .set_path("Mod_PlayerList[0].ClassList[1].Class", Field::Int(5))
+            .unwrap();
+        assert_eq!(old, Field::Int(4));
+        assert!(s
+            .set_path("Mod_PlayerList[0].Missing", Field::Int(0))
+            .is_err());
+
+        s.insert_path("Mod_PlayerList[0].ClassList[1].Level", Field::Byte(2))
+            .unwrap();
+        s.insert_path(
+            "Mod_PlayerList[0].ClassList[0]",
+            Field::BStruct(Box::new(class(2))),
+        )
+        .unwrap();
+        assert!(s
+            .insert_path("Mod_PlayerList[0].ClassList[0]", Field::Int(0))
+            .is_err());
+        assert_eq!(
+            s.get_struct_path("Mod_PlayerList[0].ClassList[2]"),
+            Ok(&Struct::new(vec![
+                ("Class", Field::Int(5)),
+                ("Level", Field::Byte(2))
+            ]))
+        );
+
+        let removed = s.remove_path("Mod_PlayerList[0].ClassList[0]").unwrap();
+        assert_eq!(removed, Field::BStruct(Box::new(class(2))));
+        s.remove_path("Mod_PlayerList[0].ClassList").unwrap();
+        assert!(s
+            .get_struct_path("Mod_PlayerList[0]")
+            .unwrap()
+            .fields
+            .keys()
+            .eq(["Door List"]));
+    }
+
+    #[test]
+    fn read_errors() {
+        let bytes = write(Gff::new(
+            ("TST ", "V0.0").into(),
+            Struct::new(vec![("Field", Field::Dword(1))]),
+        ));
+
+        let err = Gff::read(&bytes[..20]).unwrap_err();
+        assert_eq!(err.format, ResourceType::Gff);
+        assert_eq!(err.offset, Some(8));
+        assert_eq!(err.kind, ErrorKind::Truncated("header data".to_owned()));
+        assert_eq!(
+            err.to_string(),
+            "GFF at offset 8: couldn't read header data"
+        );
+
+        // label count past the end of the data
+        let mut broken = bytes.clone();
+        broken[28..32].copy_from_slice(&100u32.to_le_bytes());
+        let err = Gff::read(&broken).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Truncated(_)), "{err}");
+        assert!(err.offset.is_some());
+    }
+
+    #[test]
+    fn validation() {
+        let bytes = write(Gff::new(
+            ("TST ", "V0.0").into(),
+            Struct::new(vec![
+                ("A", Field::Dword(1)),
+                ("B", Field::String("b".to_owned())),
+                (
+                    "S",
+                    Field::BStruct(Box::new(Struct::new(vec![("C", Field::Byte(1))]))),
+                ),
+                (
+                    "L",
+                    Field::List(vec![Struct::new(vec![("C", Field::Byte(2))])]),
+                ),
+            ]),
+        ));
+        assert!(validate(&bytes).unwrap().is_valid());
+        assert!(validate(&bytes[..20]).is_err());
+
+        let dword_at = |offset: usize| {
+            u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize
+        };
+        let field_offset = dword_at(16);
+        let mut broken = bytes.clone();
+        // field A gets a missing label, B gets A's label, S points to a missing struct
+        broken[field_offset + 4..field_offset + 8].copy_from_slice(&99u32.to_le_bytes());
+        broken[field_offset + 16..field_offset + 20].copy_from_slice(&0u32.to_le_bytes());
+        broken[field_offset + 32..field_offset + 36].copy_from_slice(&50u32.to_le_bytes());
+        broken.extend([0; 4]);
+        let issues = validate(&broken).unwrap().issues;
+        assert_eq!(
+            issues,
+            [
+                Issue::TrailingBytes {
+                    offset: bytes.len(),
+                    len: 4
+                },
+                Issue::DanglingLabel {
+                    field: 0,
+                    label: 99
+                },
+                Issue::DanglingStruct {
+                    field: 2,
+                    target: 50
+                },
+            ]
+        );
+
+        let mut broken = bytes.clone();
+        broken[field_offset + 4..field_offset + 8].copy_from_slice(&1u32.to_le_bytes());
+        // field data length past the end of the file
+        broken[36..40].copy_from_slice(&1000u32.to_le_bytes());
+        let issues = validate(&broken).unwrap().issues;
+        assert_eq!(
+            issues,
+            [
+                Issue::TableOutOfBounds {
+                    table: Table::FieldData,
+                    offset: dword_at(32),
+                    len: 1000
+                },
+                Issue::Overlap {
+                    first: Table::FieldData,
+                    second: Table::FieldIndices
+                },
+                Issue::Overlap {
+                    first: Table::FieldData,
+                    second: Table::ListIndices
+                },
+                Issue::DuplicateLabel {
+                    target: 0,
+                    label: "B".to_owned()
+                },
+            ]
+        );
+
+        let struct_offset = dword_at(8);
+        let mut broken = bytes.clone();
+        // S points back to the top level struct, the list item uses the field of S's struct
+        broken[field_offset + 32..field_offset + 36].copy_from_slice(&0u32.to_le_bytes());
+        let (first, second) = (struct_offset + 12, struct_offset + 24);
+        broken.copy_within(first + 4..first + 8, second + 4);
+        let issues = validate(&broken).unwrap().issues;
+        assert_eq!(
+            issues,
+            [
+                Issue::SharedReference {
+                    reference: Reference::Field(dword_at(first + 4)),
+                    count: 2
+                },
+                Issue::SharedReference {
+                    reference: Reference::Struct(0),
+                    count: 2
+                },
+                Issue::Cycle { target: 0 },
+            ]
+        );
+        assert!(Gff::read(&broken).is_err());
     }
 }
@@ -0,0 +1,387 @@
+use crate::formats::gff::{Field, Struct};
+use std::fmt;
+
+// Paths address nested fields the way the game's structure looks:
+// "Mod_PlayerList[0].ClassList[1].KnownList0" is the KnownList0 field of the second class
+// of the first player struct. Labels can contain anything but '.', '[' and ']' (spaces included),
+// list elements are structs so an index can only be followed by a label.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathError {
+    // the path itself is malformed, position is the byte offset of the problem
+    Syntax { path: String, position: usize },
+    MissingField { segment: String },
+    OutOfBounds { segment: String, len: usize },
+    NotAStruct { segment: String },
+    NotAList { segment: String },
+    // list elements are structs and can't be used as fields
+    NotAField { segment: String },
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax { path, position } => write!(f, "invalid path {path} at {position}"),
+            Self::MissingField { segment } => write!(f, "missing field {segment}"),
+            Self::OutOfBounds { segment, len } => {
+                write!(f, "{segment} is out of bounds, the list has {len} elements")
+            }
+            Self::NotAStruct { segment } => write!(f, "{segment} isn't a struct"),
+            Self::NotAList { segment } => write!(f, "{segment} isn't a list"),
+            Self::NotAField { segment } => write!(f, "{segment} is a list element, not a field"),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+impl From<PathError> for String {
+    fn from(err: PathError) -> Self {
+        err.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Segment<'p> {
+    Label(&'p str),
+    Index(usize),
+}
+
+type PathSegment<'p> = (Segment<'p>, usize);
+
+struct Path<'p> {
+    raw: &'p str,
+    // with the end position of each segment for errors
+    segments: Vec<PathSegment<'p>>,
+}
+
+impl<'p> Path<'p> {
+    fn parse(raw: &'p str) -> Result<Self, PathError> {
+        let syntax = |position| PathError::Syntax {
+            path: raw.to_owned(),
+            position,
+        };
+        let bytes = raw.as_bytes();
+        let mut segments = vec![];
+        let mut pos = 0;
+
+        while pos < bytes.len() {
+            let start = pos;
+            while pos < bytes.len() && !matches!(bytes[pos], b'.' | b'[' | b']') {
+                pos += 1;
+            }
+            if pos == start {
+                return Err(syntax(pos));
+            }
+            segments.push((Segment::Label(&raw[start..pos]), pos));
+
+            while pos < bytes.len() && bytes[pos] == b'[' {
+                let start = pos + 1;
+                let end = raw[start..]
+                    .find(']')
+                    .map(|len| start + len)
+                    .ok_or_else(|| syntax(pos))?;
+                let idx = raw[start..end].parse().map_err(|_| syntax(start))?;
+                pos = end + 1;
+                segments.push((Segment::Index(idx), pos));
+            }
+
+            match bytes.get(pos) {
+                None => {}
+                // a trailing dot is missing its label
+                Some(b'.') if pos + 1 < bytes.len() => pos += 1,
+                Some(_) => return Err(syntax(pos)),
+            }
+        }
+
+        Ok(Self { raw, segments })
+    }
+
+    // everything up to and including the segment ending at end
+    fn segment(&self, end: usize) -> String {
+        self.raw[..end].to_owned()
+    }
+
+    fn split_last(&self) -> Result<(&[PathSegment<'p>], PathSegment<'p>), PathError> {
+        let Some((last, parent)) = self.segments.split_last() else {
+            return Err(PathError::Syntax {
+                path: self.raw.to_owned(),
+                position: 0,
+            });
+        };
+
+        Ok((parent, *last))
+    }
+}
+
+enum Node<'a> {
+    Struct(&'a Struct),
+    Field(&'a Field),
+}
+
+enum NodeMut<'a> {
+    Struct(&'a mut Struct),
+    Field(&'a mut Field),
+}
+
+fn walk<'a>(s: &'a Struct, path: &Path, segments: &[PathSegment]) -> Result<Node<'a>, PathError> {
+    let mut node = Node::Struct(s);
+    let mut prev_end = 0;
+
+    for (segment, end) in segments {
+        node = match (segment, node) {
+            (Segment::Label(label), Node::Struct(s)) => s
+                .fields
+                .get(*label)
+                .map(Node::Field)
+                .ok_or_else(|| PathError::MissingField {
+                    segment: path.segment(*end),
+                })?,
+            (Segment::Label(label), Node::Field(Field::BStruct(s))) => s
+                .fields
+                .get(*label)
+                .map(Node::Field)
+                .ok_or_else(|| PathError::MissingField {
+                    segment: path.segment(*end),
+                })?,
+            (Segment::Label(_), Node::Field(_)) => {
+                return Err(PathError::NotAStruct {
+                    segment: path.segment(prev_end),
+                })
+            }
+            (Segment::Index(idx), Node::Field(Field::List(list))) => list
+                .get(*idx)
+                .map(Node::Struct)
+                .ok_or_else(|| PathError::OutOfBounds {
+                    segment: path.segment(*end),
+                    len: list.len(),
+                })?,
+            (Segment::Index(_), _) => {
+                return Err(PathError::NotAList {
+                    segment: path.segment(prev_end),
+                })
+            }
+        };
+        prev_end = *end;
+    }
+
+    Ok(node)
+}
+
+// same as walk, the borrow checker doesn't allow sharing the code
+fn walk_mut<'a>(
+    s: &'a mut Struct,
+    path: &Path,
+    segments: &[PathSegment],
+) -> Result<NodeMut<'a>, PathError> {
+    let mut node = NodeMut::Struct(s);
+    let mut prev_end = 0;
+
+    for (segment, end) in segments {
+        node = match (segment, node) {
+            (Segment::Label(label), NodeMut::Struct(s)) => s
+                .fields
+                .get_mut(*label)
+                .map(NodeMut::Field)
+                .ok_or_else(|| PathError::MissingField {
+                    segment: path.segment(*end),
+                })?,
+            (Segment::Label(label), NodeMut::Field(Field::BStruct(s))) => s
+                .fields
+                .get_mut(*label)
+                .map(NodeMut::Field)
+                .ok_or_else(|| PathError::MissingField {
+                    segment: path.segment(*end),
+                })?,
+            (Segment::Label(_), NodeMut::Field(_)) => {
+                return Err(PathError::NotAStruct {
+                    segment: path.segment(prev_end),
+                })
+            }
+            (Segment::Index(idx), NodeMut::Field(Field::List(list))) => {
+                let len = list.len();
+                list.get_mut(*idx)
+                    .map(NodeMut::Struct)
+                    .ok_or_else(|| PathError::OutOfBounds {
+                        segment: path.segment(*end),
+                        len,
+                    })?
+            }
+            (Segment::Index(_), _) => {
+                return Err(PathError::NotAList {
+                    segment: path.segment(prev_end),
+                })
+            }
+        };
+        prev_end = *end;
+    }
+
+    Ok(node)
+}
+
+impl<'a> NodeMut<'a> {
+    // the struct a label segment ending at end belongs to
+    fn into_parent_struct(self, path: &Path, end: usize) -> Result<&'a mut Struct, PathError> {
+        match self {
+            Self::Struct(s) => Ok(s),
+            Self::Field(Field::BStruct(s)) => Ok(s),
+            Self::Field(_) => Err(PathError::NotAStruct {
+                segment: path.segment(end),
+            }),
+        }
+    }
+
+    fn into_parent_list(self, path: &Path, end: usize) -> Result<&'a mut Vec<Struct>, PathError> {
+        match self {
+            Self::Field(Field::List(list)) => Ok(list),
+            _ => Err(PathError::NotAList {
+                segment: path.segment(end),
+            }),
+        }
+    }
+}
+
+fn into_struct(value: Field, path: &Path, end: usize) -> Result<Struct, PathError> {
+    match value {
+        Field::BStruct(s) => Ok(*s),
+        _ => Err(PathError::NotAStruct {
+            segment: path.segment(end),
+        }),
+    }
+}
+
+impl Struct {
+    pub fn get_path(&self, path: &str) -> Result<&Field, PathError> {
+        let path = Path::parse(path)?;
+        let (_, (_, end)) = path.split_last()?;
+        match walk(self, &path, &path.segments)? {
+            Node::Field(f) => Ok(f),
+            Node::Struct(_) => Err(PathError::NotAField {
+                segment: path.segment(end),
+            }),
+        }
+    }
+
+    pub fn get_path_mut(&mut self, path: &str) -> Result<&mut Field, PathError> {
+        let path = Path::parse(path)?;
+        let (_, (_, end)) = path.split_last()?;
+        match walk_mut(self, &path, &path.segments)? {
+            NodeMut::Field(f) => Ok(f),
+            NodeMut::Struct(_) => Err(PathError::NotAField {
+                segment: path.segment(end),
+            }),
+        }
+    }
+
+    // a list element or a struct field, an empty path is the struct itself
+    pub fn get_struct_path(&self, path: &str) -> Result<&Struct, PathError> {
+        let path = Path::parse(path)?;
+        match walk(self, &path, &path.segments)? {
+            Node::Struct(s) => Ok(s),
+            Node::Field(Field::BStruct(s)) => Ok(s),
+            Node::Field(_) => Err(PathError::NotAStruct {
+                segment: path.raw.to_owned(),
+            }),
+        }
+    }
+
+    pub fn get_struct_path_mut(&mut self, path: &str) -> Result<&mut Struct, PathError> {
+        let path = Path::parse(path)?;
+        match walk_mut(self, &path, &path.segments)? {
+            NodeMut::Struct(s) => Ok(s),
+            NodeMut::Field(Field::BStruct(s)) => Ok(s),
+            NodeMut::Field(_) => Err(PathError::NotAStruct {
+                segment: path.raw.to_owned(),
+            }),
+        }
+    }
+
+    // replaces an existing field or list element (given as a BStruct), returns the old value
+    pub fn set_path(&mut self, path: &str, value: Field) -> Result<Field, PathError> {
+        let path = Path::parse(path)?;
+        let (parent, (target, end)) = path.split_last()?;
+        let prev_end = parent.last().map_or(0, |(_, end)| *end);
+        let node = walk_mut(self, &path, parent)?;
+
+        match target {
+            Segment::Label(label) => {
+                let s = node.into_parent_struct(&path, prev_end)?;
+                let field = s
+                    .fields
+                    .get_mut(label)
+                    .ok_or_else(|| PathError::MissingField {
+                        segment: path.segment(end),
+                    })?;
+                Ok(std::mem::replace(field, value))
+            }
+            Segment::Index(idx) => {
+                let value = into_struct(value, &path, end)?;
+                let list = node.into_parent_list(&path, prev_end)?;
+                let len = list.len();
+                let element = list.get_mut(idx).ok_or_else(|| PathError::OutOfBounds {
+                    segment: path.segment(end),
+                    len,
+                })?;
+                let old = std::mem::replace(element, value);
+                Ok(Field::BStruct(Box::new(old)))
+            }
+        }
+    }
+
+    // adds a field (replacing one with the same label) or inserts a list element
+    // (given as a BStruct) at the index, shifting the rest, only the last segment can be new
+    pub fn insert_path(&mut self, path: &str, value: Field) -> Result<Option<Field>, PathError> {
+        let path = Path::parse(path)?;
+        let (parent, (target, end)) = path.split_last()?;
+        let prev_end = parent.last().map_or(0, |(_, end)| *end);
+        let node = walk_mut(self, &path, parent)?;
+
+        match target {
+            Segment::Label(label) => {
+                let s = node.into_parent_struct(&path, prev_end)?;
+                Ok(s.fields.insert(label.to_owned(), value))
+            }
+            Segment::Index(idx) => {
+                let value = into_struct(value, &path, end)?;
+                let list = node.into_parent_list(&path, prev_end)?;
+                if idx > list.len() {
+                    return Err(PathError::OutOfBounds {
+                        segment: path.segment(end),
+                        len: list.len(),
+                    });
+                }
+                list.insert(idx, value);
+                Ok(None)
+            }
+        }
+    }
+
+    // removed list elements are returned as a BStruct, the order of everything else is kept
+    pub fn remove_path(&mut self, path: &str) -> Result<Field, PathError> {
+        let path = Path::parse(path)?;
+        let (parent, (target, end)) = path.split_last()?;
+        let prev_end = parent.last().map_or(0, |(_, end)| *end);
+        let node = walk_mut(self, &path, parent)?;
+
+        match target {
+            Segment::Label(label) => {
+                let s = node.into_parent_struct(&path, prev_end)?;
+                s.fields
+                    .shift_remove(label)
+                    .ok_or_else(|| PathError::MissingField {
+                        segment: path.segment(end),
+                    })
+            }
+            Segment::Index(idx) => {
+                let list = node.into_parent_list(&path, prev_end)?;
+                if idx >= list.len() {
+                    return Err(PathError::OutOfBounds {
+                        segment: path.segment(end),
+                        len: list.len(),
+                    });
+                }
+                Ok(Field::BStruct(Box::new(list.remove(idx))))
+            }
+        }
+    }
+}
@@ -1,26 +1,33 @@
 use crate::{
     formats::{
         gff::{
-            Field, FieldTmp, Gff, Orientation, Struct, Vector, FIELD_SIZE, HEADER_SIZE, STRUCT_SIZE,
+            Field, FieldTmp, Fields, Gff, Orientation, Struct, Vector, FIELD_SIZE, HEADER_SIZE,
+            STRUCT_SIZE,
         },
-        impl_read_resource, FileHead, LocString, ReadResource, ResourceType,
+        impl_read_resource, ErrorKind, FResult, FileHead, FormatError, LocString, ReadResource,
+        ReadResult, ResourceType,
     },
     util::{
         bytes::{
-            take, take_bytes, take_head, take_slice, take_slice_sized, take_string_sized,
-            take_string_trimmed, Cursor, IntoUsizeArray, IntoUsizeVec, SeekExt as _, DWORD_SIZE,
+            capacity, take, take_bytes, take_head, take_slice, take_slice_sized, take_string_sized,
+            take_string_trimmed, take_text_sized, Budget, Cursor, IntoUsizeArray, IntoUsizeVec,
+            SeekExt as _, DWORD_SIZE,
         },
-        ESResult, SResult,
+        encoding::CodePage,
     },
 };
-use ahash::{HashMap, HashMapExt as _};
+use ahash::{HashMap, HashMapExt as _, RandomState};
 use bytemuck::cast;
 use log::warn;
 use std::{io::BufRead as _, mem};
 
+// real files are nowhere near this, it keeps a crafted chain of structs from overflowing the stack
+const MAX_DEPTH: usize = 64;
+
+// both are taken out as they're used, every field and struct belongs to exactly one parent
 #[derive(Debug)]
 struct FieldReadTmp {
-    value: FieldTmp,
+    value: Option<FieldTmp>,
     label: String,
 }
 
@@ -28,29 +35,31 @@ struct FieldReadTmp {
 struct StructReadTmp {
     tp: u32,
     field_indices: Vec<usize>,
+    used: bool,
 }
 
 #[derive(Debug, Default)]
-struct Header {
-    file_head: FileHead,
-
-    struct_offset: usize,
-    struct_count: usize,
-    field_offset: usize,
-    field_count: usize,
-    label_offset: usize,
-    label_count: usize,
-    field_data_offset: usize,
-    field_data_bytes: usize,
-    field_indices_offset: usize,
-    field_indices_bytes: usize,
-    list_indices_offset: usize,
-    list_indices_bytes: usize,
+pub(super) struct Header {
+    pub(super) file_head: FileHead,
+
+    pub(super) struct_offset: usize,
+    pub(super) struct_count: usize,
+    pub(super) field_offset: usize,
+    pub(super) field_count: usize,
+    pub(super) label_offset: usize,
+    pub(super) label_count: usize,
+    pub(super) field_data_offset: usize,
+    pub(super) field_data_bytes: usize,
+    pub(super) field_indices_offset: usize,
+    pub(super) field_indices_bytes: usize,
+    pub(super) list_indices_offset: usize,
+    pub(super) list_indices_bytes: usize,
 }
 
 struct Reader<'a> {
     c: &'a mut Cursor<'a>,
     h: Header,
+    code_page: CodePage,
 
     list_indices: HashMap<usize, Vec<usize>>,
     field_indices: Vec<u32>,
@@ -58,13 +67,16 @@ struct Reader<'a> {
     labels: Vec<String>,
     fields: Vec<FieldReadTmp>,
     structs: Vec<StructReadTmp>,
+    budget: Budget,
 }
 
 impl<'a> Reader<'a> {
-    fn new(c: &'a mut Cursor<'a>, _: ()) -> Self {
+    fn new(c: &'a mut Cursor<'a>, code_page: CodePage) -> Self {
+        let budget = Budget::new(c.get_ref().len());
         Self {
             c,
             h: Header::default(),
+            code_page,
 
             list_indices: HashMap::new(),
             field_indices: vec![],
@@ -72,10 +84,11 @@ impl<'a> Reader<'a> {
             labels: vec![],
             fields: vec![],
             structs: vec![],
+            budget,
         }
     }
 
-    fn read(mut self) -> SResult<Gff> {
+    fn read(&mut self) -> ReadResult<Gff> {
         self.h = self.read_header()?;
         self.read_list_indices()?;
         self.read_field_indices()?;
@@ -85,49 +98,34 @@ impl<'a> Reader<'a> {
         self.read_structs()?;
 
         Ok(Gff {
-            content: Self::transform_struct(&self.structs, &mut self.fields, 0),
-            file_head: self.h.file_head,
+            content: Self::transform_struct(&mut self.structs, &mut self.fields, 0, 0)?,
+            file_head: mem::take(&mut self.h.file_head),
+            code_page: self.code_page,
         })
     }
 
-    fn read_header(&mut self) -> SResult<Header> {
-        let file_head = take_head(self.c).ok_or("couldn't read file head")?;
-        let dwords = take::<[u32; HEADER_SIZE - 2]>(self.c).ok_or("couldn't read header data")?;
-        let mut dwords = dwords.into_usize_array().into_iter();
-
-        Ok(Header {
-            file_head,
-
-            struct_offset: dwords.next().unwrap(),
-            struct_count: dwords.next().unwrap(),
-            field_offset: dwords.next().unwrap(),
-            field_count: dwords.next().unwrap(),
-            label_offset: dwords.next().unwrap(),
-            label_count: dwords.next().unwrap(),
-            field_data_offset: dwords.next().unwrap(),
-            field_data_bytes: dwords.next().unwrap(),
-            field_indices_offset: dwords.next().unwrap(),
-            field_indices_bytes: dwords.next().unwrap(),
-            list_indices_offset: dwords.next().unwrap(),
-            list_indices_bytes: dwords.next().unwrap(),
-        })
+    fn read_header(&mut self) -> ReadResult<Header> {
+        take_header(self.c)
     }
 
-    fn read_list_indices(&mut self) -> ESResult {
+    fn read_list_indices(&mut self) -> ReadResult<()> {
         let offset = self.h.list_indices_offset;
         self.c.seek_to(offset)?;
         self.list_indices = HashMap::new();
 
-        let bytes = take_bytes(self.c, self.h.list_indices_bytes)
-            .ok_or_else(|| format!("couldn't read list indices at starting offset {offset}"))?;
+        let bytes = take_bytes(self.c, self.h.list_indices_bytes).ok_or_else(|| {
+            ErrorKind::Truncated(format!("list indices at starting offset {offset}"))
+        })?;
         let c = &mut Cursor::new(bytes);
         let mut inner_offset = 0;
 
         while inner_offset < self.h.list_indices_bytes {
-            let size = take::<u32>(c)
-                .ok_or_else(|| format!("couldn't read list index size at {inner_offset}"))?;
-            let indices = take_slice::<u32>(c, size as usize)
-                .ok_or_else(|| format!("couldn't read {size} list indices at {inner_offset}"))?;
+            let size = take::<u32>(c).ok_or_else(|| {
+                ErrorKind::Truncated(format!("list index size at {inner_offset}"))
+            })?;
+            let indices = take_slice::<u32>(c, size as usize).ok_or_else(|| {
+                ErrorKind::Truncated(format!("{size} list indices at {inner_offset}"))
+            })?;
 
             self.list_indices
                 .insert(inner_offset, indices.into_usize_vec());
@@ -137,44 +135,47 @@ impl<'a> Reader<'a> {
         Ok(())
     }
 
-    fn read_field_indices(&mut self) -> ESResult {
+    fn read_field_indices(&mut self) -> ReadResult<()> {
         let offset = self.h.field_indices_offset;
         self.c.seek_to(offset)?;
 
         self.field_indices = take_slice::<u32>(self.c, self.h.field_indices_bytes / DWORD_SIZE)
-            .ok_or_else(|| format!("Couldn't read field indices, starting offset {offset}"))?
+            .ok_or_else(|| {
+                ErrorKind::Truncated(format!("field indices, starting offset {offset}"))
+            })?
             .into_owned();
 
         Ok(())
     }
 
-    fn read_field_data(&mut self) -> ESResult {
+    fn read_field_data(&mut self) -> ReadResult<()> {
         self.c.seek_to(self.h.field_data_offset)?;
-        self.field_data =
-            take_bytes(self.c, self.h.field_data_bytes).ok_or("couldn't read field data")?;
+        self.field_data = take_bytes(self.c, self.h.field_data_bytes)
+            .ok_or_else(|| ErrorKind::Truncated("field data".to_owned()))?;
 
         Ok(())
     }
 
-    fn read_labels(&mut self) -> ESResult {
+    fn read_labels(&mut self) -> ReadResult<()> {
         self.c.seek_to(self.h.label_offset)?;
-        self.labels = Vec::with_capacity(self.h.label_count);
+        self.labels = Vec::with_capacity(capacity(self.c, self.h.label_count, 16));
 
         for idx in 0..self.h.label_count {
             let label = take_string_trimmed(self.c, 16)
-                .ok_or_else(|| format!("couldn't read label {idx}"))?;
+                .ok_or_else(|| ErrorKind::Truncated(format!("label {idx}")))?;
             self.labels.push(label);
         }
 
         Ok(())
     }
 
-    fn read_fields(&mut self) -> ESResult {
+    fn read_fields(&mut self) -> ReadResult<()> {
         self.c.seek_to(self.h.field_offset)?;
-        self.fields = Vec::with_capacity(self.h.field_count);
         let field_data = self.field_data;
+        let code_page = self.code_page;
         let fields = take_slice::<[u32; FIELD_SIZE]>(self.c, self.h.field_count)
-            .ok_or("couldn't read fields")?;
+            .ok_or_else(|| ErrorKind::Truncated("fields".to_owned()))?;
+        self.fields = Vec::with_capacity(fields.len());
 
         for (idx, [tp, label_idx, value]) in fields.iter().copied().enumerate() {
             use FieldTmp::Simple;
@@ -202,7 +203,7 @@ impl<'a> Reader<'a> {
                     take(c).map(Field::Double)
                 })?),
                 10 => Simple(read_data(tp, field_data, value, idx, |c| {
-                    let field = take_string_sized::<u32>(c).map(Field::String);
+                    let field = take_text_sized::<u32>(c, code_page).map(Field::String);
                     // for some reason, android saves for kotor 2 have corrupted empty strings with garbage values for length
                     // it's still a band-aid because sometimes it'll successfully read garbade data into a string
                     if field.is_none() {
@@ -217,11 +218,13 @@ impl<'a> Reader<'a> {
                 12 => Simple(read_data(tp, field_data, value, idx, |c| {
                     c.consume(DWORD_SIZE);
                     let [str_ref, count] = take::<[u32; 2]>(c)?;
-                    let mut strings = Vec::with_capacity(count as usize);
+                    // id and length
+                    let mut strings = Vec::with_capacity(capacity(c, count as usize, 8));
 
                     for _ in 0..count {
                         let id = take::<u32>(c)?;
-                        let content = take_string_sized::<u32>(c)?;
+                        let content =
+                            take_text_sized::<u32>(c, CodePage::from_string_id(id, code_page))?;
                         strings.push(LocString { id, content });
                     }
                     Some(Field::LocString((str_ref, strings)))
@@ -244,22 +247,36 @@ impl<'a> Reader<'a> {
                 17 => Simple(read_data(tp, field_data, value, idx, |c| {
                     take::<[f32; 3]>(c).map(|[x, y, z]| Field::Vector(Vector { x, y, z }))
                 })?),
-                t => return Err(format!("Invalid field type {t} in field {idx}: {label}")),
+                t => return Err(format!("Invalid field type {t} in field {idx}: {label}").into()),
             };
 
-            self.fields.push(FieldReadTmp { value, label });
+            if let Simple(field) = &value {
+                self.budget
+                    .spend(data_len(field))
+                    .ok_or_else(|| ErrorKind::TooLarge(format!("field {idx} data")))?;
+            }
+
+            self.fields.push(FieldReadTmp {
+                value: Some(value),
+                label,
+            });
         }
         Ok(())
     }
 
-    fn read_structs(&mut self) -> ESResult {
+    fn read_structs(&mut self) -> ReadResult<()> {
         let offset = self.h.struct_offset;
         self.c.seek_to(offset)?;
-        self.structs = Vec::with_capacity(self.h.struct_count);
+        self.structs = Vec::with_capacity(capacity(
+            self.c,
+            self.h.struct_count,
+            STRUCT_SIZE * DWORD_SIZE,
+        ));
 
         for i in 0..self.h.struct_count {
-            let [tp, data, field_count] = take::<[u32; STRUCT_SIZE]>(self.c)
-                .ok_or_else(|| format!("couldn't read struct {i}, starting offset {offset}"))?;
+            let [tp, data, field_count] = take::<[u32; STRUCT_SIZE]>(self.c).ok_or_else(|| {
+                ErrorKind::Truncated(format!("struct {i}, starting offset {offset}"))
+            })?;
             let data = data as usize;
 
             let field_indices = match field_count {
@@ -267,77 +284,157 @@ impl<'a> Reader<'a> {
                 1 => vec![data],
                 _ => {
                     let start = data / DWORD_SIZE;
-                    self.field_indices
-                        .get(start..start + field_count as usize)
-                        .ok_or_else(|| format!("couldn't read struct's {i} field indices"))?
+                    // shared indices are rejected only once the fields are used
+                    self.budget
+                        .spend(field_count as usize * DWORD_SIZE)
+                        .ok_or_else(|| {
+                            ErrorKind::TooLarge(format!("struct's {i} field indices"))
+                        })?;
+                    start
+                        .checked_add(field_count as usize)
+                        .and_then(|end| self.field_indices.get(start..end))
+                        .ok_or_else(|| ErrorKind::Truncated(format!("struct's {i} field indices")))?
                         .into_usize_vec()
                 }
             };
 
-            self.structs.push(StructReadTmp { tp, field_indices });
+            self.structs.push(StructReadTmp {
+                tp,
+                field_indices,
+                used: false,
+            });
         }
 
         Ok(())
     }
 
     fn unwrap_tmp_field(
-        structs: &[StructReadTmp],
+        structs: &mut [StructReadTmp],
         fields: &mut [FieldReadTmp],
         f: FieldTmp,
-    ) -> Field {
-        match f {
+        depth: usize,
+    ) -> ReadResult<Field> {
+        Ok(match f {
             FieldTmp::Simple(value) => value,
-            FieldTmp::Struct(idx) => {
-                Field::BStruct(Box::new(Self::transform_struct(structs, fields, idx)))
-            }
+            FieldTmp::Struct(idx) => Field::BStruct(Box::new(Self::transform_struct(
+                structs,
+                fields,
+                idx,
+                depth + 1,
+            )?)),
             FieldTmp::List(indices) => {
-                let structs: Vec<Struct> = indices
+                let structs = indices
                     .into_iter()
-                    .map(|idx| Self::transform_struct(structs, fields, idx))
-                    .collect();
+                    .map(|idx| Self::transform_struct(structs, fields, idx, depth + 1))
+                    .collect::<ReadResult<_>>()?;
 
                 Field::List(structs)
             }
-        }
+        })
     }
 
     fn transform_struct(
-        structs: &[StructReadTmp],
+        structs: &mut [StructReadTmp],
         fields: &mut [FieldReadTmp],
         idx: usize,
-    ) -> Struct {
-        let s = &structs[idx];
-        let mut field_map = HashMap::with_capacity(s.field_indices.len());
-        for idx in &s.field_indices {
-            let f = &mut fields[*idx];
+        depth: usize,
+    ) -> ReadResult<Struct> {
+        if depth > MAX_DEPTH {
+            return Err(ErrorKind::TooLarge(format!("nesting of struct {idx}")));
+        }
+        let s = structs
+            .get_mut(idx)
+            .ok_or_else(|| format!("missing struct {idx}"))?;
+        // a struct that's already been used is either shared or part of a cycle
+        if mem::replace(&mut s.used, true) {
+            return Err(format!("struct {idx} is referenced more than once").into());
+        }
+        let tp = s.tp;
+        let field_indices = mem::take(&mut s.field_indices);
+
+        let mut field_map =
+            Fields::with_capacity_and_hasher(field_indices.len(), RandomState::new());
+        for field_idx in field_indices {
+            let f = fields
+                .get_mut(field_idx)
+                .ok_or_else(|| format!("missing field {field_idx} in struct {idx}"))?;
+            let value = f.value.take().ok_or_else(|| {
+                format!("field {field_idx} in struct {idx} is referenced more than once")
+            })?;
             let label = mem::take(&mut f.label);
-            let value = mem::replace(&mut f.value, FieldTmp::Simple(Field::default()));
-            field_map.insert(label, Self::unwrap_tmp_field(structs, fields, value));
+            field_map.insert(
+                label,
+                Self::unwrap_tmp_field(structs, fields, value, depth)?,
+            );
         }
-        Struct {
-            tp: s.tp,
+        Ok(Struct {
+            tp,
             fields: field_map,
-        }
+        })
     }
 }
 
+pub(super) fn take_header(c: &mut Cursor) -> ReadResult<Header> {
+    let file_head = take_head(c)?;
+    let dwords = take::<[u32; HEADER_SIZE - 2]>(c)
+        .ok_or_else(|| ErrorKind::Truncated("header data".to_owned()))?;
+    let mut dwords = dwords.into_usize_array().into_iter();
+
+    Ok(Header {
+        file_head,
+
+        struct_offset: dwords.next().unwrap(),
+        struct_count: dwords.next().unwrap(),
+        field_offset: dwords.next().unwrap(),
+        field_count: dwords.next().unwrap(),
+        label_offset: dwords.next().unwrap(),
+        label_count: dwords.next().unwrap(),
+        field_data_offset: dwords.next().unwrap(),
+        field_data_bytes: dwords.next().unwrap(),
+        field_indices_offset: dwords.next().unwrap(),
+        field_indices_bytes: dwords.next().unwrap(),
+        list_indices_offset: dwords.next().unwrap(),
+        list_indices_bytes: dwords.next().unwrap(),
+    })
+}
+
 fn read_data(
     tp: u32,
     field_data: &[u8],
     offset: u32,
     field_idx: usize,
     get_field: impl FnOnce(&mut Cursor) -> Option<Field>,
-) -> SResult<Field> {
+) -> ReadResult<Field> {
     let data = field_data
         .get(offset as usize..)
         .ok_or_else(|| format!("invalid field data offset in field {field_idx}"))?;
 
     get_field(&mut Cursor::new(data)).ok_or_else(|| {
-        format!(
-            "couldn't read Field::{} in field {field_idx}",
+        ErrorKind::Truncated(format!(
+            "Field::{} in field {field_idx}",
             Field::repr_to_string(tp as u8)
-        )
+        ))
     })
 }
 
-impl_read_resource!(Gff, Reader);
+// bytes of field data that were copied out for the field
+fn data_len(field: &Field) -> usize {
+    match field {
+        Field::String(v) | Field::ResRef(v) => v.len(),
+        Field::LocString((_, strings)) => strings.iter().map(|s| s.content.len()).sum(),
+        Field::Void(v) => v.len(),
+        _ => 0,
+    }
+}
+
+impl_read_resource!(Gff, Reader, CodePage, ResourceType::Gff);
+
+// plain strings are read as Windows-1252 if the language isn't known
+impl ReadResource<'_, ()> for Gff {
+    fn get_type() -> ResourceType {
+        <Self as ReadResource<CodePage>>::get_type()
+    }
+    fn read(bytes: &[u8], _: ()) -> FResult<Self> {
+        <Self as ReadResource<CodePage>>::read(bytes, CodePage::default())
+    }
+}
@@ -0,0 +1,641 @@
+use crate::{
+    formats::{
+        gff::{
+            read::{take_header, Header},
+            FIELD_SIZE, HEADER_SIZE, STRUCT_SIZE,
+        },
+        FResult, FormatError, ResourceType,
+    },
+    util::bytes::{take, take_slice, Cursor, DWORD_SIZE},
+};
+use ahash::{HashMap, HashMapExt as _, HashSet, HashSetExt as _};
+use std::{fmt, slice};
+
+const LABEL_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    Header,
+    Structs,
+    Fields,
+    Labels,
+    FieldData,
+    FieldIndices,
+    ListIndices,
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Header => "header",
+            Self::Structs => "struct table",
+            Self::Fields => "field table",
+            Self::Labels => "label table",
+            Self::FieldData => "field data",
+            Self::FieldIndices => "field indices",
+            Self::ListIndices => "list indices",
+        })
+    }
+}
+
+// something a field or struct points to, lists are found by their byte offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reference {
+    Field(usize),
+    Struct(usize),
+    List(usize),
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Field(idx) => write!(f, "field {idx}"),
+            Self::Struct(idx) => write!(f, "struct {idx}"),
+            Self::List(offset) => write!(f, "list at {offset}"),
+        }
+    }
+}
+
+// struct and field numbers are indices into their tables, offsets are in bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    // the header points past the end of the file
+    TableOutOfBounds {
+        table: Table,
+        offset: usize,
+        len: usize,
+    },
+    Overlap {
+        first: Table,
+        second: Table,
+    },
+    // nothing in the header points at these bytes
+    TrailingBytes {
+        offset: usize,
+        len: usize,
+    },
+    MissingRoot,
+    // a list entry whose count doesn't fit in the list indices
+    TruncatedList {
+        offset: usize,
+        count: usize,
+    },
+    // labels are stored in fixed 16 byte slots, a longer one spills into the next slot
+    // and leaves bytes after the terminating nul
+    LabelOverflow {
+        label: usize,
+    },
+    InvalidFieldType {
+        field: usize,
+        tp: u32,
+    },
+    DanglingLabel {
+        field: usize,
+        label: usize,
+    },
+    DanglingData {
+        field: usize,
+        offset: usize,
+    },
+    DanglingStruct {
+        field: usize,
+        target: usize,
+    },
+    DanglingList {
+        field: usize,
+        offset: usize,
+    },
+    DanglingFieldIndices {
+        target: usize,
+        offset: usize,
+        count: usize,
+    },
+    DanglingField {
+        target: usize,
+        field: usize,
+    },
+    DuplicateLabel {
+        target: usize,
+        label: String,
+    },
+    // two fields share some of their data
+    DataOverlap {
+        first: usize,
+        second: usize,
+    },
+    // every field, struct and list has exactly one parent, the top level struct has none
+    SharedReference {
+        reference: Reference,
+        count: usize,
+    },
+    // the struct contains itself, directly or through its children
+    Cycle {
+        target: usize,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TableOutOfBounds { table, offset, len } => {
+                write!(
+                    f,
+                    "{table} at {offset} with {len} bytes goes past the end of the file"
+                )
+            }
+            Self::Overlap { first, second } => write!(f, "{first} overlaps {second}"),
+            Self::TrailingBytes { offset, len } => {
+                write!(f, "{len} unused bytes at the end, starting at {offset}")
+            }
+            Self::MissingRoot => f.write_str("there's no top level struct"),
+            Self::TruncatedList { offset, count } => {
+                write!(
+                    f,
+                    "list at {offset} with {count} structs doesn't fit in list indices"
+                )
+            }
+            Self::LabelOverflow { label } => {
+                write!(
+                    f,
+                    "label {label} has data after its end, a previous label may be too long"
+                )
+            }
+            Self::InvalidFieldType { field, tp } => {
+                write!(f, "field {field} has invalid type {tp}")
+            }
+            Self::DanglingLabel { field, label } => {
+                write!(f, "field {field} refers to missing label {label}")
+            }
+            Self::DanglingData { field, offset } => {
+                write!(f, "field {field} has data at {offset} outside field data")
+            }
+            Self::DanglingStruct { field, target } => {
+                write!(f, "field {field} refers to missing struct {target}")
+            }
+            Self::DanglingList { field, offset } => {
+                write!(f, "field {field} refers to no list at {offset}")
+            }
+            Self::DanglingFieldIndices {
+                target,
+                offset,
+                count,
+            } => write!(
+                f,
+                "struct {target} has {count} field indices at {offset} outside field indices"
+            ),
+            Self::DanglingField { target, field } => {
+                write!(f, "struct {target} refers to missing field {field}")
+            }
+            Self::DuplicateLabel { target, label } => {
+                write!(f, "struct {target} has more than one field {label}")
+            }
+            Self::DataOverlap { first, second } => {
+                write!(f, "data of fields {first} and {second} overlaps")
+            }
+            Self::SharedReference { reference, count } => {
+                write!(f, "{reference} is used {count} times")
+            }
+            Self::Cycle { target } => write!(f, "struct {target} contains itself"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.issues.is_empty() {
+            return f.write_str("no issues found");
+        }
+        for issue in &self.issues {
+            writeln!(f, "{issue}")?;
+        }
+        Ok(())
+    }
+}
+
+struct Validator<'a> {
+    bytes: &'a [u8],
+    h: Header,
+    issues: Vec<Issue>,
+
+    structs: Vec<[u32; STRUCT_SIZE]>,
+    fields: Vec<[u32; FIELD_SIZE]>,
+    labels: Vec<String>,
+    field_data: &'a [u8],
+    field_indices: Vec<u32>,
+    // byte offset of each list to its struct indices
+    lists: HashMap<usize, Vec<u32>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    New,
+    Active,
+    Done,
+}
+
+impl<'a> Validator<'a> {
+    fn table_bounds(&self) -> [(Table, usize, usize); 7] {
+        let h = &self.h;
+        [
+            (Table::Header, 0, HEADER_SIZE * DWORD_SIZE),
+            (
+                Table::Structs,
+                h.struct_offset,
+                h.struct_count.saturating_mul(STRUCT_SIZE * DWORD_SIZE),
+            ),
+            (
+                Table::Fields,
+                h.field_offset,
+                h.field_count.saturating_mul(FIELD_SIZE * DWORD_SIZE),
+            ),
+            (
+                Table::Labels,
+                h.label_offset,
+                h.label_count.saturating_mul(LABEL_SIZE),
+            ),
+            (Table::FieldData, h.field_data_offset, h.field_data_bytes),
+            (
+                Table::FieldIndices,
+                h.field_indices_offset,
+                h.field_indices_bytes,
+            ),
+            (
+                Table::ListIndices,
+                h.list_indices_offset,
+                h.list_indices_bytes,
+            ),
+        ]
+    }
+
+    // the part of a table that's actually in the file
+    fn table(&self, offset: usize, len: usize) -> &'a [u8] {
+        let end = offset.saturating_add(len).min(self.bytes.len());
+        self.bytes.get(offset..end).unwrap_or_default()
+    }
+
+    fn check_layout(&mut self) {
+        let tables = self.table_bounds();
+        let mut end_of_data = 0;
+
+        for (idx, &(table, offset, len)) in tables.iter().enumerate() {
+            let end = offset.saturating_add(len);
+            if end > self.bytes.len() {
+                self.issues
+                    .push(Issue::TableOutOfBounds { table, offset, len });
+            }
+            if len == 0 {
+                continue;
+            }
+            end_of_data = end_of_data.max(end);
+
+            for &(other, other_offset, other_len) in &tables[..idx] {
+                if other_len > 0
+                    && offset < other_offset.saturating_add(other_len)
+                    && other_offset < end
+                {
+                    self.issues.push(Issue::Overlap {
+                        first: other,
+                        second: table,
+                    });
+                }
+            }
+        }
+
+        if end_of_data < self.bytes.len() {
+            self.issues.push(Issue::TrailingBytes {
+                offset: end_of_data,
+                len: self.bytes.len() - end_of_data,
+            });
+        }
+    }
+
+    fn read_tables(&mut self) {
+        let [_, structs, fields, labels, field_data, field_indices, _] = self
+            .table_bounds()
+            .map(|(_, offset, len)| self.table(offset, len));
+        self.field_data = field_data;
+
+        self.structs = take_slice(
+            &mut Cursor::new(structs),
+            structs.len() / (STRUCT_SIZE * DWORD_SIZE),
+        )
+        .unwrap()
+        .into_owned();
+        self.fields = take_slice(
+            &mut Cursor::new(fields),
+            fields.len() / (FIELD_SIZE * DWORD_SIZE),
+        )
+        .unwrap()
+        .into_owned();
+        self.field_indices = take_slice(
+            &mut Cursor::new(field_indices),
+            field_indices.len() / DWORD_SIZE,
+        )
+        .unwrap()
+        .into_owned();
+
+        for (idx, slot) in labels.chunks_exact(LABEL_SIZE).enumerate() {
+            let len = slot.iter().position(|b| *b == 0).unwrap_or(LABEL_SIZE);
+            if slot[len..].iter().any(|b| *b != 0) {
+                self.issues.push(Issue::LabelOverflow { label: idx });
+            }
+            self.labels
+                .push(String::from_utf8_lossy(&slot[..len]).into_owned());
+        }
+    }
+
+    fn read_lists(&mut self) {
+        let bytes = self.table(self.h.list_indices_offset, self.h.list_indices_bytes);
+        let c = &mut Cursor::new(bytes);
+
+        while (c.position() as usize) < bytes.len() {
+            let offset = c.position() as usize;
+            let Some(count) = take::<u32>(c) else {
+                self.issues.push(Issue::TruncatedList { offset, count: 0 });
+                break;
+            };
+            let Some(indices) = take_slice::<u32>(c, count as usize) else {
+                self.issues.push(Issue::TruncatedList {
+                    offset,
+                    count: count as usize,
+                });
+                break;
+            };
+            self.lists.insert(offset, indices.into_owned());
+        }
+    }
+
+    // size of the data a complex field points to, None if its length prefix isn't there
+    fn data_size(tp: u32, data: &[u8]) -> Option<usize> {
+        let prefixed = |prefix: usize| {
+            let len = data.get(..prefix)?;
+            let mut len_bytes = [0; DWORD_SIZE];
+            len_bytes[..prefix].copy_from_slice(len);
+            Some(prefix + u32::from_le_bytes(len_bytes) as usize)
+        };
+
+        match tp {
+            10 | 12 | 13 => prefixed(DWORD_SIZE),
+            11 => prefixed(1),
+            16 => Some(16),
+            17 => Some(12),
+            _ => Some(8),
+        }
+    }
+
+    fn check_fields(&mut self) {
+        let struct_count = self.structs.len();
+        let mut data_extents = vec![];
+
+        for (idx, &[tp, label, value]) in self.fields.iter().enumerate() {
+            let value = value as usize;
+            if label as usize >= self.labels.len() {
+                self.issues.push(Issue::DanglingLabel {
+                    field: idx,
+                    label: label as usize,
+                });
+            }
+
+            match tp {
+                0..=5 | 8 => {}
+                14 => {
+                    if value >= struct_count {
+                        self.issues.push(Issue::DanglingStruct {
+                            field: idx,
+                            target: value,
+                        });
+                    }
+                }
+                15 => match self.lists.get(&value) {
+                    Some(list) => {
+                        for target in list {
+                            if *target as usize >= struct_count {
+                                self.issues.push(Issue::DanglingStruct {
+                                    field: idx,
+                                    target: *target as usize,
+                                });
+                            }
+                        }
+                    }
+                    None => self.issues.push(Issue::DanglingList {
+                        field: idx,
+                        offset: value,
+                    }),
+                },
+                6 | 7 | 9..=13 | 16 | 17 => {
+                    let data = self.field_data.get(value..).unwrap_or_default();
+                    match Self::data_size(tp, data) {
+                        Some(size) if size <= data.len() => {
+                            data_extents.push((value, value + size, idx));
+                        }
+                        _ => self.issues.push(Issue::DanglingData {
+                            field: idx,
+                            offset: value,
+                        }),
+                    }
+                }
+                tp => self.issues.push(Issue::InvalidFieldType { field: idx, tp }),
+            }
+        }
+
+        data_extents.sort_unstable();
+        let mut furthest: Option<(usize, usize)> = None;
+        for (start, end, idx) in data_extents {
+            if let Some((furthest_end, furthest_idx)) = furthest {
+                if start < furthest_end {
+                    self.issues.push(Issue::DataOverlap {
+                        first: furthest_idx,
+                        second: idx,
+                    });
+                }
+                if end <= furthest_end {
+                    continue;
+                }
+            }
+            furthest = Some((end, idx));
+        }
+    }
+
+    // indices of the fields of a struct, None if they're outside the field indices
+    fn struct_fields<'b>(field_indices: &'b [u32], s: &'b [u32; STRUCT_SIZE]) -> Option<&'b [u32]> {
+        let [_, data, count] = s;
+        let (start, count) = (*data as usize, *count as usize);
+        match count {
+            0 => Some(&[]),
+            1 => Some(slice::from_ref(data)),
+            _ => (start % DWORD_SIZE == 0)
+                .then(|| {
+                    let start = start / DWORD_SIZE;
+                    field_indices.get(start..start.checked_add(count)?)
+                })
+                .flatten(),
+        }
+    }
+
+    fn check_structs(&mut self) {
+        if self.h.struct_count == 0 {
+            self.issues.push(Issue::MissingRoot);
+        }
+
+        for (idx, s) in self.structs.iter().enumerate() {
+            let Some(field_indices) = Self::struct_fields(&self.field_indices, s) else {
+                self.issues.push(Issue::DanglingFieldIndices {
+                    target: idx,
+                    offset: s[1] as usize,
+                    count: s[2] as usize,
+                });
+                continue;
+            };
+
+            let mut labels = HashSet::with_capacity(field_indices.len());
+            for field in field_indices {
+                let Some([_, label, _]) = self.fields.get(*field as usize) else {
+                    self.issues.push(Issue::DanglingField {
+                        target: idx,
+                        field: *field as usize,
+                    });
+                    continue;
+                };
+                let Some(label) = self.labels.get(*label as usize) else {
+                    continue;
+                };
+                if !labels.insert(label) {
+                    self.issues.push(Issue::DuplicateLabel {
+                        target: idx,
+                        label: label.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    fn check_references(&mut self) {
+        let mut field_refs = vec![0; self.fields.len()];
+        let mut list_refs = HashMap::<usize, usize>::new();
+        // structs in fields and lists of each struct
+        let mut children = vec![vec![]; self.structs.len()];
+
+        for (idx, s) in self.structs.iter().enumerate() {
+            let field_indices = Self::struct_fields(&self.field_indices, s).unwrap_or_default();
+            for &field in field_indices {
+                let Some(&[tp, _, value]) = self.fields.get(field as usize) else {
+                    continue;
+                };
+                field_refs[field as usize] += 1;
+                // what a shared field points to is only counted once
+                if field_refs[field as usize] > 1 {
+                    continue;
+                }
+                let value = value as usize;
+                match tp {
+                    14 => children[idx].push(value),
+                    15 => {
+                        let Some(list) = self.lists.get(&value) else {
+                            continue;
+                        };
+                        let refs = list_refs.entry(value).or_default();
+                        *refs += 1;
+                        if *refs == 1 {
+                            children[idx].extend(list.iter().map(|target| *target as usize));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut struct_refs = vec![0; self.structs.len()];
+        if let Some(root) = struct_refs.first_mut() {
+            // the top level struct isn't in any field, but it can't be in one either
+            *root = 1;
+        }
+        for target in children.iter().flatten() {
+            if let Some(refs) = struct_refs.get_mut(*target) {
+                *refs += 1;
+            }
+        }
+
+        let mut lists: Vec<_> = list_refs.into_iter().collect();
+        lists.sort_unstable();
+        let shared = (field_refs.into_iter().enumerate())
+            .map(|(idx, count)| (Reference::Field(idx), count))
+            .chain(
+                (struct_refs.into_iter().enumerate())
+                    .map(|(idx, count)| (Reference::Struct(idx), count)),
+            )
+            .chain((lists.into_iter()).map(|(offset, count)| (Reference::List(offset), count)))
+            .filter(|(_, count)| *count > 1)
+            .map(|(reference, count)| Issue::SharedReference { reference, count });
+        self.issues.extend(shared);
+
+        // depth first from every struct, so cycles that can't be reached from the top are found too
+        let mut visits = vec![Visit::New; self.structs.len()];
+        for start in 0..self.structs.len() {
+            if visits[start] != Visit::New {
+                continue;
+            }
+            visits[start] = Visit::Active;
+            // struct and how many of its children have been looked at
+            let mut stack = vec![(start, 0)];
+            while let Some(&mut (idx, ref mut next)) = stack.last_mut() {
+                let Some(&child) = children[idx].get(*next) else {
+                    visits[idx] = Visit::Done;
+                    stack.pop();
+                    continue;
+                };
+                *next += 1;
+                match visits.get(child) {
+                    Some(Visit::New) => {
+                        visits[child] = Visit::Active;
+                        stack.push((child, 0));
+                    }
+                    Some(Visit::Active) => self.issues.push(Issue::Cycle { target: child }),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+// Walks the raw tables without building the struct tree, reporting everything that
+// would make reading fail or lose data. Only an unreadable header is an error.
+pub fn validate(bytes: &[u8]) -> FResult<Report> {
+    let c = &mut Cursor::new(bytes);
+    let h = take_header(c).map_err(|kind| FormatError {
+        format: ResourceType::Gff,
+        resource: None,
+        offset: Some(c.position()),
+        kind,
+    })?;
+
+    let mut v = Validator {
+        bytes,
+        h,
+        issues: vec![],
+        structs: vec![],
+        fields: vec![],
+        labels: vec![],
+        field_data: &[],
+        field_indices: vec![],
+        lists: HashMap::new(),
+    };
+    v.check_layout();
+    v.read_tables();
+    v.read_lists();
+    v.check_fields();
+    v.check_structs();
+    v.check_references();
+
+    Ok(Report { issues: v.issues })
+}
@@ -1,37 +1,36 @@
 use crate::{
     formats::{
-        gff::{Field, FieldTmp, Gff, Struct, FIELD_SIZE, HEADER_SIZE},
+        gff::{Field, Gff, Struct, FIELD_SIZE, HEADER_SIZE, STRUCT_SIZE},
         FileHead,
     },
-    util::bytes::{
-        bytes_to_sized_bytes, nullpad_string, num_to_dword, IntoByteSlice as _, DWORD_SIZE,
+    util::{
+        bytes::{
+            bytes_to_sized_bytes, nullpad_string, num_to_dword, IntoByteSlice as _, DWORD_SIZE,
+        },
+        encoding::CodePage,
     },
 };
 use ahash::{HashMap, HashMapExt as _};
 use std::io::{Cursor, Seek, Write};
 
 const MAX_LABEL_LEN: usize = 16;
+// what the game puts into data/offset of structs without fields
+const EMPTY_STRUCT_DATA: u32 = u32::MAX;
 
-struct FieldWriteTmp {
-    tp: u32,
-    label_index: u32,
-    content: u32,
-}
-struct StructWriteTmp {
-    tp: u32,
-    my_idx: usize,
-    field_count: u32,
-}
+// Everything is laid out in the order the game itself creates it:
+// a struct is appended as soon as it's encountered, then its fields in order,
+// each field is appended before the structs it contains.
+// This way reading and writing an unmodified file gives back the same bytes.
 pub struct Writer {
     file_head: FileHead,
+    code_page: CodePage,
     // indices into labels
     label_map: HashMap<String, usize>,
     labels: Vec<String>,
-    structs: Vec<StructWriteTmp>,
-    // blocks with indices matching struct's my_idx
-    fields: Vec<Vec<FieldWriteTmp>>,
-    field_count: usize,
+    structs: Vec<[u32; STRUCT_SIZE]>,
+    fields: Vec<[u32; FIELD_SIZE]>,
     raw_data: Vec<u8>,
+    field_indices: Vec<u32>,
     list_indices: Vec<u32>,
 }
 
@@ -39,15 +38,16 @@ impl Writer {
     fn new(gff: Gff) -> Self {
         let mut w = Self {
             file_head: gff.file_head,
+            code_page: gff.code_page,
             label_map: HashMap::new(),
             labels: vec![],
             structs: vec![],
             fields: vec![],
-            field_count: 0,
             raw_data: vec![],
+            field_indices: vec![],
             list_indices: vec![],
         };
-        w.collect(gff.content);
+        w.save_struct(gff.content);
 
         w
     }
@@ -74,168 +74,122 @@ impl Writer {
         idx
     }
 
-    fn save_list_indices(&mut self, indices: &[usize]) -> u32 {
-        let idx = self.list_indices.len();
-        let size = indices.len() as u32;
-
-        self.list_indices.push(size);
-        self.list_indices
-            .extend(indices.iter().map(|i| (*i as u32)));
-
-        (idx * DWORD_SIZE) as u32
-    }
-
-    fn save_field(&mut self, block_idx: usize, field: FieldTmp, label_index: usize) {
-        let (tp, value): (u32, u32) = match field {
-            FieldTmp::Simple(v) => {
-                let tp = v.to_int();
-
-                let value = match v {
-                    Field::Byte(v) => num_to_dword(v),
-                    Field::Char(v) => num_to_dword(v),
-                    Field::Word(v) => num_to_dword(v),
-                    Field::Short(v) => num_to_dword(v),
-                    Field::Dword(v) => num_to_dword(v),
-                    Field::Int(v) => num_to_dword(v),
-                    Field::Dword64(v) => self.save_bytes(&v.to_le_bytes()),
-                    Field::Int64(v) => self.save_bytes(&v.to_le_bytes()),
-                    Field::Float(v) => num_to_dword(v),
-                    Field::Double(v) => self.save_bytes(&v.to_le_bytes()),
-                    Field::String(v) => {
-                        self.save_bytes(&bytes_to_sized_bytes::<DWORD_SIZE>(v.as_bytes()))
-                    }
-                    Field::ResRef(v) => self.save_bytes(&bytes_to_sized_bytes::<1>(v.as_bytes())),
-                    Field::LocString((str_ref, v)) => {
-                        let string_count = v.len();
-                        // Total Size will be added in the end
-                        let mut bytes = Vec::with_capacity(string_count * 10 + 2 * DWORD_SIZE);
-                        // StringRef
-                        bytes.extend(str_ref.to_le_bytes());
-                        // StringCount
-                        bytes.extend((string_count as u32).to_le_bytes());
-                        for s in v {
-                            // StringID
-                            bytes.extend(s.id.to_le_bytes());
-                            // Length + String itself
-                            bytes.extend(bytes_to_sized_bytes::<DWORD_SIZE>(s.content.as_bytes()));
-                        }
-
-                        self.save_bytes(&bytes_to_sized_bytes::<DWORD_SIZE>(&bytes))
-                    }
-                    Field::Void(v) => self.save_bytes(&bytes_to_sized_bytes::<DWORD_SIZE>(&v)),
-                    Field::Orientation(v) => {
-                        self.save_bytes([v.w, v.x, v.y, v.z].into_byte_slice())
-                    }
-                    Field::Vector(v) => self.save_bytes([v.x, v.y, v.z].into_byte_slice()),
-                    _ => unreachable!(),
-                };
-
-                (tp as u32, value)
+    fn save_struct(&mut self, s: Struct) -> u32 {
+        let idx = self.structs.len();
+        let field_count = s.fields.len();
+        // structs with 1 field refer to it directly instead of storing it's index in field indices
+        let (data, indices_start) = match field_count {
+            0 => (EMPTY_STRUCT_DATA, 0),
+            1 => (self.fields.len() as u32, 0),
+            _ => {
+                let start = self.field_indices.len();
+                // reserving the space, indices are filled in as fields get saved
+                self.field_indices.resize(start + field_count, 0);
+                ((start * DWORD_SIZE) as u32, start)
             }
-            FieldTmp::Struct(idx) => (Field::str_to_int("BStruct") as u32, idx as u32),
-            FieldTmp::List(indices) => (
-                Field::str_to_int("List") as u32,
-                self.save_list_indices(&indices),
-            ),
         };
+        self.structs.push([s.tp, data, field_count as u32]);
 
-        self.field_count += 1;
-        self.fields[block_idx].push(FieldWriteTmp {
-            tp,
-            label_index: label_index as u32,
-            content: value,
-        });
-    }
-
-    fn save_struct(&mut self, tp: u32, my_idx: usize, field_count: u32) -> usize {
-        let idx = self.structs.len();
-        self.structs.push(StructWriteTmp {
-            tp,
-            my_idx,
-            field_count,
-        });
+        for (i, (label, value)) in s.fields.into_iter().enumerate() {
+            if field_count > 1 {
+                self.field_indices[indices_start + i] = self.fields.len() as u32;
+            }
+            self.save_field(label, value);
+        }
 
-        idx
+        idx as u32
     }
 
-    fn collect(&mut self, s: Struct) -> usize {
-        let field_count = s.fields.len();
+    fn save_list(&mut self, list: Vec<Struct>) -> u32 {
+        let start = self.list_indices.len();
+        self.list_indices.push(list.len() as u32);
+        self.list_indices.resize(start + 1 + list.len(), 0);
 
-        let my_idx = self.fields.len();
-        self.fields.push(Vec::with_capacity(field_count));
+        for (i, s) in list.into_iter().enumerate() {
+            self.list_indices[start + 1 + i] = self.save_struct(s);
+        }
 
-        for (label, value) in s.fields {
-            let label_idx = self.save_label(label);
+        (start * DWORD_SIZE) as u32
+    }
 
-            match value {
-                Field::BStruct(s) => {
-                    let struct_idx = self.collect(*s);
-                    self.save_field(my_idx, FieldTmp::Struct(struct_idx), label_idx);
-                }
-                Field::List(list) => {
-                    let mut indices = Vec::with_capacity(list.len());
-                    for s in list {
-                        indices.push(self.collect(s));
-                    }
-                    self.save_field(my_idx, FieldTmp::List(indices), label_idx);
-                }
-                v => {
-                    self.save_field(my_idx, FieldTmp::Simple(v), label_idx);
-                }
+    fn save_field(&mut self, label: String, field: Field) {
+        let label_idx = self.save_label(label) as u32;
+        let tp = field.to_int() as u32;
+        let idx = self.fields.len();
+        // content to be filled later, nested structs have to go after their field
+        self.fields.push([tp, label_idx, 0]);
+
+        let content = match field {
+            Field::Byte(v) => num_to_dword(v),
+            Field::Char(v) => num_to_dword(v),
+            Field::Word(v) => num_to_dword(v),
+            Field::Short(v) => num_to_dword(v),
+            Field::Dword(v) => num_to_dword(v),
+            Field::Int(v) => num_to_dword(v),
+            Field::Dword64(v) => self.save_bytes(&v.to_le_bytes()),
+            Field::Int64(v) => self.save_bytes(&v.to_le_bytes()),
+            Field::Float(v) => num_to_dword(v),
+            Field::Double(v) => self.save_bytes(&v.to_le_bytes()),
+            Field::String(v) => {
+                let bytes = self.code_page.encode(&v);
+                self.save_bytes(&bytes_to_sized_bytes::<DWORD_SIZE>(&bytes))
             }
-        }
+            Field::ResRef(v) => self.save_bytes(&bytes_to_sized_bytes::<1>(v.as_bytes())),
+            Field::LocString((str_ref, v)) => {
+                let string_count = v.len();
+                // Total Size will be added in the end
+                let mut bytes = Vec::with_capacity(string_count * 10 + 2 * DWORD_SIZE);
+                // StringRef
+                bytes.extend(str_ref.to_le_bytes());
+                // StringCount
+                bytes.extend((string_count as u32).to_le_bytes());
+                for s in v {
+                    // StringID
+                    bytes.extend(s.id.to_le_bytes());
+                    // Length + String itself
+                    let content = CodePage::from_string_id(s.id, self.code_page).encode(&s.content);
+                    bytes.extend(bytes_to_sized_bytes::<DWORD_SIZE>(&content));
+                }
 
-        self.save_struct(s.tp, my_idx, field_count as u32);
+                self.save_bytes(&bytes_to_sized_bytes::<DWORD_SIZE>(&bytes))
+            }
+            Field::Void(v) => self.save_bytes(&bytes_to_sized_bytes::<DWORD_SIZE>(&v)),
+            Field::BStruct(s) => self.save_struct(*s),
+            Field::List(list) => self.save_list(list),
+            Field::Orientation(v) => self.save_bytes([v.w, v.x, v.y, v.z].into_byte_slice()),
+            Field::Vector(v) => self.save_bytes([v.x, v.y, v.z].into_byte_slice()),
+            Field::Invalid => unreachable!(),
+        };
 
-        my_idx
+        self.fields[idx][2] = content;
     }
 
-    fn into_bytes(mut self) -> Vec<u8> {
-        let buf = Vec::with_capacity(self.fields.len() * 12 * DWORD_SIZE);
+    fn into_bytes(self) -> Vec<u8> {
+        let buf = Vec::with_capacity(
+            HEADER_SIZE * DWORD_SIZE
+                + (self.structs.len() * STRUCT_SIZE + self.fields.len() * FIELD_SIZE) * DWORD_SIZE
+                + self.labels.len() * MAX_LABEL_LEN
+                + self.raw_data.len()
+                + (self.field_indices.len() + self.list_indices.len()) * DWORD_SIZE,
+        );
         let mut cursor = Cursor::new(buf);
-        let field_count = self.field_count;
         let file_type = self.file_head.tp;
         let file_version = self.file_head.version;
 
-        // sort structs propertly
-        self.structs.sort_by_key(|s| s.my_idx);
-        // need to flatten fields and build indices first
-        let mut field_bytes = Vec::with_capacity(field_count * FIELD_SIZE * DWORD_SIZE);
-        // matches self.fields indices and stores the index of the first field in a group
-        let mut field_group_offsets = Vec::with_capacity(self.fields.len());
-        let mut field_idx = 0;
-        for group in self.fields {
-            field_group_offsets.push(field_idx);
-            for field in group {
-                field_idx += 1;
-                let data = [field.tp, field.label_index, field.content];
-                field_bytes.write_all(data.into_byte_slice()).unwrap();
-            }
-        }
         // header to be filled later
         cursor.write_all(&[0; HEADER_SIZE * DWORD_SIZE]).unwrap();
         // STRUCTS
         let struct_offset = cursor.position();
         let struct_count = self.structs.len();
-        let mut field_indices = Vec::with_capacity(field_count);
-        for s in self.structs {
-            // structs with 1 field refer to it directly instead of storing it's index in field indices
-            let starting_field_idx = field_group_offsets[s.my_idx];
-            let idx = if s.field_count == 1 {
-                starting_field_idx
-            } else {
-                let index_offset = field_indices.len();
-                for i in 0..s.field_count {
-                    field_indices.push(starting_field_idx + i);
-                }
-                (index_offset * DWORD_SIZE) as u32
-            };
-            let data = [s.tp, idx, s.field_count];
-            cursor.write_all(data.into_byte_slice()).unwrap();
-        }
+        cursor
+            .write_all(self.structs.as_slice().into_byte_slice())
+            .unwrap();
         // FIELDS
         let field_offset = cursor.position();
-        cursor.write_all(&field_bytes).unwrap();
+        let field_count = self.fields.len();
+        cursor
+            .write_all(self.fields.as_slice().into_byte_slice())
+            .unwrap();
         // LABELS
         let label_offset = cursor.position();
         let label_count = self.labels.len();
@@ -248,13 +202,15 @@ impl Writer {
         cursor.write_all(&self.raw_data).unwrap();
         // FIELD INDICES
         let field_indices_offset = cursor.position();
-        let field_indices_bytes = field_indices.len() * DWORD_SIZE;
-        cursor.write_all(field_indices.into_byte_slice()).unwrap();
+        let field_indices_bytes = self.field_indices.len() * DWORD_SIZE;
+        cursor
+            .write_all(self.field_indices.as_slice().into_byte_slice())
+            .unwrap();
         // LIST INDICES
         let list_indices_offset = cursor.position();
         let list_indices_bytes = self.list_indices.len() * DWORD_SIZE;
         cursor
-            .write_all(self.list_indices.into_byte_slice())
+            .write_all(self.list_indices.as_slice().into_byte_slice())
             .unwrap();
         // HEADER
         cursor.rewind().unwrap();
@@ -283,6 +239,7 @@ impl Writer {
         cursor.into_inner()
     }
 }
+
 pub fn write(gff: Gff) -> Vec<u8> {
     let writer = Writer::new(gff);
     writer.into_bytes()
@@ -3,24 +3,157 @@ use ahash::HashMap;
 use std::path::PathBuf;
 
 mod read;
-pub use read::*;
+mod write;
+
+pub use write::*;
+
+// 6 DWORD fields after the file head + 32 bytes reserved
+const HEADER_SIZE: usize = 6;
+const HEADER_PADDING_SIZE_BYTES: usize = 32;
+// 2 DWORDs, 2 WORDs
+const FILE_SIZE_BYTES: usize = 2 * 4 + 2 * 2;
+const RESOURCE_NAME_LEN: usize = 16;
+// name, WORD type, DWORD id
+const RESOURCE_SIZE_BYTES: usize = RESOURCE_NAME_LEN + 2 + 4;
+// the upper 12 bits of resource ids
+const FILE_IDX_SHIFT: u32 = 20;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct KeyResRef {
-    // index into Key's file_names
+    // index into Key's files
     pub file_idx: u32,
     // bif resource index
     pub resource_idx: u32,
 }
+
+impl KeyResRef {
+    pub fn from_id(id: u32) -> Self {
+        let file_idx = id >> FILE_IDX_SHIFT;
+        Self {
+            file_idx,
+            resource_idx: id - (file_idx << FILE_IDX_SHIFT),
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.file_idx << FILE_IDX_SHIFT | self.resource_idx
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyFile {
+    // relative to the game dir, with \\ as the separator
+    pub name: String,
+    pub size: u32,
+    // which media the file is on, unused by the game
+    pub drives: u16,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Key {
-    pub file_names: Vec<String>,
+    pub files: Vec<KeyFile>,
     pub resources: HashMap<ResourceKey, KeyResRef>,
+    // years since 1900 and days since January 1st
+    pub build_year: u32,
+    pub build_day: u32,
 }
 
 impl Key {
     pub fn get_file_path(&self, idx: u32) -> PathBuf {
-        let file_name = &self.file_names[idx as usize];
+        let file_name = &self.files[idx as usize].name;
         file_name.split('\\').collect()
     }
+
+    // returns the index to use for resources in this file
+    pub fn add_file(&mut self, name: String, size: u32) -> u32 {
+        self.files.push(KeyFile {
+            name,
+            size,
+            drives: 0,
+        });
+        (self.files.len() - 1) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formats::{
+        bif::{self, Bif},
+        key::{self, Key},
+        ReadResource as _, ResourceType,
+    };
+    use ahash::HashMap;
+
+    #[test]
+    fn read_write() {
+        let mut key = Key {
+            files: vec![],
+            resources: HashMap::default(),
+            build_year: 124,
+            build_day: 1,
+        };
+        let mut bifs = vec![];
+        for (bif_name, resources) in [
+            (
+                "data\\2da.bif",
+                [
+                    ("feat", ResourceType::Twoda),
+                    // not in the type table
+                    ("spells", ResourceType::Unknown(4000)),
+                ],
+            ),
+            (
+                "data\\templates.bif",
+                [
+                    ("g_w_lghtsbr01", ResourceType::Uti),
+                    ("global", ResourceType::Jrl),
+                ],
+            ),
+        ] {
+            let mut bif = Bif::default();
+            let file_idx = key.files.len() as u32;
+            for (name, tp) in resources {
+                let res_ref = bif.push(file_idx, tp, name.as_bytes().to_vec());
+                key.resources.insert((name, tp).into(), res_ref);
+            }
+            let bytes = bif::write(bif);
+            key.add_file(bif_name.to_owned(), bytes.len() as u32);
+            bifs.push(bytes);
+        }
+
+        let bytes = key::write(key.clone());
+        let new_key = Key::read(&bytes, ()).unwrap();
+        assert_eq!(key, new_key);
+        assert_eq!(bytes, key::write(new_key.clone()));
+        assert!(new_key
+            .resources
+            .contains_key(&("spells", ResourceType::from_id(4000)).into()));
+
+        let res_ref = &new_key.resources[&("global", ResourceType::Jrl).into()];
+        assert_eq!(res_ref.id(), 1 << 20 | 1);
+        assert_eq!(
+            new_key.get_file_path(res_ref.file_idx),
+            ["data", "templates.bif"]
+                .iter()
+                .collect::<std::path::PathBuf>()
+        );
+
+        let bif_bytes = &bifs[res_ref.file_idx as usize];
+        assert_eq!(new_key.files[1].size as usize, bif_bytes.len());
+        let bif = Bif::read(bif_bytes, &[0, res_ref.resource_idx as usize]).unwrap();
+        assert_eq!(bif.resources[0].content, b"g_w_lghtsbr01");
+        assert_eq!(bif.resources[1].content, b"global");
+        assert_eq!(bif.resources[1].id, res_ref.id());
+        assert_eq!(bif.resources[1].tp, ResourceType::Jrl);
+
+        let entries = bif::read_entries(bif_bytes).unwrap();
+        assert_eq!(entries.len(), 2);
+        let entry = &entries[res_ref.resource_idx as usize];
+        assert_eq!((entry.id, entry.tp), (res_ref.id(), ResourceType::Jrl));
+        assert_eq!(
+            bif_bytes[entry.offset..entry.offset + entry.size],
+            *b"global"
+        );
+        assert!(bif::read_entries(&bif_bytes[..bif_bytes.len() - 1]).is_err());
+    }
 }
@@ -1,140 +1,158 @@
 use crate::{
     formats::{
         impl_read_resource,
-        key::{Key, KeyResRef},
-        ReadResource, ResourceKey, ResourceType,
-    },
-    util::{
-        bytes::{
-            take, take_bytes, take_head, take_string_trimmed, Cursor, IntoUsizeArray, SeekExt as _,
-            DWORD_SIZE,
+        key::{
+            Key, KeyFile, KeyResRef, FILE_SIZE_BYTES, HEADER_SIZE, RESOURCE_NAME_LEN,
+            RESOURCE_SIZE_BYTES,
         },
-        SResult,
+        ErrorKind, FResult, FormatError, ReadResource, ReadResult, ResourceKey, ResourceType,
+    },
+    util::bytes::{
+        take, take_bytes, take_head, take_string_trimmed, Budget, Cursor, IntoUsizeArray,
+        SeekExt as _,
     },
 };
 use ahash::{HashMap, HashMapExt as _};
-use std::io::BufRead as _;
 
 struct Header {
     file_count: usize,
     file_offset: usize,
     key_count: usize,
     key_offset: usize,
+    build_year: u32,
+    build_day: u32,
+}
+
+struct FileRead {
+    size: u32,
+    name_offset: u32,
+    name_size: u16,
+    drives: u16,
 }
 
 struct Reader<'a> {
     c: &'a mut Cursor<'a>,
+    budget: Budget,
 }
 
 impl<'a> Reader<'a> {
     fn new(c: &'a mut Cursor<'a>, _: ()) -> Self {
-        Self { c }
+        let budget = Budget::new(c.get_ref().len());
+        Self { c, budget }
     }
 
-    fn read(mut self) -> SResult<Key> {
+    fn read(&mut self) -> ReadResult<Key> {
         let h = self.read_header()?;
         let file_data = self.read_file_data(h.file_count, h.file_offset)?;
-        let file_names = self.read_file_names(file_data)?;
-        let resources = self.read_resources(h.key_count, h.key_offset, &file_names)?;
+        let files = self.read_files(file_data)?;
+        let resources = self.read_resources(h.key_count, h.key_offset, &files)?;
 
         Ok(Key {
-            file_names,
+            files,
             resources,
+            build_year: h.build_year,
+            build_day: h.build_day,
         })
     }
 
-    fn read_header(&mut self) -> SResult<Header> {
-        let file_head = take_head(self.c).ok_or("couldn't read file head")?;
+    fn read_header(&mut self) -> ReadResult<Header> {
+        let file_head = take_head(self.c)?;
 
         if file_head.tp != "KEY " || file_head.version != "V1  " {
-            return Err(format!("invalid file type or version {file_head:?}"));
+            return Err(ErrorKind::Header(file_head));
         }
-        let [file_count, key_count, file_offset, key_offset] = take::<[u32; 4]>(self.c)
-            .ok_or("couldn't read header contents")?
-            .into_usize_array();
+        let [file_count, key_count, file_offset, key_offset, build_year, build_day] =
+            take::<[u32; HEADER_SIZE]>(self.c)
+                .ok_or_else(|| ErrorKind::Truncated("header contents".to_owned()))?
+                .into_usize_array();
 
         Ok(Header {
             file_count,
             file_offset,
             key_count,
             key_offset,
+            build_year: build_year as u32,
+            build_day: build_day as u32,
         })
     }
 
-    fn read_file_data(&mut self, count: usize, offset: usize) -> SResult<Vec<(u32, u16)>> {
-        // 2 dwords, 2 words
-        const FILE_SIZE_BYTES: usize = DWORD_SIZE * 2 + 2 * 2;
+    fn read_file_data(&mut self, count: usize, offset: usize) -> ReadResult<Vec<FileRead>> {
         self.c.seek_to(offset)?;
-        let file_bytes =
-            take_bytes(self.c, count * FILE_SIZE_BYTES).ok_or("couldn't read file table")?;
+        let file_bytes = count
+            .checked_mul(FILE_SIZE_BYTES)
+            .and_then(|len| take_bytes(self.c, len))
+            .ok_or_else(|| ErrorKind::Truncated("file table".to_owned()))?;
         let c = &mut Cursor::new(file_bytes);
-        let mut name_data = Vec::with_capacity(count);
+        let mut file_data = Vec::with_capacity(count);
         for _ in 0..count {
-            // don't care about file size
-            c.consume(DWORD_SIZE);
-            let name_offset = take::<u32>(c).unwrap();
-            let name_size = take::<u16>(c).unwrap();
-            // don't care about drive
-            c.consume(2);
-
-            name_data.push((name_offset, name_size));
+            let [size, name_offset] = take::<[u32; 2]>(c).unwrap();
+            let [name_size, drives] = take::<[u16; 2]>(c).unwrap();
+
+            file_data.push(FileRead {
+                size,
+                name_offset,
+                name_size,
+                drives,
+            });
         }
 
-        Ok(name_data)
+        Ok(file_data)
     }
 
-    fn read_file_names(&mut self, data: Vec<(u32, u16)>) -> SResult<Vec<String>> {
-        let mut names = Vec::with_capacity(data.len());
-        for (offset, size) in data {
+    fn read_files(&mut self, data: Vec<FileRead>) -> ReadResult<Vec<KeyFile>> {
+        let mut files = Vec::with_capacity(data.len());
+        for f in data {
+            let (offset, size) = (f.name_offset, f.name_size);
             self.c.seek_to(offset)?;
             // they appear to be null-terminated
-            let name = take_string_trimmed(self.c, size as usize)
-                .ok_or_else(|| format!("couldn't read file name of size {size} at {offset}"))?;
-            names.push(name);
+            let name = take_string_trimmed(self.c, size as usize).ok_or_else(|| {
+                ErrorKind::Truncated(format!("file name of size {size} at {offset}"))
+            })?;
+            self.budget
+                .spend(name.len())
+                .ok_or_else(|| ErrorKind::TooLarge(format!("file name at {offset}")))?;
+            files.push(KeyFile {
+                name,
+                size: f.size,
+                drives: f.drives,
+            });
         }
-        Ok(names)
+        Ok(files)
     }
 
     fn read_resources(
         &mut self,
         count: usize,
         offset: usize,
-        file_names: &[String],
-    ) -> SResult<HashMap<ResourceKey, KeyResRef>> {
-        const RESOURCE_SIZE_BYTES: usize = 22;
+        files: &[KeyFile],
+    ) -> ReadResult<HashMap<ResourceKey, KeyResRef>> {
         self.c.seek_to(offset)?;
-        let bytes =
-            take_bytes(self.c, count * RESOURCE_SIZE_BYTES).ok_or("couldn't read key table")?;
+        let bytes = count
+            .checked_mul(RESOURCE_SIZE_BYTES)
+            .and_then(|len| take_bytes(self.c, len))
+            .ok_or_else(|| ErrorKind::Truncated("key table".to_owned()))?;
         let c = &mut Cursor::new(bytes);
         let mut resources = HashMap::with_capacity(count);
 
         for _ in 0..count {
-            let file_name = take_string_trimmed(c, 16).unwrap();
+            let file_name = take_string_trimmed(c, RESOURCE_NAME_LEN).unwrap();
             let tp_raw = take::<u16>(c).unwrap();
             let id = take::<u32>(c).unwrap();
-            let Ok(tp) = ResourceType::try_from(tp_raw) else {
-                // we don't care about most resource types and the relevant ones are defined
-                continue;
-            };
-            let file_idx = id >> 20;
-            if file_names.get(file_idx as usize).is_none() {
+            let tp = ResourceType::from_id(tp_raw);
+            let res_ref = KeyResRef::from_id(id);
+            let file_idx = res_ref.file_idx;
+            if files.get(file_idx as usize).is_none() {
                 return Err(format!(
                     "resource {file_name} references invalid file index {file_idx}"
-                ));
+                )
+                .into());
             }
-            let resource_idx = id - (file_idx << 20);
-
-            resources.insert(
-                (file_name, tp).into(),
-                KeyResRef {
-                    file_idx,
-                    resource_idx,
-                },
-            );
+
+            resources.insert((file_name, tp).into(), res_ref);
         }
 
         Ok(resources)
     }
 }
 
-impl_read_resource!(Key, Reader);
+impl_read_resource!(Key, Reader, (), ResourceType::Key);
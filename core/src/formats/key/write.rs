@@ -0,0 +1,87 @@
+use crate::{
+    formats::key::{
+        Key, FILE_SIZE_BYTES, HEADER_PADDING_SIZE_BYTES, HEADER_SIZE, RESOURCE_NAME_LEN,
+        RESOURCE_SIZE_BYTES,
+    },
+    util::bytes::{IntoByteSlice as _, DWORD_SIZE},
+};
+use std::io::{Cursor, Write};
+
+pub struct Writer {
+    key: Key,
+}
+
+impl Writer {
+    fn new(key: Key) -> Self {
+        Self { key }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let Key {
+            files,
+            resources,
+            build_year,
+            build_day,
+        } = self.key;
+        let file_offset = 8 + HEADER_SIZE * DWORD_SIZE + HEADER_PADDING_SIZE_BYTES;
+        let names_offset = file_offset + files.len() * FILE_SIZE_BYTES;
+        // null-terminated
+        let names_len: usize = files.iter().map(|f| f.name.len() + 1).sum();
+        let key_offset = names_offset + names_len;
+        let mut cursor = Cursor::new(Vec::with_capacity(
+            key_offset + resources.len() * RESOURCE_SIZE_BYTES,
+        ));
+
+        // HEADER
+        cursor.write_all(b"KEY V1  ").unwrap();
+        cursor
+            .write_all(
+                [
+                    files.len() as u32,
+                    resources.len() as u32,
+                    file_offset as u32,
+                    key_offset as u32,
+                    build_year,
+                    build_day,
+                ]
+                .into_byte_slice(),
+            )
+            .unwrap();
+        cursor.write_all(&[0; HEADER_PADDING_SIZE_BYTES]).unwrap();
+        // FILES
+        let mut names = Vec::with_capacity(names_len);
+        for f in files {
+            let name_offset = (names_offset + names.len()) as u32;
+            names.extend(f.name.as_bytes());
+            names.push(b'\0');
+
+            cursor
+                .write_all([f.size, name_offset].into_byte_slice())
+                .unwrap();
+            cursor
+                .write_all([f.name.len() as u16 + 1, f.drives].into_byte_slice())
+                .unwrap();
+        }
+        // FILE NAMES
+        cursor.write_all(&names).unwrap();
+        // RESOURCES
+        let mut resources: Vec<_> = resources.into_iter().collect();
+        // the map has no order, going by BIF and index in it
+        resources.sort_unstable_by_key(|(_, res_ref)| res_ref.id());
+        for (key, res_ref) in resources {
+            let mut name = key.0.into_bytes();
+            name.resize(RESOURCE_NAME_LEN, 0);
+
+            cursor.write_all(&name).unwrap();
+            cursor.write_all(&key.1.to_id().to_le_bytes()).unwrap();
+            cursor.write_all(&res_ref.id().to_le_bytes()).unwrap();
+        }
+
+        cursor.into_inner()
+    }
+}
+
+pub fn write(key: Key) -> Vec<u8> {
+    let writer = Writer::new(key);
+    writer.into_bytes()
+}
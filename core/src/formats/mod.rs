@@ -1,14 +1,18 @@
-use crate::util::SResult;
-use macros::{EnumFromInt, EnumToString};
+use macros::{EnumFromInt, EnumToInt, EnumToString};
 use serde::{Deserialize, Serialize};
 
 pub mod bif;
 pub mod erf;
+mod error;
 pub mod gff;
 pub mod key;
+pub mod rim;
 pub mod tlk;
+pub mod tpc;
 pub mod twoda;
 
+pub use error::*;
+
 #[derive(Debug, Default, PartialEq, Clone)]
 pub struct FileHead {
     pub tp: String,
@@ -28,27 +32,119 @@ impl From<(&str, &str)> for FileHead {
 }
 
 #[repr(u16)]
-#[derive(EnumFromInt, EnumToString, Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[derive(EnumToInt, EnumFromInt, EnumToString, Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum ResourceType {
-    Unknown = 0,
+    Res = 0,
+    Bmp = 1,
+    Mve = 2,
+    Tga = 3,
+    Wav = 4,
+    Plt = 6,
+    Ini = 7,
+    Mp3 = 8,
+    Mpg = 9,
     Txt = 10,
+    Wma = 11,
+    Wmv = 12,
+    Xmv = 13,
+    Plh = 2000,
+    Tex = 2001,
+    Mdl = 2002,
+    Thg = 2003,
+    Fnt = 2005,
+    Lua = 2007,
+    Slt = 2008,
+    Nss = 2009,
+    Ncs = 2010,
+    Mod = 2011,
     Are = 2012,
+    Set = 2013,
     Ifo = 2014,
+    Bic = 2015,
+    Wok = 2016,
     Twoda = 2017,
+    Tlk = 2018,
     Txi = 2022,
     Git = 2023,
+    Bti = 2024,
     Uti = 2025,
+    Btc = 2026,
     Utc = 2027,
+    Dlg = 2029,
+    Itp = 2030,
+    Btt = 2031,
+    Utt = 2032,
+    Dds = 2033,
+    Bts = 2034,
+    Uts = 2035,
+    Ltr = 2036,
+    Gff = 2037,
     Fac = 2038,
+    Bte = 2039,
+    Ute = 2040,
+    Btd = 2041,
+    Utd = 2042,
+    Btp = 2043,
+    Utp = 2044,
+    Dft = 2045,
+    Gic = 2046,
+    Gui = 2047,
+    Css = 2048,
+    Ccs = 2049,
+    Btm = 2050,
+    Utm = 2051,
+    Dwk = 2052,
+    Pwk = 2053,
+    Btg = 2054,
+    Utg = 2055,
     Jrl = 2056,
     Sav = 2057,
+    Utw = 2058,
+    FourPc = 2059,
+    Ssf = 2060,
+    Hak = 2061,
+    Nwm = 2062,
+    Bik = 2063,
+    Ndb = 2064,
+    Ptm = 2065,
+    Ptt = 2066,
+    Lyt = 3000,
+    Vis = 3001,
+    Rim = 3002,
+    Pth = 3003,
+    Lip = 3004,
+    Bwm = 3005,
+    Txb = 3006,
     Tpc = 3007,
+    Mdx = 3008,
+    Rsv = 3009,
+    Sig = 3010,
+    Xbx = 3011,
+    Erf = 9997,
+    Bif = 9998,
+    Key = 9999,
+    // anything not listed above, keeps the original ID
+    Unknown(u16) = u16::MAX,
 }
 
 impl ResourceType {
+    pub fn from_id(id: u16) -> Self {
+        Self::try_from(id).unwrap_or(Self::Unknown(id))
+    }
+
+    pub fn to_id(self) -> u16 {
+        match self {
+            Self::Unknown(id) => id,
+            _ => self.to_int(),
+        }
+    }
+
     pub fn to_extension(self) -> String {
         match self {
             Self::Twoda => "2da".to_owned(),
+            Self::FourPc => "4pc".to_owned(),
+            // there's no extension so the ID will do
+            Self::Unknown(id) => id.to_string(),
             _ => self.to_str().to_lowercase(),
         }
     }
@@ -71,12 +167,12 @@ impl From<(&str, ResourceType)> for ResourceKey {
 
 pub trait ReadResource<'a, Arg: 'a + Copy>: Sized {
     fn get_type() -> ResourceType;
-    fn read(bytes: &[u8], arg: Arg) -> SResult<Self>;
+    fn read(bytes: &[u8], arg: Arg) -> FResult<Self>;
 }
 
 pub trait ReadResourceNoArg: Sized {
     fn get_type() -> ResourceType;
-    fn read(bytes: &[u8]) -> SResult<Self>;
+    fn read(bytes: &[u8]) -> FResult<Self>;
 }
 
 impl<'a, T: ReadResource<'a, ()>> ReadResourceNoArg for T {
@@ -84,33 +180,31 @@ impl<'a, T: ReadResource<'a, ()>> ReadResourceNoArg for T {
         T::get_type()
     }
 
-    fn read(bytes: &[u8]) -> SResult<Self> {
+    fn read(bytes: &[u8]) -> FResult<Self> {
         T::read(bytes, ())
     }
 }
 
+// readers take the cursor and the argument in new and return ReadResult from read(&mut self),
+// the error gets the type and the position the reader stopped at
 macro_rules! impl_read_resource {
     ($t:ident, $reader:ident, $arg:ty, $type:expr) => {
         impl<'a> ReadResource<'a, $arg> for $t {
             fn get_type() -> ResourceType {
                 $type
             }
-            fn read(bytes: &[u8], arg: $arg) -> SResult<Self> {
+            fn read(bytes: &[u8], arg: $arg) -> FResult<Self> {
                 let c = &mut Cursor::new(bytes);
-                $reader::new(c, arg)
-                    .read()
-                    .map_err(|err| format!("{}::read| {err}", stringify!($t)))
+                let mut reader = $reader::new(c, arg);
+                reader.read().map_err(|kind| FormatError {
+                    format: $type,
+                    resource: None,
+                    offset: Some(reader.c.position()),
+                    kind,
+                })
             }
         }
     };
-
-    ($t:ident, $reader:ident, $arg:ty) => {
-        impl_read_resource!($t, $reader, $arg, ResourceType::Unknown);
-    };
-
-    ($t:ident, $reader:ident) => {
-        impl_read_resource!($t, $reader, ());
-    };
 }
 pub(crate) use impl_read_resource;
 
@@ -119,3 +213,322 @@ pub struct LocString {
     pub id: u32,
     pub content: String,
 }
+
+// Untrusted files, like uploaded saves, must never panic or exhaust memory.
+// Valid files of each format get random dwords overwritten and get cut short,
+// reading them may fail but has to return.
+#[cfg(test)]
+mod tests {
+    use crate::formats::{
+        bif::{self, Bif},
+        erf::{self, Erf, Resource},
+        gff::{self, Field, Gff, Struct},
+        key::{self, Key},
+        rim::{self, Rim},
+        tlk::{self, Tlk, TlkEntry},
+        tpc::Tpc,
+        twoda::{self, TwoDA, TwoDAFormat, TwoDARow},
+        ErrorKind, LocString, ReadResource as _, ResourceType,
+    };
+    use ahash::HashMap;
+    use proptest::prelude::*;
+
+    fn mutated(seed: Vec<u8>) -> impl Strategy<Value = Vec<u8>> {
+        let len = seed.len();
+        let value = prop_oneof![
+            Just(0),
+            Just(1),
+            Just(u32::MAX),
+            Just(u32::MAX / 2),
+            Just(len as u32),
+            any::<u32>(),
+        ];
+        (
+            prop::collection::vec((0..len, value), 1..8),
+            prop::option::weighted(0.2, 0..len),
+        )
+            .prop_map(move |(patches, cut)| {
+                let mut bytes = seed.clone();
+                for (pos, value) in patches {
+                    let end = (pos + 4).min(len);
+                    bytes[pos..end].copy_from_slice(&value.to_le_bytes()[..end - pos]);
+                }
+                if let Some(cut) = cut {
+                    bytes.truncate(cut);
+                }
+                bytes
+            })
+    }
+
+    fn make_gff() -> Vec<u8> {
+        let item = |tag: &str| {
+            Struct::new(vec![
+                ("Tag", Field::String(tag.to_owned())),
+                (
+                    "Name",
+                    Field::LocString((
+                        1,
+                        vec![LocString {
+                            id: 0,
+                            content: tag.to_owned(),
+                        }],
+                    )),
+                ),
+                ("Data", Field::Void(vec![1, 2, 3])),
+            ])
+        };
+        gff::write(Gff::new(
+            ("UTC ", "V3.2").into(),
+            Struct::new(vec![
+                ("Byte", Field::Byte(1)),
+                ("Dword64", Field::Dword64(2)),
+                ("ResRef", Field::ResRef("resref".to_owned())),
+                ("Inner", Field::BStruct(Box::new(item("inner")))),
+                ("List", Field::List(vec![item("first"), item("second")])),
+            ]),
+        ))
+    }
+
+    fn make_erf() -> Vec<u8> {
+        let mut erf = Erf::new(("SAV ", "V1.0").into());
+        for (name, tp) in [("pc", ResourceType::Utc), ("inventory", ResourceType::Res)] {
+            erf.resources.insert(
+                (name, tp).into(),
+                Resource {
+                    name: name.to_owned(),
+                    id: erf.resources.len() as u32,
+                    content: name.as_bytes().into(),
+                },
+            );
+        }
+        erf.loc_strings.push(LocString {
+            id: 0,
+            content: "Save".to_owned(),
+        });
+        erf::write(erf)
+    }
+
+    fn make_rim() -> Vec<u8> {
+        let mut rim = Rim::new();
+        for (name, tp) in [("module", ResourceType::Ifo), ("m01aa", ResourceType::Git)] {
+            rim.resources.insert(
+                (name, tp).into(),
+                Resource {
+                    name: name.to_owned(),
+                    id: rim.resources.len() as u32,
+                    content: name.as_bytes().into(),
+                },
+            );
+        }
+        rim::write(rim)
+    }
+
+    fn make_key_bif() -> (Vec<u8>, Vec<u8>) {
+        let mut key = Key {
+            files: vec![],
+            resources: HashMap::default(),
+            build_year: 124,
+            build_day: 1,
+        };
+        let mut bif = Bif::default();
+        for (name, tp) in [("feat", ResourceType::Twoda), ("global", ResourceType::Jrl)] {
+            let res_ref = bif.push(0, tp, name.as_bytes().to_vec());
+            key.resources.insert((name, tp).into(), res_ref);
+        }
+        let bif = bif::write(bif);
+        key.add_file("data\\2da.bif".to_owned(), bif.len() as u32);
+
+        (key::write(key), bif)
+    }
+
+    fn make_tlk() -> Vec<u8> {
+        let mut tlk = Tlk::new(0);
+        tlk.push(TlkEntry::new("First".to_owned()));
+        tlk.push(TlkEntry::default());
+        tlk.push(TlkEntry::new("Second".to_owned()));
+        tlk::write(tlk)
+    }
+
+    // 8x8 DXT5 with a 4x4 mipmap, then the TXI
+    fn make_tpc() -> Vec<u8> {
+        let mut bytes = 64u32.to_le_bytes().to_vec();
+        bytes.extend(0f32.to_le_bytes());
+        bytes.extend([8, 0, 8, 0, 0x04, 2]);
+        bytes.resize(128, 0);
+        bytes.extend((0..80).map(|b| b as u8));
+        bytes.extend(b"mipmap 0\r\n");
+        bytes
+    }
+
+    fn make_twoda() -> Vec<u8> {
+        twoda::write(TwoDA {
+            format: TwoDAFormat::Binary,
+            default: None,
+            columns: vec!["label".to_owned(), "name".to_owned()],
+            rows: vec![
+                TwoDARow {
+                    label: "0".to_owned(),
+                    cells: vec![Some("FIRST".to_owned()), None],
+                },
+                TwoDARow {
+                    label: "1".to_owned(),
+                    cells: vec![Some("FIRST".to_owned()), Some("100".to_owned())],
+                },
+            ],
+        })
+        .unwrap()
+    }
+
+    // anything that could be read has to be written and read again as well
+    proptest! {
+        #[test]
+        fn gff(bytes in mutated(make_gff())) {
+            if let Ok(report) = gff::validate(&bytes) {
+                report.to_string();
+            }
+            if let Ok(gff) = Gff::read(&bytes, ()) {
+                let bytes = gff::write(gff);
+                prop_assert!(Gff::read(&bytes, ()).is_ok());
+            }
+        }
+
+        #[test]
+        fn erf(bytes in mutated(make_erf())) {
+            if let Ok(erf) = Erf::read(&bytes, ()) {
+                let bytes = erf::write(erf);
+                prop_assert!(Erf::read(&bytes, ()).is_ok());
+            }
+            if let Ok(entries) = erf::read_entries(&bytes) {
+                for entry in entries {
+                    let _ = &bytes[entry.offset..entry.offset + entry.size];
+                }
+            }
+        }
+
+        #[test]
+        fn rim(bytes in mutated(make_rim())) {
+            if let Ok(rim) = Rim::read(&bytes, ()) {
+                let bytes = rim::write(rim);
+                prop_assert!(Rim::read(&bytes, ()).is_ok());
+            }
+            if let Ok(entries) = rim::read_entries(&bytes) {
+                for entry in entries {
+                    let _ = &bytes[entry.offset..entry.offset + entry.size];
+                }
+            }
+        }
+
+        #[test]
+        fn key(bytes in mutated(make_key_bif().0)) {
+            if let Ok(key) = Key::read(&bytes, ()) {
+                let bytes = key::write(key);
+                prop_assert!(Key::read(&bytes, ()).is_ok());
+            }
+        }
+
+        #[test]
+        fn bif(bytes in mutated(make_key_bif().1), idx in 0..4usize) {
+            if let Ok(bif) = Bif::read(&bytes, &[0, idx, usize::MAX]) {
+                let bytes = bif::write(bif);
+                prop_assert!(Bif::read(&bytes, &[]).is_ok());
+            }
+            if let Ok(entries) = bif::read_entries(&bytes) {
+                for entry in entries {
+                    let _ = &bytes[entry.offset..entry.offset + entry.size];
+                }
+            }
+        }
+
+        #[test]
+        fn tlk(bytes in mutated(make_tlk()), idx in any::<u32>()) {
+            if let Ok(tlk) = Tlk::read(&bytes, ()) {
+                let bytes = tlk::write(tlk);
+                prop_assert!(Tlk::read(&bytes, ()).is_ok());
+            }
+            if let Ok(header) = tlk::read_header(&bytes) {
+                for idx in [0, 2, idx] {
+                    let _ = tlk::read_text(&bytes, &header, idx);
+                }
+            }
+        }
+
+        #[test]
+        fn tpc(bytes in mutated(make_tpc())) {
+            let _ = Tpc::read(&bytes, ());
+        }
+
+        #[test]
+        fn twoda(bytes in mutated(make_twoda())) {
+            if let Ok(twoda) = TwoDA::read(&bytes, ()) {
+                // tables the format can't hold are refused rather than written
+                if let Ok(bytes) = twoda::write(twoda) {
+                    prop_assert!(TwoDA::read(&bytes, ()).is_ok());
+                }
+            }
+        }
+
+        #[test]
+        fn any_bytes(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+            let _ = Gff::read(&bytes, ());
+            let _ = Erf::read(&bytes, ());
+            let _ = Rim::read(&bytes, ());
+            let _ = Key::read(&bytes, ());
+            let _ = Bif::read(&bytes, &[0]);
+            let _ = Tlk::read(&bytes, ());
+            let _ = Tpc::read(&bytes, ());
+            let _ = TwoDA::read(&bytes, ());
+        }
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let mut s = Struct::new(vec![]);
+        for _ in 0..100 {
+            s = Struct::new(vec![("Inner", Field::BStruct(Box::new(s)))]);
+        }
+        let bytes = gff::write(Gff::new(("TST ", "V0.0").into(), s));
+        let err = Gff::read(&bytes, ()).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::TooLarge(_)), "{err}");
+    }
+
+    #[test]
+    fn shared_fields() {
+        let mut bytes = make_gff();
+        // the struct of the second list item uses the fields of the first one
+        let struct_offset = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let first = struct_offset + 2 * 12;
+        let fields = bytes[first + 4..first + 12].to_vec();
+        bytes[first + 16..first + 24].copy_from_slice(&fields);
+
+        let err = Gff::read(&bytes, ()).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Invalid(_)), "{err}");
+    }
+
+    #[test]
+    fn shared_content() {
+        let mut erf = Erf::new(("SAV ", "V1.0").into());
+        for idx in 0..1000 {
+            let name = format!("r{idx}");
+            let content = if idx == 0 { vec![0; 4000] } else { vec![0] };
+            erf.resources.insert(
+                (name.as_str(), ResourceType::Res).into(),
+                Resource {
+                    name,
+                    id: idx,
+                    content,
+                },
+            );
+        }
+        let mut bytes = erf::write(erf);
+        // every resource points at the big one
+        let resources_offset = u32::from_le_bytes(bytes[28..32].try_into().unwrap()) as usize;
+        let first = bytes[resources_offset..resources_offset + 8].to_vec();
+        for idx in 1..1000 {
+            let offset = resources_offset + idx * 8;
+            bytes[offset..offset + 8].copy_from_slice(&first);
+        }
+
+        let err = Erf::read(&bytes, ()).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::TooLarge(_)), "{err}");
+    }
+}
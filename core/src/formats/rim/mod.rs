@@ -0,0 +1,94 @@
+use crate::formats::{
+    erf::{Resource, Resources},
+    FileHead, ResourceKey, ResourceType,
+};
+
+mod read;
+mod write;
+
+pub use read::read_entries;
+pub use write::*;
+
+// reserved DWORD, entry count and key list offset
+const HEADER_SIZE: usize = 3;
+const HEADER_PADDING_SIZE_BYTES: usize = 100;
+
+const KEY_NAME_LEN: usize = 16;
+// name + 4 DWORDs
+const KEY_SIZE_BYTES: usize = KEY_NAME_LEN + 4 * 4;
+
+// same as ERF, but without localized strings and build date
+#[derive(Debug, PartialEq, Clone)]
+pub struct Rim {
+    pub file_head: FileHead,
+
+    pub resources: Resources,
+    pub reserved: Vec<u8>,
+}
+
+impl Rim {
+    pub fn new() -> Self {
+        Self {
+            file_head: ("RIM ", "V1.0").into(),
+
+            resources: Resources::default(),
+            reserved: vec![0; HEADER_PADDING_SIZE_BYTES],
+        }
+    }
+
+    pub fn get(&self, name: &str, tp: ResourceType) -> Option<&Resource> {
+        self.resources.get(&ResourceKey::from((name, tp)))
+    }
+
+    pub fn get_mut(&mut self, name: &str, tp: ResourceType) -> Option<&mut Resource> {
+        self.resources.get_mut(&ResourceKey::from((name, tp)))
+    }
+}
+
+impl Default for Rim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formats::{
+        erf::Resource,
+        rim::{write, Rim},
+        ReadResourceNoArg as _, ResourceType,
+    };
+
+    #[test]
+    fn read_write() {
+        let mut rim = Rim::new();
+        // ids and order intentionally don't match
+        for (name, id, tp) in [
+            ("module", 1, ResourceType::Ifo),
+            ("m01aa", 0, ResourceType::Are),
+            ("m01aa", 5, ResourceType::Git),
+        ] {
+            rim.resources.insert(
+                (name.to_owned(), tp).into(),
+                Resource {
+                    name: name.to_owned(),
+                    id,
+                    content: name.as_bytes().into(),
+                },
+            );
+        }
+        rim.reserved[0] = 1;
+
+        let bytes = write(rim.clone());
+        let new_rim = Rim::read(&bytes).unwrap();
+        assert_eq!(rim, new_rim);
+        assert!(rim.resources.keys().eq(new_rim.resources.keys()));
+        assert_eq!(
+            new_rim.get("m01aa", ResourceType::Git).unwrap().content,
+            b"m01aa"
+        );
+
+        let new_bytes = write(new_rim);
+        assert_eq!(bytes, new_bytes);
+    }
+}
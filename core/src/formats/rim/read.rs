@@ -0,0 +1,158 @@
+use crate::{
+    formats::{
+        erf::{ErfEntry, Resource, Resources},
+        impl_read_resource,
+        rim::{Rim, HEADER_PADDING_SIZE_BYTES, HEADER_SIZE, KEY_NAME_LEN, KEY_SIZE_BYTES},
+        ErrorKind, FResult, FileHead, FormatError, ReadResource, ReadResult, ResourceType,
+    },
+    util::bytes::{take, take_bytes, take_head, take_string_trimmed, Budget, Cursor, SeekExt as _},
+};
+use ahash::RandomState;
+
+struct Header {
+    file_head: FileHead,
+    entry_count: usize,
+    keys_offset: u32,
+    reserved: Vec<u8>,
+}
+
+struct KeyRead {
+    name: String,
+    id: u32,
+    tp: ResourceType,
+    offset: u32,
+    size: u32,
+}
+
+struct Reader<'a> {
+    c: &'a mut Cursor<'a>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(c: &'a mut Cursor<'a>, _: ()) -> Self {
+        Self { c }
+    }
+
+    fn read_header(&mut self) -> ReadResult<Header> {
+        let file_head = take_head(self.c)?;
+        if file_head.tp != "RIM " {
+            return Err(ErrorKind::Header(file_head));
+        }
+        let [_, entry_count, keys_offset] = take::<[u32; HEADER_SIZE]>(self.c)
+            .ok_or_else(|| ErrorKind::Truncated("header data".to_owned()))?;
+        let reserved = take_bytes(self.c, HEADER_PADDING_SIZE_BYTES)
+            .ok_or_else(|| ErrorKind::Truncated("reserved header bytes".to_owned()))?
+            .to_vec();
+
+        Ok(Header {
+            file_head,
+            entry_count: entry_count as usize,
+            keys_offset,
+            reserved,
+        })
+    }
+
+    fn read_key_list(&mut self, h: &Header) -> ReadResult<Vec<KeyRead>> {
+        self.c.seek_to(h.keys_offset)?;
+        let bytes = h
+            .entry_count
+            .checked_mul(KEY_SIZE_BYTES)
+            .and_then(|len| take_bytes(self.c, len))
+            .ok_or_else(|| ErrorKind::Truncated("key list".to_owned()))?;
+        let c = &mut Cursor::new(bytes);
+        let mut keys = Vec::with_capacity(h.entry_count);
+
+        for idx in 0..h.entry_count {
+            let name = take_string_trimmed(c, KEY_NAME_LEN).unwrap();
+            let [res_type, id, offset, size] = take::<[u32; 4]>(c).unwrap();
+            let tp = u16::try_from(res_type)
+                .map(ResourceType::from_id)
+                .map_err(|_| format!("invalid resource type {res_type} in key {idx}"))?;
+
+            keys.push(KeyRead {
+                name,
+                id,
+                tp,
+                offset,
+                size,
+            });
+        }
+
+        Ok(keys)
+    }
+
+    fn read(&mut self) -> ReadResult<Rim> {
+        let h = self.read_header()?;
+        let keys = self.read_key_list(&h)?;
+        let mut resources = Resources::with_capacity_and_hasher(keys.len(), RandomState::new());
+        let mut budget = Budget::new(self.c.get_ref().len());
+
+        for (idx, key) in keys.into_iter().enumerate() {
+            let KeyRead {
+                name,
+                id,
+                tp,
+                offset,
+                size,
+            } = key;
+            self.c.seek_to(offset)?;
+            let content = take_bytes(self.c, size as usize)
+                .ok_or_else(|| ErrorKind::Truncated(format!("resource content {idx} at {offset}")))?
+                .to_vec();
+            budget
+                .spend(content.len())
+                .ok_or_else(|| ErrorKind::TooLarge(format!("resource content {idx}")))?;
+
+            resources.insert(
+                (name.to_lowercase(), tp).into(),
+                Resource { name, id, content },
+            );
+        }
+
+        Ok(Rim {
+            file_head: h.file_head,
+            resources,
+            reserved: h.reserved,
+        })
+    }
+
+    fn read_entries(&mut self) -> ReadResult<Vec<ErfEntry>> {
+        let h = self.read_header()?;
+        let keys = self.read_key_list(&h)?;
+        let len = self.c.get_ref().len();
+
+        let mut entries = Vec::with_capacity(keys.len());
+        for (idx, key) in keys.into_iter().enumerate() {
+            let (offset, size) = (key.offset as usize, key.size as usize);
+            // checked here so the contents can be sliced without any checks later
+            let end = offset.checked_add(size).filter(|end| *end <= len);
+            if end.is_none() {
+                return Err(ErrorKind::Truncated(format!(
+                    "resource content {idx} at {offset}"
+                )));
+            }
+
+            entries.push(ErfEntry {
+                key: (key.name.to_lowercase(), key.tp).into(),
+                offset,
+                size,
+            });
+        }
+
+        Ok(entries)
+    }
+}
+
+impl_read_resource!(Rim, Reader, (), ResourceType::Rim);
+
+// only the key list, contents are left in place
+pub fn read_entries(bytes: &[u8]) -> FResult<Vec<ErfEntry>> {
+    let c = &mut Cursor::new(bytes);
+    let mut reader = Reader::new(c, ());
+    reader.read_entries().map_err(|kind| FormatError {
+        format: ResourceType::Rim,
+        resource: None,
+        offset: Some(reader.c.position()),
+        kind,
+    })
+}
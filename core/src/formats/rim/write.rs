@@ -0,0 +1,60 @@
+use crate::{
+    formats::rim::{Rim, HEADER_PADDING_SIZE_BYTES, HEADER_SIZE, KEY_NAME_LEN, KEY_SIZE_BYTES},
+    util::bytes::{nullpad_string, IntoByteSlice as _, DWORD_SIZE},
+};
+use std::io::{Cursor, Write};
+
+pub struct Writer {
+    rim: Rim,
+}
+
+impl Writer {
+    fn new(rim: Rim) -> Self {
+        Self { rim }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let Rim {
+            file_head,
+            resources,
+            mut reserved,
+        } = self.rim;
+        let entry_count = resources.len();
+        let keys_offset = 8 + HEADER_SIZE * DWORD_SIZE + HEADER_PADDING_SIZE_BYTES;
+        let data_offset = keys_offset + entry_count * KEY_SIZE_BYTES;
+        let data_len: usize = resources.values().map(|r| r.content.len()).sum();
+        let mut cursor = Cursor::new(Vec::with_capacity(data_offset + data_len));
+
+        // HEADER
+        reserved.resize(HEADER_PADDING_SIZE_BYTES, 0);
+        cursor.write_all(file_head.tp.as_bytes()).unwrap();
+        cursor.write_all(file_head.version.as_bytes()).unwrap();
+        cursor
+            .write_all([0, entry_count as u32, keys_offset as u32].into_byte_slice())
+            .unwrap();
+        cursor.write_all(&reserved).unwrap();
+        // KEYS
+        let mut data = Vec::with_capacity(data_len);
+        for (key, r) in resources {
+            let offset = (data_offset + data.len()) as u32;
+            let size = r.content.len() as u32;
+            data.extend(r.content);
+
+            cursor
+                .write_all(nullpad_string(r.name, KEY_NAME_LEN).as_bytes())
+                .unwrap();
+            cursor
+                .write_all([key.1.to_id() as u32, r.id, offset, size].into_byte_slice())
+                .unwrap();
+        }
+        // DATA
+        cursor.write_all(&data).unwrap();
+
+        cursor.into_inner()
+    }
+}
+
+pub fn write(rim: Rim) -> Vec<u8> {
+    let writer = Writer::new(rim);
+    writer.into_bytes()
+}
@@ -1,8 +1,182 @@
+use crate::util::encoding::CodePage;
+
 mod read;
+mod write;
 pub use read::*;
+pub use write::*;
+
+// 3 DWORD fields after the file head
+const HEADER_SIZE: usize = 3;
+// flags, sound resref, 5 DWORDs
+const ENTRY_SIZE_BYTES: usize = 10 * 4;
+const SOUND_LEN: usize = 16;
+
+// entry flags, which parts of an entry the game should use
+pub const TEXT_PRESENT: u32 = 0x1;
+pub const SOUND_PRESENT: u32 = 0x2;
+pub const SOUND_LENGTH_PRESENT: u32 = 0x4;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TlkEntry {
+    pub flags: u32,
+    pub text: String,
+    pub sound: String,
+    // unused by the game, kept for round trips
+    pub volume_variance: u32,
+    pub pitch_variance: u32,
+    // in seconds
+    pub sound_length: f32,
+}
+
+impl TlkEntry {
+    pub fn new(text: String) -> Self {
+        Self {
+            flags: TEXT_PRESENT,
+            text,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_sound(&mut self, sound: String, length: Option<f32>) {
+        self.sound = sound;
+        self.flags |= SOUND_PRESENT;
+        if let Some(length) = length {
+            self.sound_length = length;
+            self.flags |= SOUND_LENGTH_PRESENT;
+        }
+    }
+}
 
-#[derive(Debug, Clone, PartialEq)]
+// enough to look up single strings without reading the whole table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TlkHeader {
+    pub language: u32,
+    pub code_page: CodePage,
+    pub count: usize,
+    pub strings_offset: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
 pub struct Tlk {
     pub language: u32,
-    pub strings: Vec<String>,
+    // indexed by StrRef
+    pub entries: Vec<TlkEntry>,
+}
+
+impl Tlk {
+    pub fn new(language: u32) -> Self {
+        Self {
+            language,
+            entries: vec![],
+        }
+    }
+
+    pub fn get(&self, str_ref: u32) -> Option<&TlkEntry> {
+        self.entries.get(str_ref as usize)
+    }
+
+    pub fn get_mut(&mut self, str_ref: u32) -> Option<&mut TlkEntry> {
+        self.entries.get_mut(str_ref as usize)
+    }
+
+    // what the game would show, entries without the text flag are empty
+    pub fn text(&self, str_ref: u32) -> Option<&str> {
+        let entry = self.get(str_ref)?;
+        if entry.flags & TEXT_PRESENT == 0 {
+            return Some("");
+        }
+        Some(&entry.text)
+    }
+
+    // returns the StrRef of the new entry
+    pub fn push(&mut self, entry: TlkEntry) -> u32 {
+        self.entries.push(entry);
+        (self.entries.len() - 1) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formats::{
+        tlk::{read_header, read_language, read_text, write, Tlk, TlkEntry},
+        ErrorKind, FileHead, ReadResourceNoArg as _,
+    };
+
+    fn make_tlk() -> Tlk {
+        let mut tlk = Tlk::new(0);
+        tlk.push(TlkEntry::new("First".to_owned()));
+        tlk.push(TlkEntry::default());
+        let mut voiced = TlkEntry::new("Second".to_owned());
+        voiced.set_sound("n_second_01".to_owned(), Some(1.5));
+        voiced.volume_variance = 1;
+        voiced.pitch_variance = 2;
+        tlk.push(voiced);
+        // text without the flag is still kept
+        tlk.push(TlkEntry {
+            text: "Hidden".to_owned(),
+            ..Default::default()
+        });
+
+        tlk
+    }
+
+    #[test]
+    fn read_write() {
+        let tlk = make_tlk();
+        let bytes = write(tlk.clone());
+        let new_tlk = Tlk::read(&bytes).unwrap();
+        assert_eq!(tlk, new_tlk);
+        assert_eq!(new_tlk.text(2), Some("Second"));
+        assert_eq!(new_tlk.text(3), Some(""));
+        assert_eq!(new_tlk.get(2).unwrap().sound, "n_second_01");
+
+        let new_bytes = write(new_tlk);
+        assert_eq!(bytes, new_bytes);
+    }
+
+    #[test]
+    fn read_some_strings() {
+        let bytes = write(make_tlk());
+        let header = read_header(&bytes).unwrap();
+        assert_eq!(header.count, 4);
+        let strings = [2, 0, 3, 4, u32::MAX].map(|idx| read_text(&bytes, &header, idx).unwrap());
+
+        assert_eq!(
+            strings,
+            [
+                Some("Second".to_owned()),
+                Some("First".to_owned()),
+                Some(String::new()),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn code_page() {
+        // Polish
+        let mut tlk = Tlk::new(5);
+        tlk.push(TlkEntry::new("Żółć".to_owned()));
+        let bytes = write(tlk.clone());
+
+        assert!(bytes.ends_with(&[0xAF, 0xF3, 0xB3, 0xE6]));
+        assert_eq!(read_language(&bytes).unwrap(), 5);
+        let header = read_header(&bytes).unwrap();
+        assert_eq!(read_text(&bytes, &header, 0).unwrap().unwrap(), "Żółć");
+        assert_eq!(Tlk::read(&bytes).unwrap(), tlk);
+    }
+
+    #[test]
+    fn wrong_header() {
+        let mut bytes = write(make_tlk());
+        bytes[4..8].copy_from_slice(b"V4.0");
+
+        let err = Tlk::read(&bytes).unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::Header(FileHead::from(("TLK ", "V4.0")))
+        );
+        assert!(read_header(&bytes).is_err());
+    }
 }
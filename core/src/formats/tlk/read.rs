@@ -1,80 +1,164 @@
-use log::warn;
-
 use crate::{
-    formats::{impl_read_resource, tlk::Tlk, ReadResource, ResourceType},
+    formats::{
+        impl_read_resource,
+        tlk::{Tlk, TlkEntry, TlkHeader, ENTRY_SIZE_BYTES, HEADER_SIZE, SOUND_LEN, TEXT_PRESENT},
+        ErrorKind, FResult, FormatError, ReadResource, ReadResult, ResourceType,
+    },
     util::{
-        bytes::{take, take_head, take_string, Cursor, SeekExt as _, DWORD_SIZE},
-        SResult,
+        bytes::{
+            take, take_bytes, take_head, take_string_trimmed, take_text, Budget, Cursor,
+            SeekExt as _, DWORD_SIZE,
+        },
+        encoding::CodePage,
     },
 };
 use std::io::BufRead as _;
 
+const ENTRIES_OFFSET: usize = 8 + HEADER_SIZE * DWORD_SIZE;
+
 struct Reader<'a> {
     c: &'a mut Cursor<'a>,
-    required_indices: &'a [usize],
 }
 
 impl<'a> Reader<'a> {
-    fn new(c: &'a mut Cursor<'a>, required_indices: &'a [usize]) -> Self {
-        Self {
-            c,
-            required_indices,
-        }
+    fn new(c: &'a mut Cursor<'a>, _: ()) -> Self {
+        Self { c }
     }
 
-    fn read(mut self) -> SResult<Tlk> {
+    fn read(&mut self) -> ReadResult<Tlk> {
         let [language, count, offset] = self.read_header()?;
-        let strings = self.read_strings(count as usize, offset as usize)?;
+        let code_page = CodePage::from_language(language);
+        let entries = self.read_entries(count as usize, offset as usize, code_page)?;
 
-        Ok(Tlk { language, strings })
+        Ok(Tlk { language, entries })
     }
 
-    fn read_header(&mut self) -> SResult<[u32; 3]> {
-        let file_head = take_head(self.c).ok_or("couldn't read file head")?;
+    fn read_header(&mut self) -> ReadResult<[u32; HEADER_SIZE]> {
+        let file_head = take_head(self.c)?;
 
         if file_head.tp != "TLK " || file_head.version != "V3.0" {
-            return Err(format!("invalid file type or version {file_head:?}"));
+            return Err(ErrorKind::Header(file_head));
         }
-        take::<[u32; 3]>(self.c).ok_or_else(|| "couldn't read header contents".to_owned())
+        take::<[u32; HEADER_SIZE]>(self.c)
+            .ok_or_else(|| ErrorKind::Truncated("header contents".to_owned()))
     }
 
-    fn read_strings(&mut self, count: usize, offset: usize) -> SResult<Vec<String>> {
-        const STRING_DATA_SIZE_BYTES: usize = 10 * DWORD_SIZE;
-        const HEADER_SIZE_BYTES: usize = 5 * DWORD_SIZE;
-        let mut strings = Vec::with_capacity(self.required_indices.len());
-
-        for idx in self.required_indices {
-            // intentionally invalid string
-            if *idx == u32::MAX as usize {
-                strings.push(String::new());
-                continue;
-            }
-            if *idx > count {
-                strings.push("#MISSING STRING#".to_owned());
-                warn!("TLK contains {count} strings but index {idx} is requested");
-                continue;
-            }
-            self.c
-                .seek_to(HEADER_SIZE_BYTES + idx * STRING_DATA_SIZE_BYTES)?;
-            let flags =
-                take::<u32>(self.c).ok_or_else(|| format!("couldn't read string {idx} flags"))?;
-            // this entry has no string, meant to return an empty one
-            if (flags & 1) != 1 {
-                strings.push(String::new());
-                continue;
-            }
-            self.c.consume(6 * DWORD_SIZE);
-            let [str_offset, len] = take::<[u32; 2]>(self.c)
-                .ok_or_else(|| format!("couldn't read string {idx} offset and size"))?;
-            self.c.seek_to(offset + str_offset as usize)?;
-            let content = take_string(self.c, len as usize)
-                .ok_or_else(|| format!("couldn't read string {idx} content at offset {offset}"))?;
-
-            strings.push(content);
+    fn read_entries(
+        &mut self,
+        count: usize,
+        offset: usize,
+        code_page: CodePage,
+    ) -> ReadResult<Vec<TlkEntry>> {
+        self.c.seek_to(ENTRIES_OFFSET)?;
+        let bytes = count
+            .checked_mul(ENTRY_SIZE_BYTES)
+            .and_then(|len| take_bytes(self.c, len))
+            .ok_or_else(|| ErrorKind::Truncated("entry table".to_owned()))?;
+        let c = &mut Cursor::new(bytes);
+        let mut entries = Vec::with_capacity(count);
+        let mut budget = Budget::new(self.c.get_ref().len());
+
+        for idx in 0..count {
+            let flags = take::<u32>(c).unwrap();
+            let sound = take_string_trimmed(c, SOUND_LEN).unwrap();
+            let [volume_variance, pitch_variance, str_offset, len] = take::<[u32; 4]>(c).unwrap();
+            let sound_length = take::<f32>(c).unwrap();
+
+            let text = Self::take_string_content(self.c, offset, str_offset, len, code_page)
+                .ok_or_else(|| {
+                    ErrorKind::Truncated(format!("string {idx} content at offset {offset}"))
+                })?;
+            budget
+                .spend(text.len())
+                .ok_or_else(|| ErrorKind::TooLarge(format!("string {idx}")))?;
+
+            entries.push(TlkEntry {
+                flags,
+                text,
+                sound,
+                volume_variance,
+                pitch_variance,
+                sound_length,
+            });
         }
 
-        Ok(strings)
+        Ok(entries)
+    }
+
+    fn take_string_content(
+        c: &mut Cursor,
+        offset: usize,
+        str_offset: u32,
+        len: u32,
+        code_page: CodePage,
+    ) -> Option<String> {
+        c.seek_to(offset.checked_add(str_offset as usize)?).ok()?;
+        take_text(c, len as usize, code_page)
     }
+
+    // a single string, reading the whole table just to get a few of them is wasteful
+    fn read_text(&mut self, h: &TlkHeader, idx: usize) -> ReadResult<Option<String>> {
+        if idx >= h.count {
+            return Ok(None);
+        }
+        self.c
+            .seek_to(ENTRIES_OFFSET as u64 + idx as u64 * ENTRY_SIZE_BYTES as u64)?;
+        let flags = take::<u32>(self.c)
+            .ok_or_else(|| ErrorKind::Truncated(format!("string {idx} flags")))?;
+        // this entry has no string, meant to return an empty one
+        if (flags & TEXT_PRESENT) != TEXT_PRESENT {
+            return Ok(Some(String::new()));
+        }
+        self.c.consume(SOUND_LEN + 2 * DWORD_SIZE);
+        let [str_offset, len] = take::<[u32; 2]>(self.c)
+            .ok_or_else(|| ErrorKind::Truncated(format!("string {idx} offset and size")))?;
+        let content =
+            Self::take_string_content(self.c, h.strings_offset, str_offset, len, h.code_page)
+                .ok_or_else(|| {
+                    ErrorKind::Truncated(format!(
+                        "string {idx} content at offset {}",
+                        h.strings_offset
+                    ))
+                })?;
+
+        Ok(Some(content))
+    }
+}
+
+impl_read_resource!(Tlk, Reader, (), ResourceType::Tlk);
+
+pub fn read_header(bytes: &[u8]) -> FResult<TlkHeader> {
+    let c = &mut Cursor::new(bytes);
+    let mut reader = Reader::new(c, ());
+    let [language, count, strings_offset] = reader.read_header().map_err(|kind| FormatError {
+        format: ResourceType::Tlk,
+        resource: None,
+        offset: Some(reader.c.position()),
+        kind,
+    })?;
+
+    Ok(TlkHeader {
+        language,
+        code_page: CodePage::from_language(language),
+        count: count as usize,
+        strings_offset: strings_offset as usize,
+    })
 }
 
-impl_read_resource!(Tlk, Reader, &'a [usize]);
+pub fn read_language(bytes: &[u8]) -> FResult<u32> {
+    read_header(bytes).map(|h| h.language)
+}
+
+// None if the table doesn't have the StrRef
+pub fn read_text(bytes: &[u8], header: &TlkHeader, str_ref: u32) -> FResult<Option<String>> {
+    let c = &mut Cursor::new(bytes);
+    let mut reader = Reader::new(c, ());
+    reader
+        .read_text(header, str_ref as usize)
+        .map_err(|kind| FormatError {
+            format: ResourceType::Tlk,
+            resource: None,
+            offset: Some(reader.c.position()),
+            kind,
+        })
+}
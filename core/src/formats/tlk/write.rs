@@ -0,0 +1,73 @@
+use crate::{
+    formats::tlk::{Tlk, ENTRY_SIZE_BYTES, HEADER_SIZE, SOUND_LEN},
+    util::{
+        bytes::{IntoByteSlice as _, DWORD_SIZE},
+        encoding::CodePage,
+    },
+};
+use std::io::{Cursor, Write};
+
+pub struct Writer {
+    tlk: Tlk,
+}
+
+impl Writer {
+    fn new(tlk: Tlk) -> Self {
+        Self { tlk }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let Tlk { language, entries } = self.tlk;
+        let entry_count = entries.len();
+        let strings_offset = 8 + HEADER_SIZE * DWORD_SIZE + entry_count * ENTRY_SIZE_BYTES;
+        let code_page = CodePage::from_language(language);
+        let texts: Vec<_> = entries.iter().map(|e| code_page.encode(&e.text)).collect();
+        let strings_len: usize = texts.iter().map(Vec::len).sum();
+        let mut cursor = Cursor::new(Vec::with_capacity(strings_offset + strings_len));
+
+        // HEADER
+        cursor.write_all(b"TLK V3.0").unwrap();
+        cursor
+            .write_all([language, entry_count as u32, strings_offset as u32].into_byte_slice())
+            .unwrap();
+        // ENTRIES
+        let mut strings = Vec::with_capacity(strings_len);
+        for (entry, text) in entries.into_iter().zip(texts) {
+            // entries without text point to the start
+            let str_offset = if text.is_empty() {
+                0
+            } else {
+                strings.len() as u32
+            };
+            let text_len = text.len() as u32;
+            strings.extend(text);
+
+            let mut sound = entry.sound.into_bytes();
+            sound.resize(SOUND_LEN, 0);
+
+            cursor.write_all(&entry.flags.to_le_bytes()).unwrap();
+            cursor.write_all(&sound).unwrap();
+            cursor
+                .write_all(
+                    [
+                        entry.volume_variance,
+                        entry.pitch_variance,
+                        str_offset,
+                        text_len,
+                    ]
+                    .into_byte_slice(),
+                )
+                .unwrap();
+            cursor.write_all(&entry.sound_length.to_le_bytes()).unwrap();
+        }
+        // STRINGS
+        cursor.write_all(&strings).unwrap();
+
+        cursor.into_inner()
+    }
+}
+
+pub fn write(tlk: Tlk) -> Vec<u8> {
+    let writer = Writer::new(tlk);
+    writer.into_bytes()
+}
@@ -0,0 +1,121 @@
+mod read;
+
+// data size, alpha test, width and height, encoding, mipmap count, then reserved bytes
+const HEADER_SIZE_BYTES: usize = 128;
+const BLOCK_SIDE: usize = 4;
+
+// with data size of 0 the pixels are stored as is, otherwise they're DXT compressed
+const ENCODING_GRAY: u8 = 0x01;
+// DXT1 when compressed
+const ENCODING_RGB: u8 = 0x02;
+// DXT5 when compressed
+const ENCODING_RGBA: u8 = 0x04;
+// xbox only, pixels are swizzled
+const ENCODING_BGRA: u8 = 0x0C;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TpcImage {
+    pub width: usize,
+    pub height: usize,
+    // RGBA, 4 bytes per pixel, rows from the top
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tpc {
+    pub alpha_test: f32,
+    // full size first, each next one is half the size of the previous one
+    // cube maps store 6 faces, only the first one is kept
+    pub mipmaps: Vec<TpcImage>,
+    // texture properties, the plain text of a .txi file
+    pub txi: String,
+}
+
+impl Tpc {
+    pub fn image(&self) -> &TpcImage {
+        &self.mipmaps[0]
+    }
+
+    // the smallest mipmap that's still at least this large, for thumbnails
+    pub fn image_for_size(&self, size: usize) -> &TpcImage {
+        self.mipmaps
+            .iter()
+            .rev()
+            .find(|image| image.width >= size && image.height >= size)
+            .unwrap_or_else(|| self.image())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formats::{
+        tpc::{Tpc, ENCODING_GRAY, ENCODING_RGB, ENCODING_RGBA},
+        ReadResourceNoArg as _,
+    };
+
+    fn make_tpc(data_size: u32, size: u16, encoding: u8, mipmaps: u8, data: &[u8]) -> Vec<u8> {
+        let mut bytes = data_size.to_le_bytes().to_vec();
+        bytes.extend(0.5f32.to_le_bytes());
+        bytes.extend(size.to_le_bytes());
+        bytes.extend(size.to_le_bytes());
+        bytes.extend([encoding, mipmaps]);
+        bytes.resize(128, 0);
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn uncompressed() {
+        let gray = make_tpc(0, 2, ENCODING_GRAY, 1, &[0, 1, 2, 3]);
+        let tpc = Tpc::read(&gray).unwrap();
+        assert!((tpc.alpha_test - 0.5).abs() < f32::EPSILON);
+        assert_eq!(tpc.image().pixels[4..8], [1, 1, 1, 255]);
+
+        // 2x2 and 1x1 mipmaps, then the TXI
+        let mut data = vec![];
+        for pixel in 0..5 {
+            data.extend([pixel, 10, 20, 30]);
+        }
+        data.extend(b"mipmap 0\r\n");
+        let tpc = Tpc::read(&make_tpc(0, 2, ENCODING_RGBA, 2, &data)).unwrap();
+        assert_eq!(tpc.mipmaps.len(), 2);
+        assert_eq!(tpc.image().pixels, data[..16]);
+        assert_eq!(tpc.mipmaps[1].width, 1);
+        assert_eq!(tpc.mipmaps[1].pixels, [4, 10, 20, 30]);
+        assert_eq!(tpc.image_for_size(1).width, 1);
+        assert_eq!(tpc.image_for_size(2).width, 2);
+        assert_eq!(tpc.txi, "mipmap 0\r\n");
+    }
+
+    #[test]
+    fn dxt() {
+        // pure red and pure blue, top half uses the first one, bottom the second one
+        let mut dxt1 = vec![0x00, 0xF8, 0x1F, 0x00];
+        dxt1.extend([0, 0, 0x55, 0x55]);
+        let tpc = Tpc::read(&make_tpc(8, 4, ENCODING_RGB, 1, &dxt1)).unwrap();
+        let pixels = &tpc.image().pixels;
+        assert_eq!(pixels.len(), 64);
+        assert_eq!(pixels[..4], [255, 0, 0, 255]);
+        assert_eq!(pixels[60..], [0, 0, 255, 255]);
+
+        // 2x2 image still takes a whole block, first row has alpha of 255, the rest 0
+        let mut dxt5 = vec![255, 0];
+        dxt5.extend([
+            0,
+            0b1001_0000,
+            0b0010_0100,
+            0b0100_1001,
+            0b1001_0010,
+            0b0010_0100,
+        ]);
+        dxt5.extend(dxt1);
+        let tpc = Tpc::read(&make_tpc(16, 2, ENCODING_RGBA, 1, &dxt5)).unwrap();
+        let pixels = &tpc.image().pixels;
+        assert_eq!(pixels.len(), 16);
+        assert_eq!(pixels[..4], [255, 0, 0, 255]);
+        assert_eq!(pixels[8..12], [255, 0, 0, 0]);
+
+        // not enough data for the block
+        assert!(Tpc::read(&make_tpc(8, 4, ENCODING_RGB, 1, &[0; 4])).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use crate::{
+    formats::{
+        impl_read_resource,
+        tpc::{
+            Tpc, TpcImage, BLOCK_SIDE, ENCODING_BGRA, ENCODING_GRAY, ENCODING_RGB, ENCODING_RGBA,
+            HEADER_SIZE_BYTES,
+        },
+        ErrorKind, FResult, FormatError, ReadResource, ReadResult, ResourceType,
+    },
+    util::bytes::{bytes_to_string, take, take_bytes, Cursor, SeekExt as _},
+};
+use std::io::Read as _;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PixelFormat {
+    Gray,
+    Rgb,
+    Rgba,
+    Dxt1,
+    Dxt5,
+}
+
+impl PixelFormat {
+    // bytes per pixel, or per 4x4 block for DXT
+    fn unit_size(self) -> usize {
+        match self {
+            Self::Gray => 1,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+            Self::Dxt1 => 8,
+            Self::Dxt5 => 16,
+        }
+    }
+
+    fn data_size(self, width: usize, height: usize) -> Option<usize> {
+        let units = match self {
+            Self::Dxt1 | Self::Dxt5 => width
+                .div_ceil(BLOCK_SIDE)
+                .checked_mul(height.div_ceil(BLOCK_SIDE))?,
+            _ => width.checked_mul(height)?,
+        };
+        units.checked_mul(self.unit_size())
+    }
+}
+
+struct Header {
+    alpha_test: f32,
+    width: usize,
+    height: usize,
+    format: PixelFormat,
+    mipmap_count: usize,
+    layer_count: usize,
+}
+
+struct Reader<'a> {
+    c: &'a mut Cursor<'a>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(c: &'a mut Cursor<'a>, _: ()) -> Self {
+        Self { c }
+    }
+
+    fn read(&mut self) -> ReadResult<Tpc> {
+        let h = self.read_header()?;
+        let mipmaps = self.read_mipmaps(&h)?;
+        self.skip_layers(&h)?;
+        let txi = self.read_txi()?;
+
+        Ok(Tpc {
+            alpha_test: h.alpha_test,
+            mipmaps,
+            txi,
+        })
+    }
+
+    fn read_header(&mut self) -> ReadResult<Header> {
+        let [data_size, alpha_test] = take::<[u32; 2]>(self.c)
+            .ok_or_else(|| ErrorKind::Truncated("header data".to_owned()))?;
+        let [width, height] = take::<[u16; 2]>(self.c)
+            .ok_or_else(|| ErrorKind::Truncated("texture size".to_owned()))?;
+        let [encoding, mipmap_count] = take::<[u8; 2]>(self.c)
+            .ok_or_else(|| ErrorKind::Truncated("texture encoding".to_owned()))?;
+        self.c.seek_to(HEADER_SIZE_BYTES)?;
+
+        let compressed = data_size != 0;
+        let format = match (encoding, compressed) {
+            (ENCODING_GRAY, false) => PixelFormat::Gray,
+            (ENCODING_RGB, false) => PixelFormat::Rgb,
+            (ENCODING_RGBA, false) => PixelFormat::Rgba,
+            (ENCODING_RGB, true) => PixelFormat::Dxt1,
+            (ENCODING_RGBA, true) => PixelFormat::Dxt5,
+            (ENCODING_BGRA, _) => return Err("swizzled xbox textures aren't supported".into()),
+            _ => {
+                return Err(format!("invalid encoding {encoding}, compressed: {compressed}").into())
+            }
+        };
+        let (width, mut height) = (width as usize, height as usize);
+        if width == 0 || height == 0 {
+            return Err(format!("invalid texture size {width}x{height}").into());
+        }
+        // cube maps have their 6 faces stacked on top of each other
+        let layer_count = if height == width * 6 { 6 } else { 1 };
+        height /= layer_count;
+
+        Ok(Header {
+            alpha_test: f32::from_bits(alpha_test),
+            width,
+            height,
+            format,
+            mipmap_count: (mipmap_count as usize).max(1),
+            layer_count,
+        })
+    }
+
+    fn mipmap_sizes(h: &Header) -> impl Iterator<Item = (usize, usize)> {
+        let (width, height) = (h.width, h.height);
+        (0..h.mipmap_count).map(move |level| {
+            let shift = level.min(usize::BITS as usize - 1);
+            ((width >> shift).max(1), (height >> shift).max(1))
+        })
+    }
+
+    fn read_mipmaps(&mut self, h: &Header) -> ReadResult<Vec<TpcImage>> {
+        // the data has to be there before anything gets allocated for it
+        let mut mipmaps = Vec::with_capacity(h.mipmap_count.min(16));
+
+        for (level, (width, height)) in Self::mipmap_sizes(h).enumerate() {
+            let data = h
+                .format
+                .data_size(width, height)
+                .and_then(|size| take_bytes(self.c, size))
+                .ok_or_else(|| {
+                    ErrorKind::Truncated(format!("mipmap {level} of size {width}x{height}"))
+                })?;
+
+            mipmaps.push(TpcImage {
+                width,
+                height,
+                pixels: decode(h.format, width, height, data),
+            });
+        }
+
+        Ok(mipmaps)
+    }
+
+    fn skip_layers(&mut self, h: &Header) -> ReadResult<()> {
+        let layer_size = Self::mipmap_sizes(h)
+            .map(|(width, height)| h.format.data_size(width, height))
+            .sum::<Option<usize>>();
+        let skipped = layer_size.and_then(|size| size.checked_mul(h.layer_count - 1));
+        skipped
+            .and_then(|size| take_bytes(self.c, size))
+            .ok_or_else(|| ErrorKind::Truncated("cube map faces".to_owned()))?;
+
+        Ok(())
+    }
+
+    fn read_txi(&mut self) -> ReadResult<String> {
+        let mut bytes = vec![];
+        self.c
+            .read_to_end(&mut bytes)
+            .map_err(|err| format!("couldn't read TXI: {err}"))?;
+        // some files pad the end with zeroes
+        let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        bytes.truncate(len);
+
+        Ok(bytes_to_string(bytes))
+    }
+}
+
+fn decode(format: PixelFormat, width: usize, height: usize, data: &[u8]) -> Vec<u8> {
+    match format {
+        PixelFormat::Gray => data.iter().flat_map(|v| [*v, *v, *v, u8::MAX]).collect(),
+        PixelFormat::Rgb => data
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], u8::MAX])
+            .collect(),
+        PixelFormat::Rgba => data.to_vec(),
+        PixelFormat::Dxt1 | PixelFormat::Dxt5 => decode_dxt(format, width, height, data),
+    }
+}
+
+fn decode_dxt(format: PixelFormat, width: usize, height: usize, data: &[u8]) -> Vec<u8> {
+    let mut pixels = vec![0; width * height * 4];
+    let blocks_wide = width.div_ceil(BLOCK_SIDE);
+
+    for (idx, block) in data.chunks_exact(format.unit_size()).enumerate() {
+        let (block_x, block_y) = (
+            idx % blocks_wide * BLOCK_SIDE,
+            idx / blocks_wide * BLOCK_SIDE,
+        );
+        let decoded = if format == PixelFormat::Dxt1 {
+            decode_color_block(block, true)
+        } else {
+            let mut decoded = decode_color_block(&block[8..], false);
+            for (pixel, alpha) in decoded.iter_mut().zip(decode_alpha_block(&block[..8])) {
+                pixel[3] = alpha;
+            }
+            decoded
+        };
+
+        // blocks on the edges of images that aren't a multiple of 4 are cut
+        for (pixel_idx, pixel) in decoded.iter().enumerate() {
+            let (x, y) = (
+                block_x + pixel_idx % BLOCK_SIDE,
+                block_y + pixel_idx / BLOCK_SIDE,
+            );
+            if x < width && y < height {
+                let offset = (y * width + x) * 4;
+                pixels[offset..offset + 4].copy_from_slice(pixel);
+            }
+        }
+    }
+
+    pixels
+}
+
+fn rgb565(color: u16) -> [u16; 3] {
+    let [r, g, b] = [color >> 11, (color >> 5) & 0x3F, color & 0x1F];
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+// 2 colors in RGB565, the other 2 are blended from them, then a 2 bit index for each pixel
+fn decode_color_block(block: &[u8], dxt1: bool) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (rgb0, rgb1) = (rgb565(c0), rgb565(c1));
+    let blend = |w0: u16, w1: u16| {
+        let channel = |i: usize| ((rgb0[i] * w0 + rgb1[i] * w1) / (w0 + w1)) as u8;
+        [channel(0), channel(1), channel(2), u8::MAX]
+    };
+
+    let mut palette = [blend(1, 0), blend(0, 1), [0; 4], [0; 4]];
+    // DXT1 uses the order of colors to mark blocks with transparency
+    if dxt1 && c0 <= c1 {
+        palette[2] = blend(1, 1);
+    } else {
+        palette[2] = blend(2, 1);
+        palette[3] = blend(1, 2);
+    }
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    let mut pixels = [[0; 4]; 16];
+    for (idx, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = palette[(indices >> (idx * 2)) as usize & 0b11];
+    }
+    pixels
+}
+
+// 2 alpha values, 6 more interpolated from them, then a 3 bit index for each pixel
+fn decode_alpha_block(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (u16::from(block[0]), u16::from(block[1]));
+    let mut palette = [0u8; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i as usize + 1] = (((7 - i) * a0 + i * a1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i as usize + 1] = (((5 - i) * a0 + i * a1) / 5) as u8;
+        }
+        palette[7] = u8::MAX;
+    }
+
+    let mut index_bytes = [0; 8];
+    index_bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(index_bytes);
+    let mut alphas = [0; 16];
+    for (idx, alpha) in alphas.iter_mut().enumerate() {
+        *alpha = palette[(indices >> (idx * 3)) as usize & 0b111];
+    }
+    alphas
+}
+
+impl_read_resource!(Tpc, Reader, (), ResourceType::Tpc);
@@ -1,8 +1,13 @@
-use ahash::HashMap;
+use crate::util::SResult;
+use ahash::{HashMap, HashMapExt as _};
 use macros::UnwrapVariant;
 
 mod read;
-pub use read::*;
+mod write;
+pub use write::*;
+
+// used by both formats for empty cells
+const EMPTY_CELL: &str = "****";
 
 #[derive(Debug, Clone, Copy)]
 pub enum TwoDAType {
@@ -15,5 +20,162 @@ pub enum TwoDAValue {
     String(String),
     Int(i32),
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TwoDAFormat {
+    // V2.b, what's packed into BIFs
+    #[default]
+    Binary,
+    // V2.0, what most override folders ship
+    Text,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoDARow {
+    pub label: String,
+    // None is a blank (****) cell, always as long as the column list
+    pub cells: Vec<Option<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TwoDA {
+    pub format: TwoDAFormat,
+    // value of blank cells, only stored in the text format
+    pub default: Option<String>,
+    pub columns: Vec<String>,
+    pub rows: Vec<TwoDARow>,
+}
+
+// typed subset of the table's columns, with "_idx" holding the row's position
 #[derive(Debug, Clone)]
-pub struct TwoDA(pub Vec<HashMap<&'static str, Option<TwoDAValue>>>);
+pub struct TwoDAProjection(pub Vec<HashMap<&'static str, Option<TwoDAValue>>>);
+
+impl TwoDA {
+    pub fn column_idx(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|c| c.eq_ignore_ascii_case(name))
+    }
+
+    pub fn get(&self, row: usize, column: &str) -> Option<&str> {
+        let idx = self.column_idx(column)?;
+        self.rows.get(row)?.cells.get(idx)?.as_deref()
+    }
+
+    pub fn project(&self, columns: &[(&'static str, TwoDAType)]) -> SResult<TwoDAProjection> {
+        let mut target_columns = Vec::with_capacity(columns.len());
+        for (name, tp) in columns {
+            let idx = self
+                .column_idx(name)
+                .ok_or_else(|| format!("missing column {name}"))?;
+            target_columns.push((*name, idx, *tp));
+        }
+
+        let mut rows = Vec::with_capacity(self.rows.len());
+        for (row_idx, row) in self.rows.iter().enumerate() {
+            let mut projected = HashMap::with_capacity(target_columns.len() + 1);
+            projected.insert("_idx", Some(TwoDAValue::Int(row_idx as i32)));
+
+            for (name, idx, tp) in &target_columns {
+                let Some(Some(value)) = row.cells.get(*idx) else {
+                    projected.insert(*name, None);
+                    continue;
+                };
+                let parsed = match tp {
+                    TwoDAType::Int => {
+                        let (str, radix) = if value.starts_with("0x") {
+                            (value.trim_start_matches("0x"), 16)
+                        } else {
+                            (value.as_str(), 10)
+                        };
+                        let v = i32::from_str_radix(str, radix).map_err(|_| {
+                            format!(
+                                "couldn't parse int column {name} in row {row_idx}, value: {value}"
+                            )
+                        })?;
+
+                        TwoDAValue::Int(v)
+                    }
+                    TwoDAType::String => TwoDAValue::String(value.clone()),
+                };
+                projected.insert(*name, Some(parsed));
+            }
+            rows.push(projected);
+        }
+
+        Ok(TwoDAProjection(rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formats::{
+        twoda::{write, TwoDA, TwoDAFormat, TwoDARow, TwoDAType},
+        ReadResourceNoArg as _,
+    };
+
+    fn make_twoda(format: TwoDAFormat) -> TwoDA {
+        let row = |label: &str, cells: [Option<&str>; 3]| TwoDARow {
+            label: label.to_owned(),
+            cells: cells.map(|c| c.map(ToOwned::to_owned)).into(),
+        };
+        TwoDA {
+            format,
+            default: None,
+            columns: vec!["label".to_owned(), "name".to_owned(), "flags".to_owned()],
+            rows: vec![
+                row("0", [Some("FIRST"), Some("100"), Some("0x10")]),
+                row("1", [Some("with space"), None, Some("100")]),
+                row("2", [None, None, None]),
+            ],
+        }
+    }
+
+    #[test]
+    fn read_write() {
+        for format in [TwoDAFormat::Binary, TwoDAFormat::Text] {
+            let twoda = make_twoda(format);
+            let bytes = write(twoda.clone()).unwrap();
+            let new_twoda = TwoDA::read(&bytes).unwrap();
+            assert_eq!(twoda, new_twoda);
+
+            let new_bytes = write(new_twoda).unwrap();
+            assert_eq!(bytes, new_bytes);
+        }
+    }
+
+    #[test]
+    fn read_text() {
+        let text = "2DA V2.0\r\nDEFAULT: -1\r\n\r\n   label    name   \"two words\"\r\n0  FIRST  100 ****\r\n1  \"SECOND ONE\"\r\n";
+        let twoda = TwoDA::read(text.as_bytes()).unwrap();
+
+        assert_eq!(twoda.format, TwoDAFormat::Text);
+        assert_eq!(twoda.default.as_deref(), Some("-1"));
+        assert_eq!(twoda.columns, ["label", "name", "two words"]);
+        assert_eq!(twoda.rows.len(), 2);
+        assert_eq!(twoda.get(0, "name"), Some("100"));
+        assert_eq!(twoda.get(0, "two words"), None);
+        assert_eq!(twoda.get(1, "LABEL"), Some("SECOND ONE"));
+        assert_eq!(
+            twoda.rows[1].cells,
+            [Some("SECOND ONE".to_owned()), None, None]
+        );
+    }
+
+    #[test]
+    fn project() {
+        let twoda = make_twoda(TwoDAFormat::Binary);
+        let projection = twoda
+            .project(&[("name", TwoDAType::Int), ("flags", TwoDAType::Int)])
+            .unwrap();
+
+        let first = &projection.0[0];
+        assert_eq!(*first["_idx"].as_ref().unwrap().int_unwrap(), 0);
+        assert_eq!(*first["name"].as_ref().unwrap().int_unwrap(), 100);
+        assert_eq!(*first["flags"].as_ref().unwrap().int_unwrap(), 16);
+        assert!(projection.0[1]["name"].is_none());
+
+        assert!(twoda.project(&[("missing", TwoDAType::Int)]).is_err());
+        assert!(twoda.project(&[("label", TwoDAType::Int)]).is_err());
+    }
+}
@@ -1,125 +1,89 @@
 use crate::{
     formats::{
         impl_read_resource,
-        twoda::{TwoDA, TwoDAType, TwoDAValue},
-        ReadResource, ResourceType,
+        twoda::{TwoDA, TwoDAFormat, TwoDARow, EMPTY_CELL},
+        ErrorKind, FResult, FormatError, ReadResource, ReadResult, ResourceType,
     },
-    util::{
-        bytes::{take, take_head, take_slice, take_string_until, Cursor, SeekExt},
-        ESResult, SResult,
+    util::bytes::{
+        bytes_to_string, capacity, take, take_head, take_slice, take_string_until, Budget, Cursor,
+        SeekExt,
     },
 };
-use ahash::{HashMap, HashMapExt as _};
-use std::{io::BufRead as _, usize};
-
-struct TargetColumn {
-    name: &'static str,
-    idx: usize,
-    tp: TwoDAType,
-}
+use log::warn;
+use std::io::{BufRead as _, Read as _};
 
 struct Reader<'a> {
     c: &'a mut Cursor<'a>,
-    required_columns: HashMap<&'static str, TwoDAType>,
 }
 
 impl<'a> Reader<'a> {
-    fn new(c: &'a mut Cursor<'a>, required_columns: &[(&'static str, TwoDAType)]) -> Self {
-        let required_columns = required_columns
-            .iter()
-            .map(|(name, tp)| (*name, *tp))
-            .collect();
-        Self {
-            c,
-            required_columns,
-        }
+    fn new(c: &'a mut Cursor<'a>, _: ()) -> Self {
+        Self { c }
     }
 
-    fn read(mut self) -> SResult<TwoDA> {
-        self.read_header()?;
-        let (total_columns, target_columns) = self.read_columns()?;
-        let row_count = self.read_row_count()? as usize;
-        let row_indices = self.read_row_indices(row_count)?;
-        let cell_offsets = self.read_cell_offsets(total_columns, row_count)?;
-
-        self.read_rows(
-            row_count,
-            total_columns,
-            &row_indices,
-            &target_columns,
-            &cell_offsets,
-        )
+    fn read(&mut self) -> ReadResult<TwoDA> {
+        let file_head = take_head(self.c)?;
+        match file_head.version.as_str() {
+            "V2.b" => self.read_binary(),
+            "V2.0" => self.read_text(),
+            _ => Err(ErrorKind::Header(file_head)),
+        }
     }
 
-    fn read_header(&mut self) -> ESResult {
-        let file_head = take_head(self.c).ok_or("couldn't read file head")?;
-        if file_head.version != "V2.b" {
-            return Err(format!("Invalid 2da version: {}", file_head.version));
-        }
+    fn read_binary(&mut self) -> ReadResult<TwoDA> {
         // skip newline
         self.c.consume(1);
-
-        Ok(())
+        let columns = self.read_columns()?;
+        let row_count = self.read_row_count()? as usize;
+        let labels = self.read_row_labels(row_count)?;
+        let cell_offsets = self.read_cell_offsets(columns.len(), row_count)?;
+        let rows = self.read_rows(labels, columns.len(), &cell_offsets)?;
+
+        Ok(TwoDA {
+            format: TwoDAFormat::Binary,
+            default: None,
+            columns,
+            rows,
+        })
     }
 
-    fn read_columns(&mut self) -> SResult<(usize, Vec<TargetColumn>)> {
-        let mut columns_str =
-            take_string_until(self.c, b'\0').ok_or("couldn't read column list")?;
+    fn read_columns(&mut self) -> ReadResult<Vec<String>> {
+        let mut columns_str = take_string_until(self.c, b'\0')
+            .ok_or_else(|| ErrorKind::Truncated("column list".to_owned()))?;
         // drop the extra tab in the end
         columns_str.pop();
-        let columns: HashMap<_, _> = columns_str
-            .split('\t')
-            .enumerate()
-            .map(|(idx, name)| (name, idx))
-            .collect();
-
-        let total_columns = columns.len();
-        let target_columns: Vec<_> = self
-            .required_columns
-            .iter()
-            .filter_map(|(name, tp)| {
-                columns.get(name).map(|idx| TargetColumn {
-                    name,
-                    idx: *idx,
-                    tp: *tp,
-                })
-            })
-            .collect();
-
-        if target_columns.len() != self.required_columns.len() {
-            return Err(format!(
-                "found {} columns, required {}",
-                target_columns.len(),
-                self.required_columns.len()
-            ));
+        if columns_str.is_empty() {
+            return Ok(vec![]);
         }
 
-        Ok((total_columns, target_columns))
+        Ok(columns_str.split('\t').map(ToOwned::to_owned).collect())
     }
 
-    fn read_row_count(&mut self) -> SResult<u32> {
-        take::<u32>(self.c).ok_or("couldn't read row count".to_owned())
+    fn read_row_count(&mut self) -> ReadResult<u32> {
+        take::<u32>(self.c).ok_or_else(|| ErrorKind::Truncated("row count".to_owned()))
     }
 
-    fn read_row_indices(&mut self, row_count: usize) -> SResult<Vec<usize>> {
-        let mut row_indices = vec![];
+    fn read_row_labels(&mut self, row_count: usize) -> ReadResult<Vec<String>> {
+        // a label is at least its tab
+        let mut labels = Vec::with_capacity(capacity(self.c, row_count, 1));
         for i in 0..row_count {
-            let string = take_string_until(self.c, b'\t')
-                .ok_or_else(|| format!("couldn't read row index {i}"))?;
-
-            row_indices.push(
-                string
-                    .parse()
-                    .map_err(|_| format!("invalid row index {string}"))?,
-            );
+            let label = take_string_until(self.c, b'\t')
+                .ok_or_else(|| ErrorKind::Truncated(format!("row label {i}")))?;
+            labels.push(label);
         }
-        Ok(row_indices)
+        Ok(labels)
     }
 
-    fn read_cell_offsets(&mut self, total_columns: usize, row_count: usize) -> SResult<Vec<u16>> {
+    fn read_cell_offsets(
+        &mut self,
+        total_columns: usize,
+        row_count: usize,
+    ) -> ReadResult<Vec<u16>> {
         // +1 for data size we don't need
-        let mut offsets = take_slice::<u16>(self.c, total_columns * row_count + 1)
-            .ok_or("couldn't read offsets")?
+        let mut offsets = total_columns
+            .checked_mul(row_count)
+            .and_then(|count| take_slice::<u16>(self.c, count.checked_add(1)?))
+            .ok_or_else(|| ErrorKind::Truncated("offsets".to_owned()))?
             .into_owned();
         // drop datasize
         offsets.pop();
@@ -129,81 +93,118 @@ impl<'a> Reader<'a> {
 
     fn read_rows(
         &mut self,
-        row_count: usize,
+        labels: Vec<String>,
         total_columns: usize,
-        row_indices: &[usize],
-        target_columns: &[TargetColumn],
         offsets: &[u16],
-    ) -> SResult<TwoDA> {
+    ) -> ReadResult<Vec<TwoDARow>> {
         let data_offset = self.c.position();
-        let mut rows = vec![];
+        let mut rows = Vec::with_capacity(labels.len());
+        // cells share strings, so the same bytes can be read many times
+        let mut budget = Budget::new(self.c.get_ref().len());
+
+        for (row_idx, label) in labels.into_iter().enumerate() {
+            let mut cells = Vec::with_capacity(total_columns);
+            for column_idx in 0..total_columns {
+                let pos = data_offset + offsets[row_idx * total_columns + column_idx] as u64;
+                self.c.seek_to(pos)?;
+                let value = take_string_until(self.c, b'\0').ok_or_else(|| {
+                    ErrorKind::Truncated(format!("column {column_idx} in row {row_idx}"))
+                })?;
+                budget.spend(value.len()).ok_or_else(|| {
+                    ErrorKind::TooLarge(format!("column {column_idx} in row {row_idx}"))
+                })?;
+
+                cells.push((!value.is_empty()).then_some(value));
+            }
+            rows.push(TwoDARow { label, cells });
+        }
+
+        Ok(rows)
+    }
 
-        for row_idx in 0..row_count {
-            let provided_idx = row_indices.get(row_idx).copied().unwrap_or_default();
-            if row_idx != provided_idx {
-                return Err(format!(
-                    "row index {provided_idx} doesn't match actual position {row_idx}"
-                ));
+    fn read_text(&mut self) -> ReadResult<TwoDA> {
+        let mut bytes = vec![];
+        self.c
+            .read_to_end(&mut bytes)
+            .map_err(|err| format!("couldn't read table: {err}"))?;
+        let text = bytes_to_string(bytes);
+        // first line is the rest of the file head
+        let mut lines = text.lines().skip(1).enumerate();
+
+        let mut default = None;
+        let mut columns = None;
+        for (idx, line) in &mut lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
             }
+            if let Some(value) = line.strip_prefix("DEFAULT:") {
+                default = split_text_line(value).pop();
+                continue;
+            }
+            let names = split_text_line(line);
+            if names.is_empty() {
+                return Err(format!("invalid column list on line {}", idx + 2).into());
+            }
+            columns = Some(names);
+            break;
+        }
+        let columns = columns.ok_or("couldn't find column list")?;
 
-            rows.push(self.read_row(
-                data_offset,
-                row_idx,
-                total_columns,
-                target_columns,
-                offsets,
-            )?);
+        let mut rows = vec![];
+        for (idx, line) in lines {
+            let mut values = split_text_line(line).into_iter();
+            let Some(label) = values.next() else {
+                continue;
+            };
+            let mut cells: Vec<_> = values
+                .map(|v| (v != EMPTY_CELL && !v.is_empty()).then_some(v))
+                .collect();
+            if cells.len() > columns.len() {
+                warn!("extra cells in 2da row {label} on line {}", idx + 2);
+            }
+            cells.resize(columns.len(), None);
+
+            rows.push(TwoDARow { label, cells });
         }
 
-        Ok(TwoDA(rows))
+        Ok(TwoDA {
+            format: TwoDAFormat::Text,
+            default,
+            columns,
+            rows,
+        })
     }
+}
 
-    fn read_row(
-        &mut self,
-        data_offset: u64,
-        row_idx: usize,
-        total_columns: usize,
-        target_columns: &[TargetColumn],
-        offsets: &[u16],
-    ) -> SResult<HashMap<&'static str, Option<TwoDAValue>>> {
-        let mut row = HashMap::new();
-        row.insert("_idx", Some(TwoDAValue::Int(row_idx as i32)));
-        for TargetColumn { name, idx, tp } in target_columns {
-            let pos = data_offset + offsets[row_idx * total_columns + idx] as u64;
-            self.c.seek_to(pos)?;
-            let value = take_string_until(self.c, b'\0')
-                .ok_or_else(|| format!("couldn't read column {name} in row {row_idx}"))?;
-
-            let parsed = if value.is_empty() {
-                None
-            } else {
-                Some(match tp {
-                    TwoDAType::Int => {
-                        let (str, radix) = if value.starts_with("0x") {
-                            (value.trim_start_matches("0x"), 16)
-                        } else {
-                            (value.as_str(), 10)
-                        };
-                        let v = i32::from_str_radix(str, radix).map_err(|_| {
-                            format!(
-                                "couldn't parse int column {name} in row {row_idx}, value: {value}"
-                            )
-                        })?;
-
-                        TwoDAValue::Int(v)
-                    }
-                    TwoDAType::String => TwoDAValue::String(value),
-                })
-            };
-            row.insert(*name, parsed);
+// splits by whitespace, "quoted values" can contain spaces
+fn split_text_line(line: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut chars = line.chars().peekable();
+
+    while let Some(&ch) = chars.peek() {
+        if ch.is_whitespace() {
+            chars.next();
+            continue;
         }
-        Ok(row)
+        let mut value = String::new();
+        if ch == '"' {
+            chars.next();
+            for ch in chars.by_ref() {
+                if ch == '"' {
+                    break;
+                }
+                value.push(ch);
+            }
+        } else {
+            while let Some(ch) = chars.next_if(|ch| !ch.is_whitespace()) {
+                value.push(ch);
+            }
+        }
+        values.push(value);
     }
+
+    values
 }
 
-impl_read_resource!(
-    TwoDA,
-    Reader,
-    &'a [(&'static str, TwoDAType)],
-    ResourceType::Twoda
-);
+impl_read_resource!(TwoDA, Reader, (), ResourceType::Twoda);
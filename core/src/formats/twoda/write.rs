@@ -0,0 +1,138 @@
+use crate::{
+    formats::twoda::{TwoDA, TwoDAFormat, EMPTY_CELL},
+    util::{bytes::IntoByteSlice as _, SResult},
+};
+use ahash::{HashMap, HashMapExt as _};
+use std::fmt::Write as _;
+
+struct Writer {
+    twoda: TwoDA,
+}
+
+impl Writer {
+    fn new(twoda: TwoDA) -> Self {
+        Self { twoda }
+    }
+
+    fn into_binary(self) -> SResult<Vec<u8>> {
+        let TwoDA { columns, rows, .. } = self.twoda;
+        let mut buf = b"2DA V2.b\n".to_vec();
+
+        // COLUMNS
+        for column in &columns {
+            buf.extend(column.as_bytes());
+            buf.push(b'\t');
+        }
+        buf.push(b'\0');
+        // ROW LABELS
+        buf.extend((rows.len() as u32).to_le_bytes());
+        for row in &rows {
+            buf.extend(row.label.as_bytes());
+            buf.push(b'\t');
+        }
+        // CELLS
+        // identical values are stored once
+        let mut value_map: HashMap<&str, u16> = HashMap::new();
+        let mut offsets = Vec::with_capacity(rows.len() * columns.len() + 1);
+        let mut data = vec![];
+        for row in &rows {
+            for cell in &row.cells {
+                let value = cell.as_deref().unwrap_or_default();
+                if let Some(offset) = value_map.get(value) {
+                    offsets.push(*offset);
+                    continue;
+                }
+                let offset = u16::try_from(data.len())
+                    .map_err(|_| "cell data doesn't fit into the binary format".to_owned())?;
+                data.extend(value.as_bytes());
+                data.push(b'\0');
+
+                value_map.insert(value, offset);
+                offsets.push(offset);
+            }
+        }
+        offsets.push(
+            u16::try_from(data.len())
+                .map_err(|_| "cell data doesn't fit into the binary format".to_owned())?,
+        );
+        buf.extend(offsets.as_slice().into_byte_slice());
+        buf.extend(data);
+
+        Ok(buf)
+    }
+
+    fn into_text(self) -> Vec<u8> {
+        let TwoDA {
+            default,
+            columns,
+            rows,
+            ..
+        } = self.twoda;
+        let escape = |value: &str| {
+            if value.is_empty() || value.contains(char::is_whitespace) {
+                format!("\"{value}\"")
+            } else {
+                value.to_owned()
+            }
+        };
+        let columns: Vec<_> = columns.iter().map(|c| escape(c)).collect();
+        let rows: Vec<(String, Vec<String>)> = rows
+            .iter()
+            .map(|row| {
+                let cells = row
+                    .cells
+                    .iter()
+                    .map(|c| c.as_deref().map_or_else(|| EMPTY_CELL.to_owned(), escape))
+                    .collect();
+                (escape(&row.label), cells)
+            })
+            .collect();
+
+        // aligning everything so it's readable
+        let label_width = rows.iter().map(|r| r.0.len()).max().unwrap_or_default();
+        let widths: Vec<_> = columns
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| {
+                rows.iter()
+                    .map(|r| r.1.get(idx).map_or(0, String::len))
+                    .chain([name.len()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let mut text = "2DA V2.0\r\n".to_owned();
+        if let Some(default) = default {
+            write!(text, "DEFAULT: {}", escape(&default)).unwrap();
+        }
+        text += "\r\n";
+
+        let mut line = " ".repeat(label_width);
+        for (name, width) in columns.iter().zip(&widths) {
+            write!(line, " {name:width$}").unwrap();
+        }
+        text += line.trim_end();
+        text += "\r\n";
+
+        for (label, cells) in rows {
+            let mut line = format!("{label:label_width$}");
+            for (cell, width) in cells.iter().zip(&widths) {
+                write!(line, " {cell:width$}").unwrap();
+            }
+            text += line.trim_end();
+            text += "\r\n";
+        }
+
+        text.into_bytes()
+    }
+}
+
+pub fn write(twoda: TwoDA) -> SResult<Vec<u8>> {
+    let format = twoda.format;
+    let writer = Writer::new(twoda);
+    match format {
+        TwoDAFormat::Binary => writer.into_binary(),
+        TwoDAFormat::Text => Ok(writer.into_text()),
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{
+    formats::FResult,
+    game_data::{GameData, LoadProgress, ResourceManager},
+    util::{ESResult, SResult},
+};
+use log::warn;
+use std::{fs, io::ErrorKind, path::Path};
+
+// bump when GameData changes, older caches are rebuilt then
+const CACHE_VERSION: u32 = 2;
+
+impl GameData {
+    // reuses the cache until the install changes, rebuild replaces it
+    pub fn read_cached(
+        resources: &ResourceManager,
+        cache: Option<&Path>,
+        rebuild: bool,
+        progress: &(dyn Fn(LoadProgress) + Sync),
+    ) -> FResult<Self> {
+        let read = || Self::from_resources(resources, progress);
+        match cache {
+            Some(cache) => read_or_rebuild(cache, resources.fingerprint(), rebuild, read),
+            None => read(),
+        }
+    }
+}
+
+// a rebuild only skips reading the cache, the fresh data replaces it like any other
+pub(crate) fn read_or_rebuild(
+    cache: &Path,
+    fingerprint: u64,
+    rebuild: bool,
+    read: impl FnOnce() -> FResult<GameData>,
+) -> FResult<GameData> {
+    if !rebuild {
+        match read_cache(cache, fingerprint) {
+            Ok(Some(data)) => return Ok(data),
+            Ok(None) => {}
+            Err(err) => warn!("couldn't read game data cache {}: {err}", cache.display()),
+        }
+    }
+
+    let data = read()?;
+    if let Err(err) = write_cache(cache, &data) {
+        warn!("couldn't write game data cache {}: {err}", cache.display());
+    }
+
+    Ok(data)
+}
+
+// None if there's no cache or it's for a different install
+pub fn read_cache(path: &Path, fingerprint: u64) -> SResult<Option<GameData>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.to_string()),
+    };
+    // the id is the fingerprint and it comes first, no need to decode the rest if it's stale
+    let (version, id): (u32, u64) = bincode::deserialize(&bytes).map_err(|err| err.to_string())?;
+    if version != CACHE_VERSION || id != fingerprint {
+        return Ok(None);
+    }
+    let (_, data): (u32, GameData) = bincode::deserialize(&bytes).map_err(|err| err.to_string())?;
+
+    Ok(Some(data))
+}
+
+pub fn write_cache(path: &Path, data: &GameData) -> ESResult {
+    let bytes = bincode::serialize(&(CACHE_VERSION, data)).map_err(|err| err.to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    // so the app closing in the middle doesn't leave half of a cache
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes).map_err(|err| err.to_string())?;
+    fs::rename(&tmp_path, path).map_err(|err| err.to_string())
+}
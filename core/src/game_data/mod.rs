@@ -1,32 +1,35 @@
 use crate::{
     formats::{
         gff::Gff,
-        key::Key,
-        twoda::{TwoDA, TwoDAType},
-        ReadResource, ResourceType,
+        twoda::{TwoDA, TwoDAProjection, TwoDAType},
+        FResult, FormatError, ResourceType,
     },
     game_data::read::{
-        find_source, find_sources_by_name, find_sources_by_type, get_resource, get_resources,
-        read_appearances, read_classes, read_feats, read_items, read_quests, read_workshop_dir,
+        io_error, read_appearances, read_classes, read_feats, read_items, read_quests,
     },
     gff::Struct,
-    util::{
-        fs::{read_dir_filemap, read_file},
-        shorten_string, Game, SResult,
-    },
+    util::{encoding::CodePage, parallel::join, shorten_string, Game},
 };
 use ahash::HashMap;
 use macros::{EnumFromInt, EnumList};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::BTreeMap,
-    fs,
-    path::{Path, PathBuf},
+    fmt, fs,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use self::read::read_base_items;
 
+mod cache;
 mod read;
+mod resources;
+mod strings;
+mod textures;
+pub use resources::*;
+pub use strings::StringResolver;
+pub use textures::Textures;
 
 const TWODAS: &[(&str, &[(&str, TwoDAType)])] = &[
     (
@@ -64,9 +67,12 @@ const TWODAS: &[(&str, &[(&str, TwoDAType)])] = &[
             ("equipableslots", TwoDAType::Int),
             ("droidorhuman", TwoDAType::Int),
             ("weapontype", TwoDAType::Int),
+            ("itemclass", TwoDAType::String),
         ],
     ),
 ];
+// feats, powers, classes, quests, items
+const STRING_STEPS: usize = 5;
 
 pub trait Data<I> {
     fn get_id(&self) -> &I;
@@ -253,6 +259,15 @@ pub struct BaseItem {
     pub label: String,
     pub usable_by: UsableBy,
     pub slot: ItemSlot,
+    pub item_class: String,
+}
+
+impl BaseItem {
+    // inventory icon texture, e.g. iw_blstrpstl_001
+    pub fn icon(&self, model_variation: u8) -> Option<String> {
+        (!self.item_class.is_empty())
+            .then(|| format!("i{}_{model_variation:03}", self.item_class.to_lowercase()))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -265,6 +280,7 @@ pub struct Item {
     pub stack_size: u16,
     pub charges: u8,
     pub upgrade_level: Option<u8>,
+    pub icon: Option<String>,
 
     pub raw: Struct,
 }
@@ -285,7 +301,10 @@ impl_data_descr!(Item);
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GameData {
+    // fingerprint of the install it was read from
     pub id: u64,
+    // of the TLK, save text is expected to be in it as well
+    pub code_page: CodePage,
     pub feats: Vec<Feat>,
     pub powers: Vec<Power>,
     pub classes: Vec<Class>,
@@ -297,64 +316,113 @@ pub struct GameData {
     pub items: Vec<Item>,
 }
 
-impl GameData {
-    pub fn read<P: AsRef<Path>>(game: Game, dir: P, steam_dir: Option<P>) -> SResult<Self> {
-        let mut dir: PathBuf = dir.as_ref().into();
-        let mut map = read_dir_filemap(&dir)
-            .map_err(|err| format!("couldn't read game dir {dir:?}: {err}"))?;
-        // updated steam version of TSL stores game data in steamassets dir
-        let steam_assets = map.get("steamassets");
-        if game == Game::Two && steam_assets.is_some() {
-            dir.push(steam_assets.unwrap());
-            map = read_dir_filemap(&dir)
-                .map_err(|err| format!("couldn't read steamassets dir {dir:?}: {err}"))?;
-        }
-        let mut dialog_path = dir.clone();
-        dialog_path.push(map.get("dialog.tlk").ok_or("couldn't find dialog.tlk")?);
-
-        let mut overrides = if game == Game::Two && steam_dir.is_some() {
-            let (dialog_override, overrides) = read_workshop_dir(steam_dir.unwrap().as_ref());
-            if let Some(dialog) = dialog_override {
-                dialog_path = dialog;
-            }
-            overrides
-        } else {
-            vec![]
-        };
-
-        // main dir override goes after workshop so it's of least priority
-        if let Some(dir_override) = map.get("override") {
-            let mut dir = dir.clone();
-            dir.push(dir_override);
-            overrides.push(dir);
-        }
-
-        let key_bytes = read_file(&dir, "chitin.key")
-            .map_err(|err| format!("couldn't read chitin.key file: {err}"))?;
-        let key = Key::read(&key_bytes, ()).map_err(|err| format!("couldn't read key: {err}"))?;
-
-        let (twoda_names, twoda_args): (Vec<_>, Vec<_>) = TWODAS.iter().copied().unzip();
-        let twoda_sources = find_sources_by_name(&overrides, &key, &twoda_names, TwoDA::get_type())
-            .map_err(|err| format!("couldn't find 2da: {err}"))?;
-        let twodas: Vec<TwoDA> = get_resources(&dir, twoda_sources, &twoda_args)
-            .map_err(|err| format!("couldn't read 2da: {err}"))?;
-        let [feats, powers, classes, portraits, appearances, soundsets, baseitems] =
-            twodas.try_into().unwrap();
+// what GameData::from_resources is busy with, the first three run at the same time
+#[derive(EnumList, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum LoadStage {
+    TwoDAs,
+    Journal,
+    Items,
+    // looking up the names and descriptions in the TLK
+    Strings,
+}
 
-        let journal_source = find_source(&overrides, &key, "global", ResourceType::Jrl)
-            .ok_or("couldn't find global.jrl")?;
-        let journal: Gff = get_resource(&dir, journal_source, ())
-            .map_err(|err| format!("couldn't read global.jrl: {err}"))?;
+impl fmt::Display for LoadStage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::TwoDAs => "2DAs",
+            Self::Journal => "journal",
+            Self::Items => "items",
+            Self::Strings => "strings",
+        })
+    }
+}
 
-        let item_sources = find_sources_by_type(&overrides, &key, ResourceType::Uti);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub stage: LoadStage,
+    pub done: usize,
+    pub total: usize,
+}
 
-        let item_count = item_sources.len();
-        let items: Vec<Gff> = get_resources(&dir, item_sources, &vec![(); item_count])
-            .map_err(|err| format!("couldn't read item: {err}"))?;
+impl GameData {
+    pub fn read<P: AsRef<Path>>(game: Game, dir: P, steam_dir: Option<P>) -> FResult<Self> {
+        Self::from_resources(&ResourceManager::new(game, dir, steam_dir)?, &|_| ())
+    }
 
-        let tlk_bytes = fs::read(&dialog_path)
-            .map_err(|err| format!("couldn't read {dialog_path:?}: {err}"))?;
+    fn read_twodas(
+        resources: &ResourceManager,
+        progress: &(dyn Fn(LoadProgress) + Sync),
+    ) -> FResult<Vec<TwoDAProjection>> {
+        let (names, columns): (Vec<_>, Vec<_>) = TWODAS.iter().copied().unzip();
+        let resolved = names
+            .iter()
+            .map(|name| {
+                resources
+                    .resolve(name, ResourceType::Twoda)
+                    .ok_or_else(|| FormatError::missing(ResourceType::Twoda, *name))
+            })
+            .collect::<FResult<Vec<_>>>()?;
+        let twodas: Vec<TwoDA> =
+            resources.read_all_with_progress(&resolved, &[(); TWODAS.len()], &|done, total| {
+                progress(LoadProgress {
+                    stage: LoadStage::TwoDAs,
+                    done,
+                    total,
+                });
+            })?;
+
+        twodas
+            .iter()
+            .zip(names.iter().zip(columns))
+            .map(|(twoda, (name, columns))| {
+                twoda
+                    .project(columns)
+                    .map_err(|err| FormatError::invalid(ResourceType::Twoda, *name, err))
+            })
+            .collect()
+    }
 
+    // progress is called from several threads, stages report separately
+    pub fn from_resources(
+        resources: &ResourceManager,
+        progress: &(dyn Fn(LoadProgress) + Sync),
+    ) -> FResult<Self> {
+        let report = |stage, done, total| progress(LoadProgress { stage, done, total });
+        let game = resources.game();
+
+        let dialog_path = resources.dialog();
+        let tlk_bytes =
+            fs::read(dialog_path).map_err(|err| io_error(ResourceType::Tlk, dialog_path, &err))?;
+        let strings = StringResolver::new(tlk_bytes)
+            .map_err(|err| err.with_resource(dialog_path.display().to_string()))?;
+        // plain strings don't specify the language so it's assumed to be the same
+        let code_page = CodePage::from_language(strings.language());
+
+        let read_journal = || {
+            report(LoadStage::Journal, 0, 1);
+            let journal: FResult<Gff> = resources.get("global", ResourceType::Jrl, code_page);
+            report(LoadStage::Journal, 1, 1);
+            journal
+        };
+        let read_item_templates = || {
+            let sources = resources.list(ResourceType::Uti);
+            report(LoadStage::Items, 0, sources.len());
+            resources.read_all_with_progress::<Gff, _>(
+                &sources,
+                &vec![code_page; sources.len()],
+                &|done, total| report(LoadStage::Items, done, total),
+            )
+        };
+        let (twodas, (journal, items)) = join(
+            || Self::read_twodas(resources, progress),
+            || join(read_journal, read_item_templates),
+        );
+        let [feats, powers, classes, portraits, appearances, soundsets, base_items_twoda] =
+            twodas?.try_into().unwrap();
+        let (journal, items) = (journal?, items?);
+
+        let base_items = read_base_items(base_items_twoda);
         let mut soundsets = read_appearances(soundsets, "label");
         if game == Game::Two && !soundsets.iter().any(|s| s.id == 85) {
             // for some reason they aren't in the 2da, the rest seems to be fine
@@ -368,33 +436,65 @@ impl GameData {
             soundsets.sort_unstable_by(|a, b| a.name.cmp(&b.name));
         };
 
+        // each of these looks its strings up as it goes, the resolver is shared
+        let strings_done = AtomicUsize::new(0);
+        let step_done = || {
+            let done = strings_done.fetch_add(1, Ordering::Relaxed) + 1;
+            report(LoadStage::Strings, done, STRING_STEPS);
+        };
+        report(LoadStage::Strings, 0, STRING_STEPS);
+        let read_feat_names = || {
+            let feats = read_feats(feats, &strings, "description", None);
+            step_done();
+            feats
+        };
+        let read_power_names = || {
+            let power_types = ["FORCE_POWER", "FORM_FORCE", "FORM_SABER"];
+            let powers = read_feats(powers, &strings, "spelldesc", Some(&power_types));
+            step_done();
+            powers
+        };
+        let read_class_names = || {
+            let classes = read_classes(classes, &strings);
+            step_done();
+            classes
+        };
+        let read_quest_names = || {
+            let quests = read_quests(journal, &strings);
+            step_done();
+            quests
+        };
+        let read_item_names = || {
+            let items = read_items(items, &base_items, &strings);
+            step_done();
+            items
+        };
+        let ((feats, powers), (classes, (quests, items))) = join(
+            || join(read_feat_names, read_power_names),
+            || join(read_class_names, || join(read_quest_names, read_item_names)),
+        );
+
         Ok(Self {
-            id: fastrand::u64(..),
-            feats: read_feats(feats, &tlk_bytes, "description", None)
-                .map_err(|err| format!("couldn't read feats: {err}"))?
+            id: resources.fingerprint(),
+            code_page,
+            feats: feats
+                .map_err(|err| FormatError::invalid(ResourceType::Twoda, "feat", err))?
                 .into_iter()
                 .map(|(f, _)| f)
                 .collect(),
-            powers: read_feats(
-                powers,
-                &tlk_bytes,
-                "spelldesc",
-                Some(&["FORCE_POWER", "FORM_FORCE", "FORM_SABER"]),
-            )
-            .map_err(|err| format!("couldn't read powers: {err}"))?
-            .into_iter()
-            .map(Into::into)
-            .collect(),
-            classes: read_classes(classes, &tlk_bytes)
-                .map_err(|err| format!("couldn't read classes: {err}"))?,
+            powers: powers
+                .map_err(|err| FormatError::invalid(ResourceType::Twoda, "spells", err))?
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            classes: classes
+                .map_err(|err| FormatError::invalid(ResourceType::Twoda, "classes", err))?,
             portraits: read_appearances(portraits, "baseresref"),
             appearances: read_appearances(appearances, "label"),
             soundsets,
-            quests: read_quests(journal, &tlk_bytes)
-                .map_err(|err| format!("couldn't read journal: {err}"))?,
-            base_items: read_base_items(baseitems),
-            items: read_items(items, &tlk_bytes)
-                .map_err(|err| format!("couldn't read items: {err}"))?,
+            quests: quests.map_err(|err| FormatError::invalid(ResourceType::Jrl, "global", err))?,
+            items: items.map_err(|err| FormatError::invalid(ResourceType::Uti, "items", err))?,
+            base_items,
         })
     }
 }
@@ -407,12 +507,15 @@ macro_rules! impl_game_data_mapped {
         pub struct GameDataMapped {
             $($(pub $field: HashMap<$id_type, $s>,)+)+
             pub inner: GameData,
+            // only read from the game dir, not a part of the bundled data
+            pub textures: Option<Textures>,
         }
         impl From<GameData> for GameDataMapped {
             fn from(inner: GameData) -> GameDataMapped {
                 GameDataMapped {
                     $($($field: inner.$field.clone().into_iter().map(|s| (s.get_id().clone(), s)).collect(),)+)+
                     inner,
+                    textures: None,
                 }
             }
         }
@@ -427,3 +530,255 @@ impl_game_data_mapped!(
     [Quest, String, [quests,]],
     [Item, String, [items,]],
 );
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        formats::{
+            bif::{self, Bif},
+            erf::{self, Erf, Resource},
+            key::{self, Key},
+            rim::{self, Rim},
+            tlk::{self, Tlk, TlkEntry},
+            twoda::{self, TwoDA, TwoDAFormat, TwoDARow},
+            ResourceType,
+        },
+        game_data::{
+            cache::{read_cache, read_or_rebuild, write_cache},
+            GameData, Layer, ResourceManager, StringResolver,
+        },
+        util::{encoding::CodePage, Game},
+    };
+    use ahash::HashMap;
+    use std::{fs, path::Path, sync::Mutex};
+
+    fn make_twoda(value: &str) -> Vec<u8> {
+        twoda::write(TwoDA {
+            format: TwoDAFormat::Binary,
+            default: None,
+            columns: vec!["value".to_owned()],
+            rows: vec![TwoDARow {
+                label: "0".to_owned(),
+                cells: vec![Some(value.to_owned())],
+            }],
+        })
+        .unwrap()
+    }
+
+    // feat.2da is everywhere but override, spells.2da is everywhere but chitin
+    fn make_game_dir(dir: &Path) {
+        for sub in ["data", "modules", "Override", "TexturePacks"] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        fs::write(dir.join("dialog.tlk"), tlk::write(Tlk::new(0))).unwrap();
+
+        let mut key = Key {
+            files: vec![],
+            resources: HashMap::default(),
+            build_year: 124,
+            build_day: 1,
+        };
+        let mut bif = Bif::default();
+        for (name, tp, content) in [
+            ("FEAT", ResourceType::Twoda, make_twoda("chitin")),
+            ("global", ResourceType::Jrl, vec![1, 2, 3]),
+        ] {
+            let res_ref = bif.push(0, tp, content);
+            key.resources.insert((name, tp).into(), res_ref);
+        }
+        let bif = bif::write(bif);
+        key.add_file("data\\2da.bif".to_owned(), bif.len() as u32);
+        fs::write(dir.join("data").join("2da.bif"), bif).unwrap();
+        fs::write(dir.join("chitin.key"), key::write(key)).unwrap();
+
+        let mut module = Erf::new(("MOD ", "V1.0").into());
+        for name in ["feat", "spells"] {
+            module.resources.insert(
+                (name, ResourceType::Twoda).into(),
+                Resource {
+                    name: name.to_owned(),
+                    id: module.resources.len() as u32,
+                    content: make_twoda("modules"),
+                },
+            );
+        }
+        fs::write(dir.join("modules").join("a.mod"), erf::write(module)).unwrap();
+
+        let mut module = Rim::new();
+        module.resources.insert(
+            ("module", ResourceType::Ifo).into(),
+            Resource {
+                name: "module".to_owned(),
+                id: 0,
+                content: vec![7, 8],
+            },
+        );
+        fs::write(dir.join("modules").join("b.rim"), rim::write(module)).unwrap();
+
+        let mut pack = Erf::new(("ERF ", "V1.0").into());
+        pack.resources.insert(
+            ("po_revan", ResourceType::Tpc).into(),
+            Resource {
+                name: "PO_Revan".to_owned(),
+                id: 0,
+                content: vec![4, 5, 6],
+            },
+        );
+        let pack_path = dir.join("TexturePacks").join("swpc_tex_gui.erf");
+        fs::write(pack_path, erf::write(pack)).unwrap();
+
+        let spells = dir.join("Override").join("Spells.2DA");
+        fs::write(spells, make_twoda("override")).unwrap();
+    }
+
+    #[test]
+    fn resource_layers() {
+        let dir = std::env::temp_dir().join(format!("sotor_layers_{}", fastrand::u64(..)));
+        make_game_dir(&dir);
+        let resources = ResourceManager::new(Game::One, &dir, None).unwrap();
+
+        let feat = resources.resolve("Feat", ResourceType::Twoda).unwrap();
+        assert_eq!(feat.name, "feat");
+        assert_eq!(feat.layer, Layer::Modules);
+        let spells = resources.resolve("spells", ResourceType::Twoda).unwrap();
+        assert_eq!(spells.layer, Layer::Override);
+        let journal = resources.resolve("global", ResourceType::Jrl).unwrap();
+        assert_eq!(journal.layer, Layer::Chitin);
+        assert_eq!(*resources.get_bytes(&journal).unwrap(), [1, 2, 3]);
+        assert!(resources.resolve("global", ResourceType::Twoda).is_none());
+        let info = resources.resolve("module", ResourceType::Ifo).unwrap();
+        assert_eq!(info.layer, Layer::Modules);
+        assert_eq!(*resources.get_bytes(&info).unwrap(), [7, 8]);
+        let portrait = resources.resolve("PO_Revan", ResourceType::Tpc).unwrap();
+        assert_eq!(portrait.layer, Layer::TexturePack);
+        assert_eq!(*resources.get_bytes(&portrait).unwrap(), [4, 5, 6]);
+        assert_eq!(resources.list(ResourceType::Tpc), [portrait]);
+
+        let list = resources.list(ResourceType::Twoda);
+        assert_eq!(list, [feat, spells]);
+        let reported = Mutex::new(vec![]);
+        let twodas: Vec<TwoDA> = resources
+            .read_all_with_progress(&list, &[(), ()], &|done, total| {
+                reported.lock().unwrap().push((done, total));
+            })
+            .unwrap();
+        let mut reported = reported.into_inner().unwrap();
+        reported.sort_unstable();
+        assert_eq!(reported, [(1, 2), (2, 2)]);
+        let values: Vec<_> = twodas
+            .iter()
+            .map(|t| t.rows[0].cells[0].as_deref().unwrap())
+            .collect();
+        assert_eq!(values, ["modules", "override"]);
+
+        let err = resources
+            .get_by_names::<TwoDA, _>(&["feat", "classes"], ResourceType::Twoda, &[(), ()])
+            .unwrap_err();
+        assert_eq!(err.resource.as_deref(), Some("classes"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn install_cache() {
+        let dir = std::env::temp_dir().join(format!("sotor_cache_{}", fastrand::u64(..)));
+        make_game_dir(&dir);
+        let fingerprint = || {
+            ResourceManager::new(Game::One, &dir, None)
+                .unwrap()
+                .fingerprint()
+        };
+        let original = fingerprint();
+        assert_eq!(fingerprint(), original);
+        let spells = dir.join("Override").join("Spells.2DA");
+        fs::write(spells, make_twoda("changed override")).unwrap();
+        let changed = fingerprint();
+        assert_ne!(changed, original);
+
+        let data = GameData {
+            id: changed,
+            code_page: CodePage::Windows1251,
+            feats: vec![],
+            powers: vec![],
+            classes: vec![],
+            portraits: vec![],
+            appearances: vec![],
+            soundsets: vec![],
+            quests: vec![],
+            base_items: HashMap::default(),
+            items: vec![],
+        };
+        let path = dir.join("cache").join("game_data.bin");
+        assert!(read_cache(&path, changed).unwrap().is_none());
+        write_cache(&path, &data).unwrap();
+        let cached = read_cache(&path, changed).unwrap().unwrap();
+        assert_eq!(
+            (cached.id, cached.code_page),
+            (changed, CodePage::Windows1251)
+        );
+        assert!(read_cache(&path, original).unwrap().is_none());
+
+        fs::write(&path, [1, 0, 0, 0]).unwrap();
+        assert!(read_cache(&path, changed).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rebuild_cache() {
+        let dir = std::env::temp_dir().join(format!("sotor_rebuild_{}", fastrand::u64(..)));
+        let path = dir.join("game_data.bin");
+        let data = |code_page| GameData {
+            id: 1,
+            code_page,
+            feats: vec![],
+            powers: vec![],
+            classes: vec![],
+            portraits: vec![],
+            appearances: vec![],
+            soundsets: vec![],
+            quests: vec![],
+            base_items: HashMap::default(),
+            items: vec![],
+        };
+        let read = |rebuild, code_page| {
+            read_or_rebuild(&path, 1, rebuild, || Ok(data(code_page)))
+                .unwrap()
+                .code_page
+        };
+
+        assert_eq!(read(false, CodePage::Windows1251), CodePage::Windows1251);
+        // up to date, so it's what gets read
+        assert_eq!(read(false, CodePage::Windows1250), CodePage::Windows1251);
+        // the fresh data replaces it even though it's still up to date
+        assert_eq!(read(true, CodePage::Windows1250), CodePage::Windows1250);
+        let cached = read_cache(&path, 1).unwrap().unwrap();
+        assert_eq!(cached.code_page, CodePage::Windows1250);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn string_resolver() {
+        let mut base = Tlk::new(0);
+        for text in ["Zero", "One", "Two"] {
+            base.push(TlkEntry::new(text.to_owned()));
+        }
+        // the first entry has no text, so the base one is used
+        let mut custom = Tlk::new(0);
+        custom.push(TlkEntry::default());
+        custom.push(TlkEntry::new("Custom".to_owned()));
+
+        let strings = StringResolver::new(tlk::write(base)).unwrap();
+        assert_eq!(strings.get(1).unwrap(), "One");
+        let strings = strings.with_custom(tlk::write(custom)).unwrap();
+        assert_eq!(strings.get(0).unwrap(), "Zero");
+        assert_eq!(strings.get(1).unwrap(), "Custom");
+        assert_eq!(strings.get(1).unwrap(), "Custom");
+        assert_eq!(strings.get(2).unwrap(), "Two");
+        assert_eq!(strings.get(3).unwrap(), "#MISSING STRING#");
+        assert_eq!(strings.get_signed(-1).unwrap(), "");
+
+        assert!(StringResolver::new(vec![0; 4]).is_err());
+    }
+}
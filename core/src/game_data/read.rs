@@ -1,173 +1,80 @@
 use crate::{
     formats::{
-        bif::Bif,
         gff::{Field, Gff},
-        key::Key,
-        tlk::Tlk,
-        twoda::TwoDA,
-        ReadResource, ResourceType,
+        twoda::TwoDAProjection,
+        ErrorKind, FResult, FormatError, ResourceType,
     },
-    game_data::{Appearance, Class, Feat, Item, Quest, QuestStage},
+    game_data::{Appearance, Class, Feat, Item, Quest, QuestStage, StringResolver},
     util::{
-        fs::{read_dir_dirs, read_dir_filemap, read_file},
-        prefix_to_sort_suffix, prepare_item_name, SResult,
+        fs::{read_dir_dirs, read_dir_filemap},
+        prefix_to_sort_suffix, prepare_item_name, Game, SResult,
     },
     BaseItem, Data, ItemSlot, WeaponType,
 };
 use ahash::{HashMap, HashMapExt as _};
 use std::{
-    fs, mem,
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    fs,
+    hash::{Hash, Hasher},
     path::{Path, PathBuf},
 };
 
-#[derive(Debug)]
-pub enum ResourceSource {
-    File(PathBuf),
-    Bif { file: PathBuf, res_idx: u32 },
+pub fn io_error(format: ResourceType, path: &Path, err: &std::io::Error) -> FormatError {
+    FormatError::new(format, ErrorKind::Io(err.to_string()))
+        .with_resource(path.display().to_string())
 }
 
-pub fn find_source(
-    overrides: &[PathBuf],
-    key: &Key,
-    name: &str,
-    tp: ResourceType,
-) -> Option<ResourceSource> {
-    for over in overrides {
-        let mut path = over.clone();
-        path.push(name);
-        path.set_extension(tp.to_extension());
-        if path.exists() {
-            return Some(ResourceSource::File(path));
-        }
-    }
-
-    let res_ref = key.resources.get(&(name, tp).into())?;
-
-    let file = key.get_file_path(res_ref.file_idx);
-    Some(ResourceSource::Bif {
-        file,
-        res_idx: res_ref.resource_idx,
-    })
-}
-
-pub fn find_sources_by_name(
-    overrides: &[PathBuf],
-    key: &Key,
-    names: &[&str],
-    tp: ResourceType,
-) -> SResult<Vec<ResourceSource>> {
-    let mut sources = Vec::with_capacity(names.len());
-
-    for name in names {
-        let Some(source) = find_source(overrides, key, name, tp) else {
-            return Err(format!("couldn't find resource {name}"));
-        };
-        sources.push(source);
-    }
-
-    Ok(sources)
-}
-
-pub fn find_sources_by_type(
-    overrides: &[PathBuf],
-    key: &Key,
-    tp: ResourceType,
-) -> Vec<ResourceSource> {
-    // search in Key
-    let mut sources = vec![];
-    for (_, v) in key.resources.iter().filter(|(k, _)| k.1 == tp) {
-        sources.push(ResourceSource::Bif {
-            file: key.get_file_path(v.file_idx),
-            res_idx: v.resource_idx,
-        });
-    }
-
-    // search loose files in override
-    let mut ext = tp.to_extension();
-    ext.insert(0, '.');
-    for over in overrides {
-        let files = read_dir_filemap(over).unwrap_or_default();
-
-        for (_, v) in files.iter().filter(|(k, _)| k.ends_with(&ext)) {
-            sources.push(ResourceSource::File(PathBuf::from_iter([
-                over.clone(),
-                v.into(),
-            ])));
-        }
-    }
-
-    sources
+// the game dir and the dirs with loose files
+pub struct GameDirs {
+    pub dir: PathBuf,
+    // lowercase -> real names of files in the game dir
+    pub files: HashMap<String, String>,
+    // override dirs of steam workshop mods
+    pub workshop: Vec<PathBuf>,
+    pub override_dir: Option<PathBuf>,
+    pub dialog: PathBuf,
 }
 
-pub fn get_resources<'a, T: ReadResource<'a, Arg>, Arg: 'a + Copy>(
-    dir: &Path,
-    sources: Vec<ResourceSource>,
-    args: &[Arg],
-) -> SResult<Vec<T>> {
-    let mut in_files = vec![];
-    // accumulating so we can read everything in the bif in 1 go
-    let mut in_bif = HashMap::new();
-    for (idx, source) in sources.into_iter().enumerate() {
-        match source {
-            ResourceSource::File(path) => {
-                in_files.push((idx, path));
-            }
-            ResourceSource::Bif { file, res_idx } => {
-                let bif_resources = in_bif.entry(file).or_insert_with(Vec::new);
-                bif_resources.push((idx, res_idx as usize));
-            }
-        }
+pub fn read_game_dirs<P: AsRef<Path>>(
+    game: Game,
+    dir: P,
+    steam_dir: Option<P>,
+) -> FResult<GameDirs> {
+    let mut dir: PathBuf = dir.as_ref().into();
+    // without a readable game dir there's no chitin.key either
+    let mut map = read_dir_filemap(&dir).map_err(|err| io_error(ResourceType::Key, &dir, &err))?;
+    // updated steam version of TSL stores game data in steamassets dir
+    let steam_assets = map.get("steamassets");
+    if game == Game::Two && steam_assets.is_some() {
+        dir.push(steam_assets.unwrap());
+        map = read_dir_filemap(&dir).map_err(|err| io_error(ResourceType::Key, &dir, &err))?;
     }
-
-    let mut resource_bytes = vec![];
-
-    for (idx, path) in in_files {
-        let bytes = fs::read(&path)
-            .map_err(|err| format!("couldn't read resource file {path:?}: {err}"))?;
-        resource_bytes.push((idx, bytes));
-    }
-
-    for (file, resources) in in_bif {
-        let bif_bytes =
-            read_file(dir, &file).map_err(|err| format!("couldn't read bif {file:?}: {err}"))?;
-        let (indices, res_indices): (Vec<_>, Vec<_>) = resources.into_iter().unzip();
-        let bif = Bif::read(&bif_bytes, &res_indices)
-            .map_err(|err| format!("couldn't read bif {file:?}: {err}"))?;
-
-        for (idx, bytes) in bif.resources.into_iter().enumerate() {
-            resource_bytes.push((indices[idx], bytes));
-        }
-    }
-    resource_bytes.sort_unstable_by_key(|r| r.0);
-
-    let mut resources = Vec::with_capacity(resource_bytes.len());
-    for (idx, bytes) in resource_bytes {
-        let resource = T::read(&bytes, args[idx])
-            .map_err(|err| format!("couldn't read resource {idx}: {err}"))?;
-        resources.push(resource);
-    }
-
-    Ok(resources)
-}
-
-pub fn get_resource<'a, T: ReadResource<'a, Arg>, Arg: 'a + Copy>(
-    dir: &Path,
-    source: ResourceSource,
-    arg: Arg,
-) -> SResult<T> {
-    let bytes = match source {
-        ResourceSource::File(path) => {
-            fs::read(&path).map_err(|err| format!("couldn't read file {path:?}: {err}"))?
-        }
-        ResourceSource::Bif { file, res_idx } => {
-            let bif_bytes = read_file(dir, &file)
-                .map_err(|err| format!("couldn't read bif {file:?}: {err}"))?;
-            let mut bif = Bif::read(&bif_bytes, &[res_idx as usize])
-                .map_err(|err| format!("couldn't read bif {file:?}: {err}"))?;
-            bif.resources.pop().unwrap()
+    let mut dialog_path = dir.clone();
+    dialog_path.push(
+        map.get("dialog.tlk")
+            .ok_or_else(|| FormatError::missing(ResourceType::Tlk, "dialog.tlk"))?,
+    );
+
+    let workshop = if game == Game::Two && steam_dir.is_some() {
+        let (dialog_override, overrides) = read_workshop_dir(steam_dir.unwrap().as_ref());
+        if let Some(dialog) = dialog_override {
+            dialog_path = dialog;
         }
+        overrides
+    } else {
+        vec![]
     };
-    T::read(&bytes, arg)
+    let override_dir = map
+        .get("override")
+        .map(|dir_override| PathBuf::from_iter([&dir, Path::new(dir_override)]));
+
+    Ok(GameDirs {
+        dir,
+        files: map,
+        workshop,
+        override_dir,
+        dialog: dialog_path,
+    })
 }
 
 pub fn read_workshop_dir(steam_dir: impl AsRef<Path>) -> (Option<PathBuf>, Vec<PathBuf>) {
@@ -197,23 +104,52 @@ pub fn read_workshop_dir(steam_dir: impl AsRef<Path>) -> (Option<PathBuf>, Vec<P
     (dialog_override, overrides)
 }
 
-fn to_str_ref(v: i32) -> usize {
-    if v.is_negative() {
-        u32::MAX as usize
-    } else {
-        v as usize
+fn hash_file(hasher: &mut DefaultHasher, path: &Path) {
+    path.hash(hasher);
+    // a file going missing is a change too
+    let metadata = fs::metadata(path).ok();
+    metadata.as_ref().map(fs::Metadata::len).hash(hasher);
+    metadata.and_then(|m| m.modified().ok()).hash(hasher);
+}
+
+fn hash_dir(hasher: &mut DefaultHasher, dir: &Path) {
+    let mut names: Vec<_> = read_dir_filemap(&dir.into())
+        .unwrap_or_default()
+        .into_values()
+        .collect();
+    names.sort_unstable();
+    for name in names {
+        hash_file(hasher, &dir.join(name));
     }
 }
 
+// changes whenever any of the files game data comes from does, only looks at sizes and dates
+pub fn fingerprint(game: Game, dirs: &GameDirs) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    game.idx().hash(&mut hasher);
+    let key = dirs
+        .files
+        .get("chitin.key")
+        .map_or("chitin.key", String::as_str);
+    hash_file(&mut hasher, &dirs.dir.join(key));
+    hash_file(&mut hasher, &dirs.dialog);
+    for dir in dirs.workshop.iter().chain(&dirs.override_dir) {
+        hash_dir(&mut hasher, dir);
+    }
+    if let Some(modules) = dirs.files.get("modules") {
+        hash_dir(&mut hasher, &dirs.dir.join(modules));
+    }
+
+    hasher.finish()
+}
+
 pub fn read_feats(
-    twoda: TwoDA,
-    tlk_bytes: &[u8],
+    twoda: TwoDAProjection,
+    strings: &StringResolver,
     descr_field: &str,
     extra_filter: Option<&[&str]>,
 ) -> SResult<Vec<(Feat, bool)>> {
-    let mut tmp = Vec::with_capacity(twoda.0.len());
-    let mut str_refs = Vec::with_capacity(twoda.0.len() * 2);
-    let mut idx = 0;
+    let mut feats = Vec::with_capacity(twoda.0.len());
     for feat in twoda.0 {
         let id = *feat["_idx"].as_ref().unwrap().int_unwrap();
         let label = feat["label"]
@@ -230,24 +166,14 @@ pub fn read_feats(
             !prefixes.iter().any(|p| label.starts_with(p)) || name_ref == -1
         });
 
-        tmp.push((idx, id as u16, label, extra));
-        str_refs.push(to_str_ref(name_ref));
-        str_refs.push(to_str_ref(descr_ref));
-        idx += 1;
-    }
-    let mut tlk =
-        Tlk::read(tlk_bytes, &str_refs).map_err(|err| format!("couldn't read strings: {err}"))?;
-
-    let mut feats = Vec::with_capacity(tmp.len());
-    for (idx, id, label, extra) in tmp {
-        let name = mem::take(&mut tlk.strings[idx * 2]);
-        let descr = mem::take(&mut tlk.strings[idx * 2 + 1]);
+        let name = strings.get_signed(name_ref)?;
+        let descr = strings.get_signed(descr_ref)?;
         let name = if name.is_empty() { label } else { name };
         // so that Flurry and Improved Flurry go after each other instead of strictly alphabetically
         let sorting_name =
             prefix_to_sort_suffix(&name, &["Improved ", "Advanced ", "Knight ", "Master "]);
         let feat = Feat {
-            id,
+            id: id as u16,
             sorting_name,
             name,
             description: (!descr.is_empty()).then_some(descr),
@@ -259,10 +185,8 @@ pub fn read_feats(
     Ok(feats)
 }
 
-pub fn read_classes(twoda: TwoDA, tlk_bytes: &[u8]) -> SResult<Vec<Class>> {
-    let mut tmp = Vec::with_capacity(twoda.0.len());
-    let mut str_refs = Vec::with_capacity(twoda.0.len());
-    let mut idx = 0;
+pub fn read_classes(twoda: TwoDAProjection, strings: &StringResolver) -> SResult<Vec<Class>> {
+    let mut classes = Vec::with_capacity(twoda.0.len());
     for class in twoda.0 {
         let id = *class["_idx"].as_ref().unwrap().int_unwrap();
         let force_user = class["spellgaintable"].is_some();
@@ -276,21 +200,12 @@ pub fn read_classes(twoda: TwoDA, tlk_bytes: &[u8]) -> SResult<Vec<Class>> {
             continue;
         };
 
-        tmp.push((idx, id, force_user, hit_die as u8, force_die as u8));
-        str_refs.push(to_str_ref(name_ref));
-        idx += 1;
-    }
-    let mut tlk =
-        Tlk::read(tlk_bytes, &str_refs).map_err(|err| format!("couldn't read strings: {err}"))?;
-
-    let mut classes = Vec::with_capacity(tmp.len());
-    for (idx, id, force_user, hit_die, force_die) in tmp {
         classes.push(Class {
             id,
             force_user,
-            name: mem::take(&mut tlk.strings[idx]),
-            hit_die,
-            force_die,
+            name: strings.get_signed(name_ref)?,
+            hit_die: hit_die as u8,
+            force_die: force_die as u8,
         });
     }
     classes.sort_unstable_by(|a, b| a.name.cmp(&b.name));
@@ -298,7 +213,7 @@ pub fn read_classes(twoda: TwoDA, tlk_bytes: &[u8]) -> SResult<Vec<Class>> {
     Ok(classes)
 }
 
-pub fn read_appearances(twoda: TwoDA, field: &str) -> Vec<Appearance> {
+pub fn read_appearances(twoda: TwoDAProjection, field: &str) -> Vec<Appearance> {
     let mut appearances = Vec::with_capacity(twoda.0.len());
     for appearance in twoda.0 {
         let id = *appearance["_idx"].clone().unwrap().int_unwrap() as u16;
@@ -312,49 +227,30 @@ pub fn read_appearances(twoda: TwoDA, field: &str) -> Vec<Appearance> {
     appearances
 }
 
-pub fn read_quests(mut journal: Gff, tlk_bytes: &[u8]) -> SResult<Vec<Quest>> {
+pub fn read_quests(mut journal: Gff, strings: &StringResolver) -> SResult<Vec<Quest>> {
     let mut list = journal.take("Categories", Field::list_take)?;
-    let mut tmp = Vec::with_capacity(list.len());
-    let mut str_refs = Vec::with_capacity(list.len() * 10);
+    let mut quests = Vec::with_capacity(list.len());
     for quest in &mut list {
         let id = quest.take("Tag", Field::string_take)?;
-        let name_ref = quest.take("Name", Field::loc_string_take)?.0 as usize;
+        let name_ref = quest.take("Name", Field::loc_string_take)?.0;
         let mut stages_list = quest.take("EntryList", Field::list_take)?;
-        let mut stages = Vec::with_capacity(stages_list.len());
+        let mut stages = BTreeMap::new();
         for stage in &mut stages_list {
             let id = stage.take("ID", Field::dword_take)? as i32;
             let end = stage.take("End", Field::word_take)? != 0;
-            let descr_ref = stage.take("Text", Field::loc_string_take)?.0 as usize;
+            let descr_ref = stage.take("Text", Field::loc_string_take)?.0;
 
-            stages.push((id, end, descr_ref));
-            str_refs.push(descr_ref);
+            let stage = QuestStage {
+                id,
+                end,
+                description: strings.get(descr_ref)?,
+            };
+            stages.insert(id, stage);
         }
-        tmp.push((id.to_lowercase(), name_ref, stages));
-        str_refs.push(name_ref);
-    }
-    let tlk =
-        Tlk::read(tlk_bytes, &str_refs).map_err(|err| format!("couldn't read strings: {err}"))?;
-    let mut map: HashMap<_, _> = str_refs.into_iter().zip(tlk.strings).collect();
-    let mut quests = Vec::with_capacity(tmp.len());
-
-    for (id, name_ref, stages) in tmp {
-        let stages = stages
-            .into_iter()
-            .map(|(id, end, descr_ref)| {
-                (
-                    id,
-                    QuestStage {
-                        id,
-                        end,
-                        description: mem::take(map.get_mut(&descr_ref).unwrap()),
-                    },
-                )
-            })
-            .collect();
 
         quests.push(Quest {
-            id,
-            name: mem::take(map.get_mut(&name_ref).unwrap()),
+            id: id.to_lowercase(),
+            name: strings.get(name_ref)?,
             stages,
         });
     }
@@ -363,7 +259,7 @@ pub fn read_quests(mut journal: Gff, tlk_bytes: &[u8]) -> SResult<Vec<Quest>> {
     Ok(quests)
 }
 
-pub fn read_base_items(items: TwoDA) -> HashMap<i32, BaseItem> {
+pub fn read_base_items(items: TwoDAProjection) -> HashMap<i32, BaseItem> {
     let mut base_items = HashMap::with_capacity(items.0.len());
     for item in items.0 {
         let id = *item["_idx"].as_ref().unwrap().int_unwrap();
@@ -380,6 +276,10 @@ pub fn read_base_items(items: TwoDA) -> HashMap<i32, BaseItem> {
         let Ok(usable_by) = (droid_or_human as u8).try_into() else {
             continue;
         };
+        let item_class = item["itemclass"]
+            .as_ref()
+            .map(|t| t.string_unwrap().clone())
+            .unwrap_or_default();
 
         let slot = match slot {
             0x00200 | 0x00208 => ItemSlot::Implant,
@@ -412,47 +312,35 @@ pub fn read_base_items(items: TwoDA) -> HashMap<i32, BaseItem> {
                 label,
                 usable_by,
                 slot,
+                item_class,
             },
         );
     }
     base_items
 }
 
-pub fn read_items(items: Vec<Gff>, tlk_bytes: &[u8]) -> SResult<Vec<Item>> {
-    let mut tmp = Vec::with_capacity(items.len());
-    let mut str_refs = Vec::with_capacity(items.len() * 2);
+pub fn read_items(
+    items: Vec<Gff>,
+    base_items: &HashMap<i32, BaseItem>,
+    strings: &StringResolver,
+) -> SResult<Vec<Item>> {
+    let mut read = Vec::with_capacity(items.len());
     for item in items {
         let tag = item.get("Tag", Field::string)?;
         let base_item = item.get("BaseItem", Field::int)?;
-        let name_ref = item.get("LocalizedName", Field::loc_string)?.0 as usize;
-        let descr_ref = item.get("DescIdentified", Field::loc_string)?.0 as usize;
+        let name_ref = item.get("LocalizedName", Field::loc_string)?.0;
+        let descr_ref = item.get("DescIdentified", Field::loc_string)?.0;
         let stack_size = item.get("StackSize", Field::word)?;
         let charges = item.get("Charges", Field::byte)?;
         let upgrade_level = item.get("UpgradeLevel", Field::byte).ok();
-
-        tmp.push((
-            tag,
-            base_item,
-            name_ref,
-            descr_ref,
-            stack_size,
-            charges,
-            upgrade_level,
-            item.content,
-        ));
-        str_refs.push(name_ref);
-        str_refs.push(descr_ref);
-    }
-    let tlk =
-        Tlk::read(tlk_bytes, &str_refs).map_err(|err| format!("couldn't read strings: {err}"))?;
-    let mut map: HashMap<_, _> = str_refs.into_iter().zip(tlk.strings).collect();
-    let mut items = Vec::with_capacity(tmp.len());
-
-    for (tag, base_item, name_ref, descr_ref, stack_size, charges, upgrade_level, raw) in tmp {
-        let name = mem::take(map.get_mut(&name_ref).unwrap());
-        let name = prepare_item_name(&name);
-        let descr = mem::take(map.get_mut(&descr_ref).unwrap());
-        items.push(Item {
+        let model_variation = item.get("ModelVariation", Field::byte).unwrap_or(1);
+        let icon = base_items
+            .get(&base_item)
+            .and_then(|base| base.icon(model_variation));
+
+        let name = prepare_item_name(&strings.get(name_ref)?);
+        let descr = strings.get(descr_ref)?;
+        read.push(Item {
             id: tag.to_lowercase(),
             tag,
             base_item,
@@ -461,15 +349,16 @@ pub fn read_items(items: Vec<Gff>, tlk_bytes: &[u8]) -> SResult<Vec<Item>> {
             name: (!name.is_empty()).then_some(name),
             description: (!descr.is_empty()).then_some(descr),
             upgrade_level,
+            icon,
 
-            raw,
+            raw: item.content,
         });
     }
-    items.sort_unstable_by(|a, b| {
+    read.sort_unstable_by(|a, b| {
         a.get_name()
             .to_lowercase()
             .cmp(&b.get_name().to_lowercase())
     });
 
-    Ok(items)
+    Ok(read)
 }
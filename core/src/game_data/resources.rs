@@ -0,0 +1,618 @@
+use crate::{
+    formats::{
+        bif::{self, BifEntry},
+        erf,
+        key::{Key, KeyResRef},
+        rim, FResult, FormatError, ReadResource, ResourceKey, ResourceType,
+    },
+    game_data::read::{fingerprint, io_error, read_game_dirs, GameDirs},
+    util::{
+        fs::{find_file, read_dir_filemap, read_file},
+        parallel::par_map,
+        Game,
+    },
+};
+use ahash::{HashMap, HashMapExt as _};
+use log::warn;
+use memmap2::Mmap;
+use std::{
+    fmt, fs, mem,
+    ops::{Deref, Range},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+const BATCH_SIZE: usize = 64;
+// icons and portraits live here, other packs only have textures for models
+const TEXTURE_PACK: &str = "swpc_tex_gui.erf";
+
+// where a resource was found, in lookup order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Layer {
+    // override dirs of steam workshop mods, TSL only
+    Workshop,
+    Override,
+    // .rim and .mod files in the modules dir
+    Modules,
+    // the GUI texture pack
+    TexturePack,
+    // BIFs listed in chitin.key
+    Chitin,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Workshop => "workshop",
+            Self::Override => "override",
+            Self::Modules => "modules",
+            Self::TexturePack => "texture pack",
+            Self::Chitin => "chitin",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResourceSource {
+    File(PathBuf),
+    // file is relative to the game dir
+    Bif { file: PathBuf, res_idx: u32 },
+    // .rim, .mod/.erf or the texture pack
+    Archive { file: PathBuf, key: ResourceKey },
+}
+
+impl ResourceSource {
+    // for errors, BIF resources don't have names
+    pub fn describe(&self) -> String {
+        match self {
+            Self::File(path) => path.display().to_string(),
+            Self::Bif { file, res_idx } => format!("{} #{res_idx}", file.display()),
+            Self::Archive { file, key } => {
+                format!("{} {}.{}", file.display(), key.0, key.1.to_extension())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resolved {
+    // lowercase
+    pub name: String,
+    pub tp: ResourceType,
+    pub layer: Layer,
+    pub source: ResourceSource,
+}
+
+// BIFs can be hundreds of megabytes while only a few resources are needed from them,
+// so they're mapped instead of read and only the resource table is parsed
+pub struct BifHandle {
+    map: Arc<Mmap>,
+    entries: Vec<BifEntry>,
+}
+
+impl BifHandle {
+    fn open(dir: &Path, file: &Path) -> FResult<Self> {
+        let io_error = |err| io_error(ResourceType::Bif, file, &err);
+        let path = find_file(dir, file).map_err(io_error)?;
+        let bif_file = fs::File::open(path).map_err(io_error)?;
+        // the game doesn't modify its BIFs, mods only add files to the override
+        let map = unsafe { Mmap::map(&bif_file) }.map_err(io_error)?;
+        let entries =
+            bif::read_entries(&map).map_err(|err| err.with_resource(file.display().to_string()))?;
+
+        Ok(Self {
+            map: Arc::new(map),
+            entries,
+        })
+    }
+
+    fn range(&self, file: &Path, idx: usize) -> FResult<Range<usize>> {
+        let entry = self.entries.get(idx).ok_or_else(|| {
+            let count = self.entries.len();
+            FormatError::invalid(
+                ResourceType::Bif,
+                file.display().to_string(),
+                format!("resource {idx} requested, BIF contains {count}"),
+            )
+        })?;
+
+        Ok(entry.offset..entry.offset + entry.size)
+    }
+}
+
+// same for ERFs and RIMs, only the key list is parsed
+pub struct ArchiveHandle {
+    file: PathBuf,
+    map: Arc<Mmap>,
+    entries: HashMap<ResourceKey, Range<usize>>,
+}
+
+impl ArchiveHandle {
+    fn open(file: PathBuf) -> FResult<Self> {
+        let tp = archive_type(&file);
+        let io_error = |err| io_error(tp, &file, &err);
+        let archive_file = fs::File::open(&file).map_err(io_error)?;
+        // like BIFs, these aren't replaced while the game or the editor runs
+        let map = unsafe { Mmap::map(&archive_file) }.map_err(io_error)?;
+        let entries = if tp == ResourceType::Rim {
+            rim::read_entries(&map)
+        } else {
+            erf::read_entries(&map)
+        };
+        let entries = entries
+            .map_err(|err| err.with_resource(file.display().to_string()))?
+            .into_iter()
+            .map(|entry| (entry.key, entry.offset..entry.offset + entry.size))
+            .collect();
+
+        Ok(Self {
+            file,
+            map: Arc::new(map),
+            entries,
+        })
+    }
+
+    fn range(&self, key: &ResourceKey) -> FResult<Range<usize>> {
+        self.entries.get(key).cloned().ok_or_else(|| {
+            let name = format!("{} {}.{}", self.file.display(), key.0, key.1.to_extension());
+            FormatError::missing(key.1, name)
+        })
+    }
+}
+
+// contents of a resource, ones from BIFs and archives point into the mapped file
+pub enum ResourceBytes {
+    Owned(Vec<u8>),
+    Mapped { map: Arc<Mmap>, range: Range<usize> },
+}
+
+impl Deref for ResourceBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Owned(bytes) => bytes,
+            Self::Mapped { map, range } => &map[range.clone()],
+        }
+    }
+}
+
+struct LooseDir {
+    layer: Layer,
+    dir: PathBuf,
+    // lowercase -> real names
+    files: HashMap<String, String>,
+}
+
+// resources read_all takes from the same place in one go
+enum Batch<'r> {
+    File(usize, &'r Path),
+    Bif(&'r Path, Vec<(usize, usize)>),
+    Archive(&'r Path, Vec<(usize, &'r ResourceKey)>),
+}
+
+// finds resources the way the game does: workshop -> override -> modules -> texture pack -> chitin
+pub struct ResourceManager {
+    game: Game,
+    fingerprint: u64,
+    dir: PathBuf,
+    dialog: PathBuf,
+    loose: Vec<LooseDir>,
+    modules: Vec<ArchiveHandle>,
+    texture_pack: Option<ArchiveHandle>,
+    key: Key,
+    // opened the first time anything is taken from them
+    bifs: Mutex<HashMap<PathBuf, Arc<BifHandle>>>,
+}
+
+impl ResourceManager {
+    pub fn new<P: AsRef<Path>>(game: Game, dir: P, steam_dir: Option<P>) -> FResult<Self> {
+        let dirs = read_game_dirs(game, dir, steam_dir)?;
+        let fingerprint = fingerprint(game, &dirs);
+        let GameDirs {
+            dir,
+            files,
+            workshop,
+            override_dir,
+            dialog,
+        } = dirs;
+
+        let workshop = workshop.into_iter().map(|dir| (Layer::Workshop, dir));
+        let override_dir = override_dir.map(|dir| (Layer::Override, dir));
+        let loose = workshop
+            .chain(override_dir)
+            .map(|(layer, dir)| LooseDir {
+                layer,
+                files: read_dir_filemap(&dir).unwrap_or_default(),
+                dir,
+            })
+            .collect();
+
+        let key_path = PathBuf::from_iter([&dir, Path::new("chitin.key")]);
+        let key_bytes = read_file(&dir, "chitin.key")
+            .map_err(|err| io_error(ResourceType::Key, &key_path, &err))?;
+        let mut key = Key::read(&key_bytes, ())
+            .map_err(|err| err.with_resource(key_path.display().to_string()))?;
+        // names in other layers are lowercase already
+        key.resources = mem::take(&mut key.resources)
+            .into_iter()
+            .map(|(k, v)| (ResourceKey::from((k.0.to_lowercase(), k.1)), v))
+            .collect();
+
+        let modules = files
+            .get("modules")
+            .map(|modules_dir| {
+                read_modules_dir(&PathBuf::from_iter([&dir, Path::new(modules_dir)]))
+            })
+            .unwrap_or_default();
+
+        let pack_path = PathBuf::from_iter(["texturepacks", TEXTURE_PACK]);
+        // only thumbnails come from it, everything else can do without
+        let texture_pack = find_file(&dir, &pack_path)
+            .map_err(|err| io_error(ResourceType::Erf, &pack_path, &err))
+            .and_then(ArchiveHandle::open)
+            .map_err(|err| warn!("couldn't read {TEXTURE_PACK}: {err}"))
+            .ok();
+
+        Ok(Self {
+            game,
+            fingerprint,
+            dir,
+            dialog,
+            loose,
+            modules,
+            texture_pack,
+            key,
+            bifs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn game(&self) -> Game {
+        self.game
+    }
+
+    // of the files in the install, taken when the manager is created
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // dialog.tlk, workshop mods can replace it
+    pub fn dialog(&self) -> &Path {
+        &self.dialog
+    }
+
+    fn chitin_source(&self, res_ref: &KeyResRef) -> ResourceSource {
+        ResourceSource::Bif {
+            file: self.key.get_file_path(res_ref.file_idx),
+            res_idx: res_ref.resource_idx,
+        }
+    }
+
+    pub fn resolve(&self, name: &str, tp: ResourceType) -> Option<Resolved> {
+        let name = name.to_lowercase();
+        let resolved = |layer, source| Resolved {
+            name: name.clone(),
+            tp,
+            layer,
+            source,
+        };
+
+        let file_name = format!("{name}.{}", tp.to_extension());
+        for loose in &self.loose {
+            if let Some(real_name) = loose.files.get(&file_name) {
+                let path = PathBuf::from_iter([&loose.dir, Path::new(real_name)]);
+                return Some(resolved(loose.layer, ResourceSource::File(path)));
+            }
+        }
+
+        let key = ResourceKey::from((name.as_str(), tp));
+        for module in &self.modules {
+            if module.entries.contains_key(&key) {
+                let source = ResourceSource::Archive {
+                    file: module.file.clone(),
+                    key,
+                };
+                return Some(resolved(Layer::Modules, source));
+            }
+        }
+
+        if let Some(pack) = self
+            .texture_pack
+            .as_ref()
+            .filter(|p| p.entries.contains_key(&key))
+        {
+            let source = ResourceSource::Archive {
+                file: pack.file.clone(),
+                key,
+            };
+            return Some(resolved(Layer::TexturePack, source));
+        }
+
+        let res_ref = self.key.resources.get(&key)?;
+        Some(resolved(Layer::Chitin, self.chitin_source(res_ref)))
+    }
+
+    // every resource of the type, only the one that wins for each name, sorted by name
+    pub fn list(&self, tp: ResourceType) -> Vec<Resolved> {
+        // going from the lowest priority, a later source replaces the earlier one with the same name
+        let mut found = HashMap::new();
+        for (k, v) in self.key.resources.iter().filter(|(k, _)| k.1 == tp) {
+            found.insert(k.0.clone(), (Layer::Chitin, self.chitin_source(v)));
+        }
+
+        if let Some(pack) = &self.texture_pack {
+            for k in pack.entries.keys().filter(|k| k.1 == tp) {
+                let source = ResourceSource::Archive {
+                    file: pack.file.clone(),
+                    key: k.clone(),
+                };
+                found.insert(k.0.clone(), (Layer::TexturePack, source));
+            }
+        }
+
+        for module in self.modules.iter().rev() {
+            for k in module.entries.keys().filter(|k| k.1 == tp) {
+                let source = ResourceSource::Archive {
+                    file: module.file.clone(),
+                    key: k.clone(),
+                };
+                found.insert(k.0.clone(), (Layer::Modules, source));
+            }
+        }
+
+        let ext = format!(".{}", tp.to_extension());
+        for loose in self.loose.iter().rev() {
+            for (k, v) in &loose.files {
+                let Some(name) = k.strip_suffix(&ext) else {
+                    continue;
+                };
+                let path = PathBuf::from_iter([&loose.dir, Path::new(v)]);
+                found.insert(name.to_owned(), (loose.layer, ResourceSource::File(path)));
+            }
+        }
+
+        let mut list: Vec<_> = found
+            .into_iter()
+            .map(|(name, (layer, source))| Resolved {
+                name,
+                tp,
+                layer,
+                source,
+            })
+            .collect();
+        // so the order doesn't change between runs
+        list.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        list
+    }
+
+    fn bif(&self, file: &Path) -> FResult<Arc<BifHandle>> {
+        let mut bifs = self.bifs.lock().unwrap();
+        if let Some(bif) = bifs.get(file) {
+            return Ok(bif.clone());
+        }
+        let bif = Arc::new(BifHandle::open(&self.dir, file)?);
+        bifs.insert(file.to_owned(), bif.clone());
+
+        Ok(bif)
+    }
+
+    // archives are opened with the manager, a source from another one finds nothing
+    fn archive(&self, file: &Path) -> FResult<&ArchiveHandle> {
+        let mut archives = self.modules.iter().chain(&self.texture_pack);
+        archives
+            .find(|archive| archive.file == file)
+            .ok_or_else(|| FormatError::missing(archive_type(file), file.display().to_string()))
+    }
+
+    pub fn get_bytes(&self, resolved: &Resolved) -> FResult<ResourceBytes> {
+        let bytes = match &resolved.source {
+            ResourceSource::File(path) => {
+                fs::read(path).map_err(|err| io_error(resolved.tp, path, &err))?
+            }
+            ResourceSource::Bif { file, res_idx } => {
+                let bif = self.bif(file)?;
+                let range = bif.range(file, *res_idx as usize)?;
+                return Ok(ResourceBytes::Mapped {
+                    map: bif.map.clone(),
+                    range,
+                });
+            }
+            ResourceSource::Archive { file, key } => {
+                let archive = self.archive(file)?;
+                return Ok(ResourceBytes::Mapped {
+                    map: archive.map.clone(),
+                    range: archive.range(key)?,
+                });
+            }
+        };
+
+        Ok(ResourceBytes::Owned(bytes))
+    }
+
+    pub fn read<'a, T: ReadResource<'a, Arg>, Arg: 'a + Copy>(
+        &self,
+        resolved: &Resolved,
+        arg: Arg,
+    ) -> FResult<T> {
+        let bytes = self.get_bytes(resolved)?;
+        T::read(&bytes, arg).map_err(|err| err.with_resource(resolved.source.describe()))
+    }
+
+    pub fn read_all<'a, T: ReadResource<'a, Arg> + Send, Arg: 'a + Copy + Sync>(
+        &self,
+        resolved: &[Resolved],
+        args: &[Arg],
+    ) -> FResult<Vec<T>> {
+        self.read_all_with_progress(resolved, args, &|_, _| ())
+    }
+
+    // reads on all cores, progress gets the number of resources read so far and the total
+    pub fn read_all_with_progress<'a, T: ReadResource<'a, Arg> + Send, Arg: 'a + Copy + Sync>(
+        &self,
+        resolved: &[Resolved],
+        args: &[Arg],
+        progress: &(dyn Fn(usize, usize) + Sync),
+    ) -> FResult<Vec<T>> {
+        let mut batches = vec![];
+        // accumulating so every BIF and module is opened once
+        let mut in_bif = HashMap::new();
+        let mut in_archives = HashMap::new();
+        for (idx, r) in resolved.iter().enumerate() {
+            match &r.source {
+                ResourceSource::File(path) => {
+                    batches.push(Batch::File(idx, path));
+                }
+                ResourceSource::Bif { file, res_idx } => {
+                    let bif_resources = in_bif.entry(file.as_path()).or_insert_with(Vec::new);
+                    bif_resources.push((idx, *res_idx as usize));
+                }
+                ResourceSource::Archive { file, key } => {
+                    let archive_resources =
+                        in_archives.entry(file.as_path()).or_insert_with(Vec::new);
+                    archive_resources.push((idx, key));
+                }
+            }
+        }
+        // the mapping is shared, so a big BIF or archive can be spread over several threads
+        for (file, res_indices) in in_bif {
+            for chunk in res_indices.chunks(BATCH_SIZE) {
+                batches.push(Batch::Bif(file, chunk.to_vec()));
+            }
+        }
+        for (file, keys) in in_archives {
+            for chunk in keys.chunks(BATCH_SIZE) {
+                batches.push(Batch::Archive(file, chunk.to_vec()));
+            }
+        }
+
+        let total = resolved.len();
+        let done = AtomicUsize::new(0);
+        let read = |idx: usize, bytes: &[u8]| {
+            let resource = T::read(bytes, args[idx])
+                .map_err(|err| err.with_resource(resolved[idx].source.describe()))?;
+            progress(done.fetch_add(1, Ordering::Relaxed) + 1, total);
+            Ok((idx, resource))
+        };
+        let read_batch = |batch: &Batch| -> FResult<Vec<(usize, T)>> {
+            match batch {
+                Batch::File(idx, path) => {
+                    let bytes =
+                        fs::read(path).map_err(|err| io_error(resolved[*idx].tp, path, &err))?;
+                    Ok(vec![read(*idx, &bytes)?])
+                }
+                Batch::Bif(file, res_indices) => {
+                    let bif = self.bif(file)?;
+                    res_indices
+                        .iter()
+                        .map(|(idx, res_idx)| {
+                            let range = bif.range(file, *res_idx)?;
+                            read(*idx, &bif.map[range])
+                        })
+                        .collect()
+                }
+                Batch::Archive(file, keys) => {
+                    let archive = self.archive(file)?;
+                    keys.iter()
+                        .map(|(idx, key)| read(*idx, &archive.map[archive.range(key)?]))
+                        .collect()
+                }
+            }
+        };
+
+        let mut resources: Vec<_> = resolved.iter().map(|_| None).collect();
+        for batch in par_map(&batches, read_batch) {
+            for (idx, resource) in batch? {
+                resources[idx] = Some(resource);
+            }
+        }
+
+        Ok(resources.into_iter().map(Option::unwrap).collect())
+    }
+
+    pub fn get<'a, T: ReadResource<'a, Arg>, Arg: 'a + Copy>(
+        &self,
+        name: &str,
+        tp: ResourceType,
+        arg: Arg,
+    ) -> FResult<T> {
+        let resolved = self
+            .resolve(name, tp)
+            .ok_or_else(|| FormatError::missing(tp, name))?;
+        self.read(&resolved, arg)
+    }
+
+    // fails if any of them is missing
+    pub fn get_by_names<'a, T: ReadResource<'a, Arg> + Send, Arg: 'a + Copy + Sync>(
+        &self,
+        names: &[&str],
+        tp: ResourceType,
+        args: &[Arg],
+    ) -> FResult<Vec<T>> {
+        let resolved = names
+            .iter()
+            .map(|name| {
+                self.resolve(name, tp)
+                    .ok_or_else(|| FormatError::missing(tp, *name))
+            })
+            .collect::<FResult<Vec<_>>>()?;
+        self.read_all(&resolved, args)
+    }
+}
+
+fn archive_type(file: &Path) -> ResourceType {
+    let is_rim = file
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("rim"));
+    if is_rim {
+        ResourceType::Rim
+    } else {
+        ResourceType::Erf
+    }
+}
+
+// all archives in the modules dir, in lookup order
+fn read_modules_dir(dir: &Path) -> Vec<ArchiveHandle> {
+    let Ok(files) = read_dir_filemap(&dir.into()) else {
+        return vec![];
+    };
+    let mut names: Vec<_> = files.keys().collect();
+    names.sort_unstable();
+
+    let mut candidates = Vec::with_capacity(names.len());
+    for name in names {
+        let module = if let Some(module) = name.strip_suffix(".rim") {
+            // _s.rim files only extend the main one
+            let module = module.strip_suffix("_s").unwrap_or(module);
+            // .mod files replace both .rim files
+            if files.contains_key(&format!("{module}.mod")) {
+                continue;
+            }
+            module
+        } else if let Some(module) = name.strip_suffix(".mod") {
+            module
+        } else {
+            continue;
+        };
+        let file = PathBuf::from_iter([dir, Path::new(&files[name])]);
+        candidates.push((module, file));
+    }
+
+    // only the key lists are read, the contents are sliced out of the mapping when needed
+    let opened = par_map(&candidates, |(_, file)| ArchiveHandle::open(file.clone()));
+    let mut archives = Vec::with_capacity(candidates.len());
+    for ((module, _), archive) in candidates.into_iter().zip(opened) {
+        match archive {
+            Ok(archive) => archives.push(archive),
+            Err(err) => warn!("couldn't read module {module}: {err}"),
+        }
+    }
+
+    archives
+}
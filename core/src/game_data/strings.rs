@@ -0,0 +1,87 @@
+use crate::formats::{
+    tlk::{self, TlkHeader},
+    FResult,
+};
+use ahash::HashMap;
+use log::warn;
+use std::sync::Mutex;
+
+// StrRef meaning no string at all
+const NO_STRING: u32 = u32::MAX;
+const MISSING_STRING: &str = "#MISSING STRING#";
+
+struct Table {
+    bytes: Vec<u8>,
+    header: TlkHeader,
+}
+
+impl Table {
+    fn new(bytes: Vec<u8>) -> FResult<Self> {
+        let header = tlk::read_header(&bytes)?;
+        Ok(Self { bytes, header })
+    }
+
+    fn text(&self, str_ref: u32) -> FResult<Option<String>> {
+        tlk::read_text(&self.bytes, &self.header, str_ref)
+    }
+}
+
+// looks StrRefs up in dialog.tlk as they're needed, the header is only read once
+pub struct StringResolver {
+    base: Table,
+    // strings from it replace the ones from the base table, empty ones don't count
+    custom: Option<Table>,
+    cache: Mutex<HashMap<u32, String>>,
+}
+
+impl StringResolver {
+    pub fn new(bytes: Vec<u8>) -> FResult<Self> {
+        Ok(Self {
+            base: Table::new(bytes)?,
+            custom: None,
+            cache: Mutex::default(),
+        })
+    }
+
+    pub fn with_custom(mut self, bytes: Vec<u8>) -> FResult<Self> {
+        self.custom = Some(Table::new(bytes)?);
+        self.cache.get_mut().unwrap().clear();
+        Ok(self)
+    }
+
+    // of the base table, plain strings in resources use its code page too
+    pub fn language(&self) -> u32 {
+        self.base.header.language
+    }
+
+    // can be called from several threads at once
+    pub fn get(&self, str_ref: u32) -> FResult<String> {
+        if str_ref == NO_STRING {
+            return Ok(String::new());
+        }
+        if let Some(text) = self.cache.lock().unwrap().get(&str_ref) {
+            return Ok(text.clone());
+        }
+
+        let custom = match &self.custom {
+            Some(custom) => custom.text(str_ref)?.filter(|text| !text.is_empty()),
+            None => None,
+        };
+        let text = match custom {
+            Some(text) => text,
+            None => self.base.text(str_ref)?.unwrap_or_else(|| {
+                let count = self.base.header.count;
+                warn!("TLK contains {count} strings but index {str_ref} is requested");
+                MISSING_STRING.to_owned()
+            }),
+        };
+        self.cache.lock().unwrap().insert(str_ref, text.clone());
+
+        Ok(text)
+    }
+
+    // for 2DA cells and GFF fields, where -1 means no string
+    pub fn get_signed(&self, str_ref: i32) -> FResult<String> {
+        self.get(u32::try_from(str_ref).unwrap_or(NO_STRING))
+    }
+}
@@ -0,0 +1,35 @@
+use crate::{
+    formats::{tpc::Tpc, ResourceType},
+    game_data::resources::ResourceManager,
+};
+use log::warn;
+use std::fmt;
+
+// icons and portraits, found like any other resource with the GUI texture pack as a layer
+pub struct Textures {
+    resources: ResourceManager,
+}
+
+impl Textures {
+    // takes the manager the game data was read with, it's the same install
+    pub fn new(resources: ResourceManager) -> Self {
+        Self { resources }
+    }
+
+    // missing and unreadable textures are both None, the latter get logged
+    pub fn get(&self, name: &str) -> Option<Tpc> {
+        let resolved = self.resources.resolve(name, ResourceType::Tpc)?;
+        let tpc = self.resources.read(&resolved, ());
+
+        tpc.map_err(|err| warn!("{err}")).ok()
+    }
+}
+
+impl fmt::Debug for Textures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Textures")
+            .field("game", &self.resources.game())
+            .field("dir", &self.resources.dir())
+            .finish()
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{formats::FileHead, util::ESResult};
+use crate::{
+    formats::{ErrorKind, FileHead, ReadResult},
+    util::{encoding::CodePage, ESResult},
+};
 use bytemuck::{
     bytes_of, cast_slice, try_cast_slice, try_pod_read_unaligned, AnyBitPattern, NoUninit, Pod,
 };
@@ -32,6 +35,7 @@ pub fn num_to_dword<T: AnyBitPattern + NoUninit>(num: T) -> u32 {
     u32::from_ne_bytes(buf)
 }
 
+// for labels, resrefs and such, text meant for the player has to go through a CodePage
 pub fn bytes_to_string(bytes: Vec<u8>) -> String {
     String::from_utf8(bytes).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
 }
@@ -82,10 +86,13 @@ pub trait SeekExt: Seek {
 impl<T: Seek> SeekExt for T {}
 
 pub fn nullpad_string(mut str: String, to_len: usize) -> String {
-    let len = str.len();
-    if len < to_len {
-        str.push_str(&"\0".repeat(to_len - len));
+    // fixed size fields can't hold more, cut on a char boundary so the result stays valid
+    if str.len() > to_len {
+        let end = (0..=to_len).rev().find(|&i| str.is_char_boundary(i)).unwrap();
+        str.truncate(end);
     }
+    let len = str.len();
+    str.push_str(&"\0".repeat(to_len - len));
     str
 }
 
@@ -93,13 +100,45 @@ pub type Cursor<'a> = std::io::Cursor<&'a [u8]>;
 
 pub fn take_bytes<'a>(input: &mut Cursor<'a>, len: usize) -> Option<&'a [u8]> {
     let pos = input.position() as usize;
-    let end = pos + len;
+    // the position stays where it was on failure so errors can point at it
+    let bytes = input.get_ref().get(pos..pos.checked_add(len)?)?;
     input.consume(len);
-    input.get_ref().get(pos..end)
+    Some(bytes)
+}
+
+// counts in headers can't be trusted, never reserve more entries than the rest of the input could hold
+pub fn capacity(input: &Cursor, count: usize, entry_size: usize) -> usize {
+    let remaining = input
+        .get_ref()
+        .len()
+        .saturating_sub(input.position() as usize);
+    count.min(remaining / entry_size.max(1))
+}
+
+// Several entries can point at the same data, so a small file could make a reader copy out gigabytes.
+// Readers spend from this whenever they copy something out of the input.
+pub struct Budget {
+    left: usize,
+}
+
+impl Budget {
+    // generous enough for files that share strings between entries on purpose
+    const INPUT_MULTIPLE: usize = 32;
+
+    pub fn new(input_len: usize) -> Self {
+        Self {
+            left: input_len.saturating_mul(Self::INPUT_MULTIPLE),
+        }
+    }
+
+    pub fn spend(&mut self, len: usize) -> Option<()> {
+        self.left = self.left.checked_sub(len)?;
+        Some(())
+    }
 }
 
 pub fn take_slice<'a, T: Pod>(input: &'a mut Cursor, len: usize) -> Option<Cow<'a, [T]>> {
-    let byte_len = len * size_of::<T>();
+    let byte_len = len.checked_mul(size_of::<T>())?;
     let bytes = take_bytes(input, byte_len)?;
     if let Ok(slice) = try_cast_slice(bytes) {
         Some(Cow::Borrowed(slice))
@@ -138,6 +177,19 @@ pub fn take_string_sized<P: AnyBitPattern + TryInto<usize>>(input: &mut Cursor)
     Some(bytes_to_string(bytes.into_owned()))
 }
 
+pub fn take_text(input: &mut Cursor, len: usize, code_page: CodePage) -> Option<String> {
+    let bytes = take_bytes(input, len)?;
+    Some(code_page.decode(bytes))
+}
+
+pub fn take_text_sized<P: AnyBitPattern + TryInto<usize>>(
+    input: &mut Cursor,
+    code_page: CodePage,
+) -> Option<String> {
+    let bytes = take_slice_sized::<P, u8>(input)?;
+    Some(code_page.decode(&bytes))
+}
+
 pub fn take_string_until(input: &mut Cursor, terminator: u8) -> Option<String> {
     let mut buf = vec![];
     input.read_until(terminator, &mut buf).ok()?;
@@ -162,9 +214,19 @@ pub fn take_string_trimmed(input: &mut Cursor, max_len: usize) -> Option<String>
     Some(bytes_to_string(buf))
 }
 
-pub fn take_head(input: &mut Cursor) -> Option<FileHead> {
-    let head = take_string(input, 8)?;
-    let (tp, version) = head.split_at(4);
+pub fn take_head(input: &mut Cursor) -> ReadResult<FileHead> {
+    // split before decoding, a garbage head may not be valid UTF-8
+    let (tp, version) = take_bytes(input, 8)
+        .ok_or_else(|| ErrorKind::Truncated("file head".to_owned()))?
+        .split_at(4);
+    let head = FileHead {
+        tp: bytes_to_string(tp.to_vec()),
+        version: bytes_to_string(version.to_vec()),
+    };
+    // anything else couldn't be written back as the same 8 bytes
+    if !head.tp.is_ascii() || !head.version.is_ascii() {
+        return Err(ErrorKind::Header(head));
+    }
 
-    Some((tp, version).into())
+    Ok(head)
 }
@@ -0,0 +1,100 @@
+use encoding_rs::{EncoderResult, Encoding};
+use macros::EnumList;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// the game doesn't use unicode, all text is in the code page of its language
+#[derive(EnumList, Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CodePage {
+    // Polish
+    Windows1250,
+    // Russian, only fan translations use it so there's no language ID for it
+    Windows1251,
+    // English, French, German, Italian, Spanish
+    #[default]
+    Windows1252,
+    // Japanese
+    ShiftJis,
+    // Simplified Chinese
+    Gbk,
+    // Korean
+    Windows949,
+    // Traditional Chinese
+    Big5,
+}
+
+impl CodePage {
+    // Tlk.language, same IDs as LocStrings use
+    pub fn from_language(language: u32) -> Self {
+        match language {
+            5 => Self::Windows1250,
+            128 => Self::Windows949,
+            129 => Self::Big5,
+            130 => Self::Gbk,
+            131 => Self::ShiftJis,
+            _ => Self::Windows1252,
+        }
+    }
+
+    // the names encoding_rs and the web use, case doesn't matter
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::LIST
+            .into_iter()
+            .find(|code_page| code_page.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(self) -> &'static str {
+        self.encoding().name()
+    }
+
+    // LocString IDs are language * 2 + gender. Translations that reuse the IDs of the western
+    // languages don't have to use their code page, so those strings are in the one of the file.
+    pub fn from_string_id(id: u32, file_code_page: Self) -> Self {
+        match Self::from_language(id / 2) {
+            Self::Windows1252 => file_code_page,
+            code_page => code_page,
+        }
+    }
+
+    fn encoding(self) -> &'static Encoding {
+        match self {
+            Self::Windows1250 => encoding_rs::WINDOWS_1250,
+            Self::Windows1251 => encoding_rs::WINDOWS_1251,
+            Self::Windows1252 => encoding_rs::WINDOWS_1252,
+            Self::ShiftJis => encoding_rs::SHIFT_JIS,
+            Self::Gbk => encoding_rs::GBK,
+            Self::Windows949 => encoding_rs::EUC_KR,
+            Self::Big5 => encoding_rs::BIG5,
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let (str, _) = self.encoding().decode_without_bom_handling(bytes);
+        str.into_owned()
+    }
+
+    // characters missing from the code page are replaced with '?'
+    pub fn encode(self, mut str: &str) -> Vec<u8> {
+        let mut encoder = self.encoding().new_encoder();
+        let mut bytes = Vec::with_capacity(str.len());
+
+        loop {
+            let (result, read) =
+                encoder.encode_from_utf8_to_vec_without_replacement(str, &mut bytes, true);
+            str = &str[read..];
+            match result {
+                EncoderResult::InputEmpty => break,
+                EncoderResult::OutputFull => bytes.reserve(str.len().max(8)),
+                EncoderResult::Unmappable(_) => bytes.push(b'?'),
+            }
+        }
+
+        bytes
+    }
+}
+
+impl fmt::Display for CodePage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -6,7 +6,8 @@ use std::{
     time::SystemTime,
 };
 
-pub fn read_file(dir: impl AsRef<Path>, file: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+// file names on disk can have any case, this finds the real path
+pub fn find_file(dir: impl AsRef<Path>, file: impl AsRef<Path>) -> io::Result<PathBuf> {
     let mut path = dir.as_ref().to_path_buf();
     for part in file.as_ref() {
         let map = read_dir_filemap(&path)?;
@@ -18,7 +19,11 @@ pub fn read_file(dir: impl AsRef<Path>, file: impl AsRef<Path>) -> io::Result<Ve
         path.push(real_name);
     }
 
-    fs::read(path)
+    Ok(path)
+}
+
+pub fn read_file(dir: impl AsRef<Path>, file: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    fs::read(find_file(dir, file)?)
 }
 
 // map of lowercase -> real filenames in a dir
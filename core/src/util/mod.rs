@@ -2,7 +2,9 @@ use macros::EnumList;
 use std::fmt::{self, Display};
 
 pub mod bytes;
+pub mod encoding;
 pub mod fs;
+pub mod parallel;
 
 pub type SResult<T> = Result<T, String>;
 pub type ESResult = SResult<()>;
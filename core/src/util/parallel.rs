@@ -0,0 +1,56 @@
+use std::{
+    num::NonZeroUsize,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+// there are no threads on the web, everything runs in place there
+const THREADS_AVAILABLE: bool = !cfg!(target_arch = "wasm32");
+
+// runs both at the same time, b on the current thread
+pub fn join<A: Send, B: Send>(
+    a: impl FnOnce() -> A + Send,
+    b: impl FnOnce() -> B + Send,
+) -> (A, B) {
+    if !THREADS_AVAILABLE {
+        return (a(), b());
+    }
+    thread::scope(|s| {
+        let a = s.spawn(a);
+        let b = b();
+        (a.join().unwrap(), b)
+    })
+}
+
+// maps items on all cores, results are in the order of items
+pub fn par_map<I: Sync, O: Send>(items: &[I], f: impl Fn(&I) -> O + Sync) -> Vec<O> {
+    let workers = thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(items.len());
+    if !THREADS_AVAILABLE || workers <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    // items can take very different time, so workers take the next one when they're done
+    let next = AtomicUsize::new(0);
+    let work = || {
+        let mut done = vec![];
+        loop {
+            let idx = next.fetch_add(1, Ordering::Relaxed);
+            let Some(item) = items.get(idx) else {
+                return done;
+            };
+            done.push((idx, f(item)));
+        }
+    };
+    let mut results: Vec<_> = thread::scope(|s| {
+        let handles: Vec<_> = (0..workers).map(|_| s.spawn(work)).collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    results.sort_unstable_by_key(|(idx, _)| *idx);
+
+    results.into_iter().map(|(_, result)| result).collect()
+}
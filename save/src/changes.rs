@@ -0,0 +1,634 @@
+use crate::{snapshot::Snapshot, Character, Class, Item, PartyMember, Save};
+use ahash::{HashMap, HashSet};
+use core::{Appearance, Data as _, GameDataMapped};
+use std::cmp::Ordering;
+
+const ATTRIBUTES: [&str; 6] = ["Str", "Dex", "Con", "Int", "Wis", "Cha"];
+const SKILLS: [&str; 8] = [
+    "Computer Use",
+    "Demolitions",
+    "Stealth",
+    "Awareness",
+    "Persuade",
+    "Repair",
+    "Security",
+    "Treat Injury",
+];
+// in the order of EQUIPMENT_SLOT_IDS
+const EQUIPMENT_SLOTS: [&str; 12] = [
+    "Implant",
+    "Head",
+    "Gloves",
+    "Arm Left",
+    "Armor",
+    "Arm Right",
+    "Belt",
+    "Main Hand",
+    "Offhand",
+    "Main Hand 2",
+    "Offhand 2",
+    "Hidden slot",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum CharTarget {
+    Name,
+    Hp,
+    HpMax,
+    Fp,
+    FpMax,
+    Min1Hp,
+    GoodEvil,
+    Experience,
+    Attribute(usize),
+    Skill(usize),
+    Feat(u16),
+    Class(i32),
+    Power(i32, u16), // class ID, power ID
+    Gender,
+    Portrait,
+    Appearance,
+    Soundset,
+    Equipment(usize),
+}
+
+// what gets copied back from the original on revert
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    SaveName,
+    CheatUsed,
+    Credits,
+    PartyXp,
+    Components,
+    Chemicals,
+    Party,
+    Available(usize),
+    Selectable(usize),
+    Influence(usize),
+    Global(String),
+    Character(usize, CharTarget),
+    Items(String), // tag, all the items with it are reverted together
+    Quest(String),
+    DoorLocked(usize),
+    DoorOpenState(usize),
+}
+
+// a single difference from the save as it was last read or written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub description: String,
+    target: Target,
+}
+
+struct Differ<'a> {
+    original: &'a Snapshot,
+    save: &'a Save,
+    data: &'a GameDataMapped,
+    changes: Vec<Change>,
+}
+
+// a change for each of the listed fields that differs
+macro_rules! compare {
+    ($self:ident, $old:ident, $new:ident, $prefix:expr, [$($field:ident $label:literal $target:expr,)+]) => {$(
+        if $old.$field != $new.$field {
+            let (old, new) = (&$old.$field, &$new.$field);
+            $self.push($target, format!("{}{} {old} → {new}", $prefix, $label));
+        }
+    )+};
+}
+
+impl<'a> Differ<'a> {
+    fn push(&mut self, target: Target, description: String) {
+        self.changes.push(Change {
+            description,
+            target,
+        });
+    }
+
+    fn npc_name(&self, idx: usize) -> String {
+        let char = self.save.characters.iter().find(|char| char.idx == idx);
+        char.map_or_else(|| format!("NPC {idx}"), |char| char.get_name().to_owned())
+    }
+
+    fn party_names(&self, members: &[PartyMember]) -> String {
+        if members.is_empty() {
+            return "nobody".to_owned();
+        }
+        let names: Vec<_> = members.iter().map(|m| self.npc_name(m.idx)).collect();
+        names.join(", ")
+    }
+
+    fn general(&mut self) {
+        let (original, save) = (self.original, self.save);
+
+        let (old, new) = (&original.nfo, &save.nfo);
+        compare!(self, old, new, "", [
+            save_name "Save name" Target::SaveName,
+            cheat_used "Cheats used" Target::CheatUsed,
+        ]);
+
+        let (old, new) = (&original.party_table, &save.party_table);
+        compare!(self, old, new, "", [
+            credits "Credits" Target::Credits,
+            party_xp "Party XP" Target::PartyXp,
+        ]);
+        if let (Some(old), Some(new)) = (old.components, new.components) {
+            if old != new {
+                self.push(Target::Components, format!("Components {old} → {new}"));
+            }
+        }
+        if let (Some(old), Some(new)) = (old.chemicals, new.chemicals) {
+            if old != new {
+                self.push(Target::Chemicals, format!("Chemicals {old} → {new}"));
+            }
+        }
+        if old.members != new.members {
+            let old_names = self.party_names(&old.members);
+            let new_names = self.party_names(&new.members);
+            self.push(Target::Party, format!("Party {old_names} → {new_names}"));
+        }
+
+        let members = old.available_members.iter().zip(&new.available_members);
+        for (idx, (old, new)) in members.enumerate() {
+            if old != new {
+                let prefix = format!("{}: ", self.npc_name(idx));
+                compare!(self, old, new, prefix, [
+                    available "Available" Target::Available(idx),
+                    selectable "Selectable" Target::Selectable(idx),
+                ]);
+            }
+        }
+        if let (Some(old), Some(new)) = (&old.influence, &new.influence) {
+            for (idx, (old, new)) in old.iter().zip(new).enumerate() {
+                if old != new {
+                    let name = self.npc_name(idx);
+                    let description = format!("{name}: Influence {old} → {new}");
+                    self.push(Target::Influence(idx), description);
+                }
+            }
+        }
+    }
+
+    fn globals(&mut self) {
+        let (original, save) = (self.original, self.save);
+        if original.globals == save.globals {
+            return;
+        }
+
+        for (idx, new) in save.globals.iter().enumerate() {
+            // the list rarely changes, looking it up by name is only a fallback
+            let old = original.globals.get(idx).filter(|old| old.name == new.name);
+            let old = old.or_else(|| original.globals.iter().find(|old| old.name == new.name));
+            let (name, value) = (&new.name, &new.value);
+            match old {
+                Some(old) if old.value != *value => {
+                    let description = format!("global {name}: {} → {value}", old.value);
+                    self.push(Target::Global(name.clone()), description);
+                }
+                Some(_) => {}
+                // added by a JSON import
+                None => {
+                    let description = format!("+ global {name} = {value}");
+                    self.push(Target::Global(name.clone()), description);
+                }
+            }
+        }
+        for old in &original.globals {
+            if !save.globals.iter().any(|new| new.name == old.name) {
+                let name = &old.name;
+                self.push(Target::Global(name.clone()), format!("- global {name}"));
+            }
+        }
+    }
+
+    fn characters(&mut self) {
+        let (original, save) = (self.original, self.save);
+        for new in &save.characters {
+            let Some(old) = original.character(new.idx) else {
+                continue;
+            };
+            if old != new {
+                self.character(old, new);
+            }
+        }
+    }
+
+    fn appearance_name(list: &HashMap<u16, Appearance>, id: u16) -> String {
+        list.get(&id)
+            .map_or_else(|| id.to_string(), |a| a.get_name().to_owned())
+    }
+
+    fn character(&mut self, old: &Character, new: &Character) {
+        let data = self.data;
+        let name = new.get_name();
+        let prefix = format!("{name}: ");
+        let target = |target| Target::Character(new.idx, target);
+
+        compare!(self, old, new, prefix, [
+            name "Name" target(CharTarget::Name),
+            hp "HP" target(CharTarget::Hp),
+            hp_max "Max HP" target(CharTarget::HpMax),
+            fp "FP" target(CharTarget::Fp),
+            fp_max "Max FP" target(CharTarget::FpMax),
+            min_1_hp "Min 1 HP" target(CharTarget::Min1Hp),
+            good_evil "Alignment" target(CharTarget::GoodEvil),
+            experience "XP" target(CharTarget::Experience),
+            gender "Gender" target(CharTarget::Gender),
+        ]);
+
+        for (idx, attribute) in ATTRIBUTES.into_iter().enumerate() {
+            let (old, new) = (old.attributes[idx], new.attributes[idx]);
+            if old != new {
+                let description = format!("{name}: {attribute} {old} → {new}");
+                self.push(target(CharTarget::Attribute(idx)), description);
+            }
+        }
+        for (idx, skill) in SKILLS.into_iter().enumerate() {
+            let (old, new) = (old.skills[idx], new.skills[idx]);
+            if old != new {
+                let description = format!("{name}: {skill} {old} → {new}");
+                self.push(target(CharTarget::Skill(idx)), description);
+            }
+        }
+
+        let feat_name = |id: &u16| data.feats.get(id).map_or("unknown", |f| f.get_name());
+        for id in new.feats.iter().filter(|id| !old.feats.contains(id)) {
+            let description = format!("{name}: + feat {}", feat_name(id));
+            self.push(target(CharTarget::Feat(*id)), description);
+        }
+        for id in old.feats.iter().filter(|id| !new.feats.contains(id)) {
+            let description = format!("{name}: - feat {}", feat_name(id));
+            self.push(target(CharTarget::Feat(*id)), description);
+        }
+
+        let class_name = |id: &i32| data.classes.get(id).map_or("unknown", |c| c.get_name());
+        let power_name = |id: &u16| data.powers.get(id).map_or("unknown", |p| p.get_name());
+        for class in &new.classes {
+            let class_name = class_name(&class.id);
+            let Some(old) = old.classes.iter().find(|c| c.id == class.id) else {
+                let description = format!("{name}: + class {class_name}");
+                self.push(target(CharTarget::Class(class.id)), description);
+                continue;
+            };
+            let (old_level, new_level) = (old.level, class.level);
+            if old_level != new_level {
+                let description = format!("{name}: {class_name} level {old_level} → {new_level}");
+                self.push(target(CharTarget::Class(class.id)), description);
+            }
+
+            let old_powers = old.powers.as_deref().unwrap_or_default();
+            let new_powers = class.powers.as_deref().unwrap_or_default();
+            for id in new_powers.iter().filter(|id| !old_powers.contains(id)) {
+                let description = format!("{name}: + power {}", power_name(id));
+                self.push(target(CharTarget::Power(class.id, *id)), description);
+            }
+            for id in old_powers.iter().filter(|id| !new_powers.contains(id)) {
+                let description = format!("{name}: - power {}", power_name(id));
+                self.push(target(CharTarget::Power(class.id, *id)), description);
+            }
+        }
+        let removed = |class: &&Class| new.classes.iter().all(|c| c.id != class.id);
+        for class in old.classes.iter().filter(removed) {
+            let description = format!("{name}: - class {}", class_name(&class.id));
+            self.push(target(CharTarget::Class(class.id)), description);
+        }
+
+        let appearances = [
+            ("Portrait", CharTarget::Portrait, &data.portraits),
+            ("Appearance", CharTarget::Appearance, &data.appearances),
+            ("Soundset", CharTarget::Soundset, &data.soundsets),
+        ];
+        let ids = [
+            (old.portrait, new.portrait),
+            (old.appearance, new.appearance),
+            (old.soundset, new.soundset),
+        ];
+        for ((label, char_target, list), (old, new)) in appearances.into_iter().zip(ids) {
+            if old != new {
+                let old = Self::appearance_name(list, old);
+                let new = Self::appearance_name(list, new);
+                let description = format!("{name}: {label} {old} → {new}");
+                self.push(target(char_target), description);
+            }
+        }
+
+        let tag = Self::item_tag;
+        for (idx, slot) in EQUIPMENT_SLOTS.into_iter().enumerate() {
+            let (old, new) = (old.equipment[idx].as_ref(), new.equipment[idx].as_ref());
+            if old == new {
+                continue;
+            }
+            let description = if tag(old) == tag(new) {
+                format!("{name}: {slot} {} changed", tag(new))
+            } else {
+                format!("{name}: {slot} {} → {}", tag(old), tag(new))
+            };
+            self.push(target(CharTarget::Equipment(idx)), description);
+        }
+    }
+
+    fn item_tag(item: Option<&Item>) -> &str {
+        item.as_ref().map_or("nothing", |item| item.tag.as_str())
+    }
+
+    fn item_fields(old: &Item, new: &Item) -> Vec<String> {
+        let numbers: [(&str, u32, u32); 4] = [
+            ("Stack size", old.stack_size.into(), new.stack_size.into()),
+            ("Charges", old.charges.into(), new.charges.into()),
+            (
+                "Max charges",
+                old.max_charges.into(),
+                new.max_charges.into(),
+            ),
+            ("Upgrades", old.upgrades, new.upgrades),
+        ];
+        let mut fields: Vec<_> = numbers
+            .into_iter()
+            .filter(|(_, old, new)| old != new)
+            .map(|(label, old, new)| format!("{label} {old} → {new}"))
+            .collect();
+        if old.upgrade_slots != new.upgrade_slots {
+            fields.push("Upgrade slots".to_owned());
+        }
+
+        fields
+    }
+
+    fn inventory(&mut self) {
+        let (original, save) = (self.original, self.save);
+        if original.inventory == save.inventory {
+            return;
+        }
+
+        // grouped by tag, items don't have anything better to tell them apart
+        let mut seen = HashSet::default();
+        let tags = original.inventory.iter().chain(&save.inventory);
+        let tags: Vec<_> = tags
+            .map(|i| &i.tag)
+            .filter(|tag| seen.insert(*tag))
+            .collect();
+        for tag in tags {
+            let with_tag = |items: &'a [Item]| -> Vec<&'a Item> {
+                items.iter().filter(|i| i.tag == *tag).collect()
+            };
+            let (old, new) = (with_tag(&original.inventory), with_tag(&save.inventory));
+            if old == new {
+                continue;
+            }
+
+            let count = |n: usize| {
+                if n > 1 {
+                    format!(" ×{n}")
+                } else {
+                    String::new()
+                }
+            };
+            let description = match new.len().cmp(&old.len()) {
+                Ordering::Greater => format!("+ item {tag}{}", count(new.len() - old.len())),
+                Ordering::Less => format!("- item {tag}{}", count(old.len() - new.len())),
+                Ordering::Equal => {
+                    let fields: Vec<_> = old
+                        .iter()
+                        .zip(&new)
+                        .flat_map(|(old, new)| Self::item_fields(old, new))
+                        .collect();
+                    if fields.is_empty() {
+                        format!("item {tag} changed")
+                    } else {
+                        format!("item {tag}: {}", fields.join(", "))
+                    }
+                }
+            };
+            self.push(Target::Items(tag.clone()), description);
+        }
+    }
+
+    fn quests(&mut self) {
+        let (original, save) = (self.original, self.save);
+        let (old, new) = (&original.party_table.journal, &save.party_table.journal);
+        if old == new {
+            return;
+        }
+
+        let data = self.data;
+        let quest_name =
+            |id: &'a String| -> &'a str { data.quests.get(id).map_or(id, |quest| &quest.name) };
+        for entry in new {
+            let target = Target::Quest(entry.id.clone());
+            let name = quest_name(&entry.id);
+            match old.iter().find(|old| old.id == entry.id) {
+                None => self.push(target, format!("+ quest {name} (stage {})", entry.stage)),
+                Some(old) if old.stage != entry.stage => {
+                    let (old, new) = (old.stage, entry.stage);
+                    self.push(target, format!("quest {name}: Stage {old} → {new}"));
+                }
+                Some(old) if old != entry => self.push(target, format!("quest {name} changed")),
+                Some(_) => {}
+            }
+        }
+        for entry in old.iter().filter(|old| new.iter().all(|e| e.id != old.id)) {
+            let name = quest_name(&entry.id);
+            self.push(Target::Quest(entry.id.clone()), format!("- quest {name}"));
+        }
+    }
+
+    fn doors(&mut self) {
+        let (original, save) = (self.original, self.save);
+        let (Some(old), Some(new)) = (&original.doors, &save.doors) else {
+            return;
+        };
+
+        for (idx, (old, new)) in old.iter().zip(new).enumerate() {
+            if old != new {
+                let prefix = format!("door {}: ", new.tag);
+                compare!(self, old, new, prefix, [
+                    locked "Locked" Target::DoorLocked(idx),
+                    open_state "Open state" Target::DoorOpenState(idx),
+                ]);
+            }
+        }
+    }
+}
+
+// everything that was edited since the save was last read or written, in the order of the tabs
+pub fn changes(save: &Save, data: &GameDataMapped) -> Vec<Change> {
+    let mut differ = Differ {
+        original: &save.inner.original,
+        save,
+        data,
+        changes: vec![],
+    };
+    if !save.is_modified() {
+        return differ.changes;
+    }
+
+    differ.general();
+    differ.globals();
+    differ.characters();
+    differ.inventory();
+    differ.quests();
+    differ.doors();
+
+    differ.changes
+}
+
+// puts the element at idx of the original list back after the closest one before it that's still
+// there, so that reverting every change gives the original order and nothing counts as modified
+fn restore<T: Clone>(list: &mut Vec<T>, original: &[T], idx: usize, same: impl Fn(&T, &T) -> bool) {
+    let pos = original[..idx]
+        .iter()
+        .rev()
+        .find_map(|prev| list.iter().rposition(|item| same(item, prev)))
+        .map_or(0, |pos| pos + 1);
+    list.insert(pos, original[idx].clone());
+}
+
+impl Change {
+    // only what the change covers goes back, the rest of the edits stay
+    pub fn revert(&self, save: &mut Save) {
+        let original = &save.inner.original;
+        let (old, new) = (&original.party_table, &mut save.party_table);
+
+        match &self.target {
+            Target::SaveName => save.nfo.save_name.clone_from(&original.nfo.save_name),
+            Target::CheatUsed => save.nfo.cheat_used = original.nfo.cheat_used,
+            Target::Credits => new.credits = old.credits,
+            Target::PartyXp => new.party_xp = old.party_xp,
+            Target::Components => new.components = old.components,
+            Target::Chemicals => new.chemicals = old.chemicals,
+            Target::Party => new.members.clone_from(&old.members),
+            Target::Available(idx) => {
+                if let (Some(old), Some(new)) = (
+                    old.available_members.get(*idx),
+                    new.available_members.get_mut(*idx),
+                ) {
+                    new.available = old.available;
+                }
+            }
+            Target::Selectable(idx) => {
+                if let (Some(old), Some(new)) = (
+                    old.available_members.get(*idx),
+                    new.available_members.get_mut(*idx),
+                ) {
+                    new.selectable = old.selectable;
+                }
+            }
+            Target::Influence(idx) => {
+                let old = old.influence.as_ref().and_then(|list| list.get(*idx));
+                let new = new.influence.as_mut().and_then(|list| list.get_mut(*idx));
+                if let (Some(old), Some(new)) = (old, new) {
+                    *new = *old;
+                }
+            }
+            Target::Global(name) => {
+                let old_idx = original.globals.iter().position(|g| g.name == *name);
+                let globals = &mut save.globals;
+                match (old_idx, globals.iter().position(|g| g.name == *name)) {
+                    (Some(idx), Some(pos)) => {
+                        globals[pos].value = original.globals[idx].value.clone();
+                    }
+                    (Some(idx), None) => {
+                        restore(globals, &original.globals, idx, |a, b| a.name == b.name);
+                    }
+                    (None, Some(pos)) => {
+                        globals.remove(pos);
+                    }
+                    (None, None) => {}
+                }
+            }
+            Target::Character(idx, target) => {
+                let old = original.character(*idx);
+                let new = save.characters.iter_mut().find(|char| char.idx == *idx);
+                if let (Some(old), Some(new)) = (old, new) {
+                    Self::revert_character(old, new, target);
+                }
+            }
+            Target::Items(tag) => {
+                save.inventory.retain(|item| item.tag != *tag);
+                for (idx, item) in original.inventory.iter().enumerate() {
+                    if item.tag == *tag {
+                        restore(&mut save.inventory, &original.inventory, idx, |a, b| a == b);
+                    }
+                }
+            }
+            Target::Quest(id) => {
+                let old_idx = old.journal.iter().position(|entry| entry.id == *id);
+                let journal = &mut new.journal;
+                match (old_idx, journal.iter().position(|entry| entry.id == *id)) {
+                    (Some(idx), Some(pos)) => journal[pos] = old.journal[idx].clone(),
+                    (Some(idx), None) => restore(journal, &old.journal, idx, |a, b| a.id == b.id),
+                    (None, Some(pos)) => {
+                        journal.remove(pos);
+                    }
+                    (None, None) => {}
+                }
+            }
+            Target::DoorLocked(idx) | Target::DoorOpenState(idx) => {
+                let old = original.doors.as_ref().and_then(|doors| doors.get(*idx));
+                let new = save.doors.as_mut().and_then(|doors| doors.get_mut(*idx));
+                let (Some(old), Some(new)) = (old, new) else {
+                    return;
+                };
+                if matches!(self.target, Target::DoorLocked(_)) {
+                    new.locked = old.locked;
+                } else {
+                    new.open_state = old.open_state;
+                }
+            }
+        }
+    }
+
+    fn revert_character(old: &Character, new: &mut Character, target: &CharTarget) {
+        match target {
+            CharTarget::Name => new.name.clone_from(&old.name),
+            CharTarget::Hp => new.hp = old.hp,
+            CharTarget::HpMax => new.hp_max = old.hp_max,
+            CharTarget::Fp => new.fp = old.fp,
+            CharTarget::FpMax => new.fp_max = old.fp_max,
+            CharTarget::Min1Hp => new.min_1_hp = old.min_1_hp,
+            CharTarget::GoodEvil => new.good_evil = old.good_evil,
+            CharTarget::Experience => new.experience = old.experience,
+            CharTarget::Attribute(idx) => new.attributes[*idx] = old.attributes[*idx],
+            CharTarget::Skill(idx) => new.skills[*idx] = old.skills[*idx],
+            CharTarget::Feat(id) => match old.feats.iter().position(|feat| feat == id) {
+                None => new.feats.retain(|feat| feat != id),
+                Some(idx) if !new.feats.contains(id) => {
+                    restore(&mut new.feats, &old.feats, idx, |a, b| a == b);
+                }
+                Some(_) => {}
+            },
+            CharTarget::Class(id) => {
+                let old_idx = old.classes.iter().position(|class| class.id == *id);
+                let classes = &mut new.classes;
+                match (old_idx, classes.iter().position(|class| class.id == *id)) {
+                    (Some(idx), Some(pos)) => classes[pos].level = old.classes[idx].level,
+                    (Some(idx), None) => restore(classes, &old.classes, idx, |a, b| a.id == b.id),
+                    (None, Some(pos)) => {
+                        classes.remove(pos);
+                    }
+                    (None, None) => {}
+                }
+            }
+            CharTarget::Power(class, id) => {
+                let old = old.classes.iter().find(|c| c.id == *class);
+                let old_powers = old.and_then(|c| c.powers.as_deref()).unwrap_or_default();
+                let Some(new) = new.classes.iter_mut().find(|c| c.id == *class) else {
+                    return;
+                };
+                if let Some(idx) = old_powers.iter().position(|power| power == id) {
+                    let powers = new.powers.get_or_insert_with(Vec::new);
+                    if !powers.contains(id) {
+                        restore(powers, old_powers, idx, |a, b| a == b);
+                    }
+                } else if let Some(powers) = &mut new.powers {
+                    powers.retain(|power| power != id);
+                }
+            }
+            CharTarget::Gender => new.gender = old.gender,
+            CharTarget::Portrait => new.portrait = old.portrait,
+            CharTarget::Appearance => new.appearance = old.appearance,
+            CharTarget::Soundset => new.soundset = old.soundset,
+            CharTarget::Equipment(idx) => new.equipment[*idx].clone_from(&old.equipment[*idx]),
+        }
+    }
+}
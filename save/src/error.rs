@@ -0,0 +1,74 @@
+use core::FormatError;
+use std::{fmt, io};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveError {
+    // a file of the save couldn't be read or written
+    Io { file: String, reason: String },
+    // a file every save has isn't there
+    Missing(String),
+    // one of the files or a resource inside them couldn't be parsed
+    Format(FormatError),
+    // the files are readable but their contents aren't what a save should have
+    Invalid(String),
+}
+
+pub type SaveResult<T> = Result<T, SaveError>;
+
+impl SaveError {
+    pub(crate) fn io(file: impl Into<String>, err: &io::Error) -> Self {
+        let file = file.into();
+        if err.kind() == io::ErrorKind::NotFound {
+            return Self::Missing(file);
+        }
+        Self::Io {
+            file,
+            reason: err.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { file, reason } => write!(f, "couldn't access {file}: {reason}"),
+            Self::Missing(file) => write!(f, "couldn't find {file}"),
+            Self::Format(err) => write!(f, "{err}"),
+            Self::Invalid(reason) => write!(f, "invalid save: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Format(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FormatError> for SaveError {
+    fn from(err: FormatError) -> Self {
+        Self::Format(err)
+    }
+}
+
+// the model is read with the plain string errors of Struct::get and friends
+impl From<String> for SaveError {
+    fn from(reason: String) -> Self {
+        Self::Invalid(reason)
+    }
+}
+
+impl From<&str> for SaveError {
+    fn from(reason: &str) -> Self {
+        Self::Invalid(reason.to_owned())
+    }
+}
+
+impl From<SaveError> for String {
+    fn from(err: SaveError) -> Self {
+        err.to_string()
+    }
+}
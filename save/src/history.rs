@@ -0,0 +1,76 @@
+use crate::{snapshot::Snapshot, Save};
+use std::mem;
+
+// steps back that are kept, each one is a copy of everything that can be edited
+const HISTORY_LIMIT: usize = 100;
+
+// undo and redo for any change to the model, a step is whatever changed between two records
+pub struct History {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    current: Snapshot,
+}
+
+impl History {
+    pub fn new(save: &Save) -> Self {
+        Self {
+            undo: vec![],
+            redo: vec![],
+            current: Snapshot::new(save),
+        }
+    }
+
+    // makes a step if the save has changed since the last one, true if it did
+    pub fn record(&mut self, save: &Save) -> bool {
+        if self.current.matches(save) {
+            return false;
+        }
+        let previous = mem::replace(&mut self.current, Snapshot::new(save));
+        self.undo.push(previous);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+
+        true
+    }
+
+    // for changes that aren't edits, e.g. writing the save fills in some of the raw data
+    pub fn rebase(&mut self, save: &Save) {
+        self.current = Snapshot::new(save);
+    }
+
+    pub fn undo(&mut self, save: &mut Save) -> bool {
+        // edits that haven't been recorded yet are undone first
+        self.record(save);
+        let Some(snapshot) = self.undo.pop() else {
+            return false;
+        };
+        let current = mem::replace(&mut self.current, snapshot.clone());
+        self.redo.push(current);
+        snapshot.restore(save);
+
+        true
+    }
+
+    pub fn redo(&mut self, save: &mut Save) -> bool {
+        // a new edit makes the steps ahead of it unreachable
+        self.record(save);
+        let Some(snapshot) = self.redo.pop() else {
+            return false;
+        };
+        let current = mem::replace(&mut self.current, snapshot.clone());
+        self.undo.push(current);
+        snapshot.restore(save);
+
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
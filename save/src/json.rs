@@ -0,0 +1,611 @@
+use crate::{
+    update::Updater, AvailablePartyMember, Character, Class, Door, Gender, Global, GlobalValue,
+    Item, JournalEntry, PartyMember, Save, EQUIPMENT_SLOT_IDS,
+};
+use ahash::{HashMap, RandomState};
+use core::{
+    util::{ESResult, SResult},
+    GameDataMapped,
+};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// The domain model of a save as JSON, without any of the GFF layout behind it:
+//
+// {
+//   "format": 1,
+//   "game": 1,
+//   "nfo": { "save_name": "...", "cheat_used": false, ... },
+//   "party_table": { "credits": 100, "journal": [{ "id": "...", "stage": 10 }], ... },
+//   "globals": { "K_SWG_HELENA": 1, "K_KOR_TOMB": true },
+//   "characters": [{ "tag": "...", "feats": [1, 2], "equipment": [null, { "tag": "..." }, ...] }],
+//   "inventory": [{ "tag": "g_w_lghtsbr01", "stack_size": 1 }],
+//   "doors": [{ "tag": "...", "locked": true, "open_state": 0 }]
+// }
+//
+// Exporting writes everything, but every field except the format and the keys below is optional
+// when applying, a missing one leaves the save as is. That way a file can be a full snapshot or a
+// "recipe" that only sets a couple of things on any save.
+// - game: 1 or 2, applying to a save from the other game fails
+// - nfo: pc_name, area_name, last_module and time_played are informational and aren't applied,
+//   cheat_used also covers the party table flag
+// - party_table: journal, members, available_members and influence replace the whole list,
+//   journal entries without date and time continue from the previous one,
+//   available_members has to have as many entries as the save
+// - globals: booleans and numbers from 0 to 255, only the listed ones are changed, new ones are added
+// - characters: matched by tag in order, the listed ones are changed, classes and feats are
+//   replaced, gender is a name (Male, Female, Both, Other, None), equipment always has 12 slots in
+//   the order
+//   implant, head, gloves, left arm, armor, right arm, belt, main hand, offhand,
+//   main hand 2, offhand 2 and a hidden slot
+// - inventory: replaces the whole inventory
+// - items: matched by tag with the items already in the save to keep what the model doesn't cover,
+//   the rest come from the game data by template resref or tag,
+//   name, description and base_item are informational
+// - doors: only for the current area, matched by tag
+
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonSave {
+    format: u32,
+    game: Option<u8>,
+    nfo: Option<JsonNfo>,
+    party_table: Option<JsonPartyTable>,
+    globals: Option<IndexMap<String, JsonGlobal, RandomState>>,
+    characters: Option<Vec<JsonCharacter>>,
+    inventory: Option<Vec<JsonItem>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    doors: Option<Vec<JsonDoor>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonNfo {
+    save_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pc_name: Option<String>,
+    area_name: Option<String>,
+    last_module: Option<String>,
+    cheat_used: Option<bool>,
+    time_played: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonPartyTable {
+    credits: Option<u32>,
+    party_xp: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    components: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chemicals: Option<u32>,
+    journal: Option<Vec<JsonJournalEntry>>,
+    members: Option<Vec<JsonPartyMember>>,
+    available_members: Option<Vec<JsonAvailablePartyMember>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    influence: Option<Vec<i32>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonJournalEntry {
+    id: String,
+    stage: i32,
+    date: Option<u32>,
+    time: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonPartyMember {
+    idx: usize,
+    leader: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonAvailablePartyMember {
+    available: bool,
+    selectable: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JsonGlobal {
+    Boolean(bool),
+    Number(u8),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonCharacter {
+    tag: String,
+    name: Option<String>,
+    hp: Option<i16>,
+    hp_max: Option<i16>,
+    fp: Option<i16>,
+    fp_max: Option<i16>,
+    min_1_hp: Option<bool>,
+    good_evil: Option<u8>,
+    experience: Option<u32>,
+    attributes: Option<[u8; 6]>,
+    skills: Option<[u8; 8]>,
+    feats: Option<Vec<u16>>,
+    classes: Option<Vec<JsonClass>>,
+    gender: Option<String>,
+    portrait: Option<u16>,
+    appearance: Option<u16>,
+    soundset: Option<u16>,
+    equipment: Option<Vec<Option<JsonItem>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonClass {
+    id: i32,
+    level: i16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    powers: Option<Vec<u16>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonItem {
+    tag: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    base_item: Option<i32>,
+    stack_size: Option<u16>,
+    charges: Option<u8>,
+    max_charges: Option<u8>,
+    new: Option<bool>,
+    upgrades: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    upgrade_slots: Option<[i32; 6]>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JsonDoor {
+    tag: String,
+    locked: Option<bool>,
+    open_state: Option<u8>,
+}
+
+// copies the fields that are present in the JSON, the names have to match
+macro_rules! apply_fields {
+    ($json:expr, $target:expr, [$($field:ident),+ $(,)?]) => {
+        $(if let Some(value) = $json.$field {
+            $target.$field = value;
+        })+
+    };
+}
+
+// items that are already in the save go first, the rest are made from the game data
+struct ItemPool<'a> {
+    existing: HashMap<String, VecDeque<Item>>,
+    data: &'a GameDataMapped,
+}
+
+impl<'a> ItemPool<'a> {
+    fn new(save: &Save, data: &'a GameDataMapped) -> Self {
+        let mut existing: HashMap<_, VecDeque<_>> = HashMap::default();
+        // same order as the export, so applying it gives every item its own data back
+        let equipped = save
+            .characters
+            .iter()
+            .flat_map(|char| char.equipment.iter().flatten());
+        for item in save.inventory.iter().chain(equipped) {
+            existing
+                .entry(item.tag.to_lowercase())
+                .or_default()
+                .push_back(item.clone());
+        }
+
+        Self { existing, data }
+    }
+
+    fn take(&mut self, json: &JsonItem) -> SResult<Item> {
+        let tag = json.tag.to_lowercase();
+        let mut item = if let Some(item) = self.existing.get_mut(&tag).and_then(VecDeque::pop_front)
+        {
+            item
+        } else {
+            let items = &self.data.items;
+            // resref first, it's unique unlike the tag, same as the CLI's add-item
+            items
+                .get(&tag)
+                .or_else(|| {
+                    items
+                        .values()
+                        .find(|item| item.tag.eq_ignore_ascii_case(&tag))
+                })
+                .map(Item::from)
+                .ok_or_else(|| format!("item {} isn't in the save or the game data", json.tag))?
+        };
+        apply_fields!(
+            json,
+            item,
+            [stack_size, charges, max_charges, new, upgrades]
+        );
+        if let Some(slots) = json.upgrade_slots {
+            if item.upgrade_slots.is_none() {
+                return Err(format!("item {} doesn't have upgrade slots", json.tag));
+            }
+            item.upgrade_slots = Some(slots);
+        }
+
+        Ok(item)
+    }
+}
+
+impl JsonItem {
+    fn new(item: &Item) -> Self {
+        Self {
+            tag: item.tag.clone(),
+            name: item.name.clone(),
+            description: item.description.clone(),
+            base_item: Some(item.base_item),
+            stack_size: Some(item.stack_size),
+            charges: Some(item.charges),
+            max_charges: Some(item.max_charges),
+            new: Some(item.new),
+            upgrades: Some(item.upgrades),
+            upgrade_slots: item.upgrade_slots,
+        }
+    }
+}
+
+impl JsonCharacter {
+    fn new(char: &Character) -> Self {
+        let classes = char
+            .classes
+            .iter()
+            .map(|class| JsonClass {
+                id: class.id,
+                level: class.level,
+                powers: class.powers.clone(),
+            })
+            .collect();
+        let equipment = char
+            .equipment
+            .iter()
+            .map(|item| item.as_ref().map(JsonItem::new))
+            .collect();
+
+        Self {
+            tag: char.tag.clone(),
+            name: Some(char.name.clone()),
+            hp: Some(char.hp),
+            hp_max: Some(char.hp_max),
+            fp: Some(char.fp),
+            fp_max: Some(char.fp_max),
+            min_1_hp: Some(char.min_1_hp),
+            good_evil: Some(char.good_evil),
+            experience: Some(char.experience),
+            attributes: Some(char.attributes),
+            skills: Some(char.skills),
+            feats: Some(char.feats.clone()),
+            classes: Some(classes),
+            gender: Some(char.gender.to_str().to_owned()),
+            portrait: Some(char.portrait),
+            appearance: Some(char.appearance),
+            soundset: Some(char.soundset),
+            equipment: Some(equipment),
+        }
+    }
+
+    fn apply(self, char: &mut Character, items: &mut ItemPool) -> ESResult {
+        if let Some(gender) = &self.gender {
+            char.gender = Gender::LIST
+                .into_iter()
+                .find(|g| g.to_str().eq_ignore_ascii_case(gender))
+                .ok_or_else(|| format!("invalid gender {gender}"))?;
+        }
+        if let Some(classes) = self.classes {
+            char.classes = classes
+                .into_iter()
+                .map(|class| Class {
+                    id: class.id,
+                    level: class.level,
+                    powers: class.powers,
+                })
+                .collect();
+        }
+        if let Some(equipment) = self.equipment {
+            if equipment.len() != EQUIPMENT_SLOT_IDS.len() {
+                return Err(format!(
+                    "{} has {} equipment slots instead of {}",
+                    self.tag,
+                    equipment.len(),
+                    EQUIPMENT_SLOT_IDS.len()
+                ));
+            }
+            for (slot, item) in char.equipment.iter_mut().zip(equipment) {
+                *slot = item.as_ref().map(|item| items.take(item)).transpose()?;
+            }
+        }
+        apply_fields!(
+            self,
+            char,
+            [
+                name, hp, hp_max, fp, fp_max, min_1_hp, good_evil, experience, attributes, skills,
+                feats, portrait, appearance, soundset,
+            ]
+        );
+
+        Ok(())
+    }
+}
+
+impl JsonPartyTable {
+    fn apply(self, save: &mut Save) -> ESResult {
+        let pt = &mut save.party_table;
+        if let Some(journal) = self.journal {
+            pt.journal.clear();
+            for entry in journal {
+                let last = pt.journal.last();
+                let entry = JournalEntry {
+                    id: entry.id,
+                    stage: entry.stage,
+                    date: entry.date.or(last.map(|e| e.date)).unwrap_or(0),
+                    time: entry.time.or(last.map(|e| e.time + 1)).unwrap_or(0),
+                };
+                pt.journal.push(entry);
+            }
+        }
+        if let Some(available) = self.available_members {
+            if available.len() != pt.available_members.len() {
+                return Err(format!(
+                    "the save has {} available party members, got {}",
+                    pt.available_members.len(),
+                    available.len()
+                ));
+            }
+            pt.available_members = available
+                .into_iter()
+                .map(|m| AvailablePartyMember {
+                    available: m.available,
+                    selectable: m.selectable,
+                })
+                .collect();
+        }
+        if let Some(members) = self.members {
+            if let Some(m) = members.iter().find(|m| m.idx >= pt.available_members.len()) {
+                return Err(format!("invalid party member {}", m.idx));
+            }
+            pt.members = members
+                .into_iter()
+                .map(|m| PartyMember {
+                    idx: m.idx,
+                    leader: m.leader,
+                })
+                .collect();
+        }
+
+        // K1 doesn't have these
+        let k2_only = [
+            (
+                "influence",
+                self.influence.is_some(),
+                pt.influence.is_some(),
+            ),
+            (
+                "components",
+                self.components.is_some(),
+                pt.components.is_some(),
+            ),
+            (
+                "chemicals",
+                self.chemicals.is_some(),
+                pt.chemicals.is_some(),
+            ),
+        ];
+        if let Some((name, ..)) = k2_only.iter().find(|(_, json, save)| *json && !save) {
+            return Err(format!("the save doesn't have {name}"));
+        }
+        if let Some(influence) = self.influence {
+            pt.influence = Some(influence);
+        }
+        if let Some(components) = self.components {
+            pt.components = Some(components);
+        }
+        if let Some(chemicals) = self.chemicals {
+            pt.chemicals = Some(chemicals);
+        }
+        apply_fields!(self, pt, [credits, party_xp]);
+
+        Ok(())
+    }
+}
+
+impl JsonSave {
+    fn new(save: &Save) -> Self {
+        let nfo = &save.nfo;
+        let pt = &save.party_table;
+
+        let journal = pt
+            .journal
+            .iter()
+            .map(|e| JsonJournalEntry {
+                id: e.id.clone(),
+                stage: e.stage,
+                date: Some(e.date),
+                time: Some(e.time),
+            })
+            .collect();
+        let members = pt
+            .members
+            .iter()
+            .map(|m| JsonPartyMember {
+                idx: m.idx,
+                leader: m.leader,
+            })
+            .collect();
+        let available_members = pt
+            .available_members
+            .iter()
+            .map(|m| JsonAvailablePartyMember {
+                available: m.available,
+                selectable: m.selectable,
+            })
+            .collect();
+        let globals = save
+            .globals
+            .iter()
+            .map(|g| {
+                let value = match g.value {
+                    GlobalValue::Boolean(value) => JsonGlobal::Boolean(value),
+                    GlobalValue::Number(value) => JsonGlobal::Number(value),
+                };
+                (g.name.clone(), value)
+            })
+            .collect();
+        let doors = save.doors.as_ref().map(|doors| {
+            doors
+                .iter()
+                .map(|d| JsonDoor {
+                    tag: d.tag.clone(),
+                    locked: Some(d.locked),
+                    open_state: Some(d.open_state),
+                })
+                .collect()
+        });
+
+        Self {
+            format: FORMAT_VERSION,
+            game: Some(save.game as u8 + 1),
+            nfo: Some(JsonNfo {
+                save_name: Some(nfo.save_name.clone()),
+                pc_name: nfo.pc_name.clone(),
+                area_name: Some(nfo.area_name.clone()),
+                last_module: Some(nfo.last_module.clone()),
+                cheat_used: Some(nfo.cheat_used),
+                time_played: Some(nfo.time_played),
+            }),
+            party_table: Some(JsonPartyTable {
+                credits: Some(pt.credits),
+                party_xp: Some(pt.party_xp),
+                components: pt.components,
+                chemicals: pt.chemicals,
+                journal: Some(journal),
+                members: Some(members),
+                available_members: Some(available_members),
+                influence: pt.influence.clone(),
+            }),
+            globals: Some(globals),
+            characters: Some(save.characters.iter().map(JsonCharacter::new).collect()),
+            inventory: Some(save.inventory.iter().map(JsonItem::new).collect()),
+            doors,
+        }
+    }
+
+    fn apply(self, save: &mut Save, data: &GameDataMapped) -> ESResult {
+        if self.format != FORMAT_VERSION {
+            return Err(format!(
+                "unsupported format {}, expected {FORMAT_VERSION}",
+                self.format
+            ));
+        }
+        if let Some(game) = self.game.filter(|game| *game != save.game as u8 + 1) {
+            return Err(format!(
+                "made for KotOR {game}, the save is from KotOR {}",
+                save.game
+            ));
+        }
+        let mut items = ItemPool::new(save, data);
+
+        if let Some(nfo) = self.nfo {
+            if let Some(cheat_used) = nfo.cheat_used {
+                save.nfo.cheat_used = cheat_used;
+                save.party_table.cheat_used = cheat_used;
+            }
+            apply_fields!(nfo, save.nfo, [save_name]);
+        }
+        if let Some(pt) = self.party_table {
+            pt.apply(save)?;
+        }
+        for (name, value) in self.globals.into_iter().flatten() {
+            let value = match value {
+                JsonGlobal::Boolean(value) => GlobalValue::Boolean(value),
+                JsonGlobal::Number(value) => GlobalValue::Number(value),
+            };
+            if let Some(global) = save.globals.iter_mut().find(|g| g.name == name) {
+                global.value = value;
+            } else {
+                save.globals.push(Global { name, value });
+            }
+        }
+        save.globals
+            .sort_unstable_by_key(|global| global.name.to_lowercase());
+
+        // before the characters, the pool has the inventory first
+        if let Some(inventory) = self.inventory {
+            save.inventory = inventory
+                .iter()
+                .map(|item| items.take(item))
+                .collect::<SResult<_>>()?;
+        }
+        let chars = &mut save.characters;
+        let mut used = vec![false; chars.len()];
+        for json in self.characters.into_iter().flatten() {
+            // party members from mods can share a tag, they go in order like the doors
+            let idx = (0..chars.len())
+                .find(|idx| !used[*idx] && chars[*idx].tag.eq_ignore_ascii_case(&json.tag))
+                .ok_or_else(|| format!("no character with tag {}", json.tag))?;
+            used[idx] = true;
+            json.apply(&mut chars[idx], &mut items)?;
+        }
+        if let Some(doors) = self.doors {
+            apply_doors(save.doors.as_mut(), doors)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn apply_doors(save_doors: Option<&mut Vec<Door>>, doors: Vec<JsonDoor>) -> ESResult {
+    let save_doors = save_doors.ok_or("the save doesn't have doors")?;
+    let mut used = vec![false; save_doors.len()];
+    for json in doors {
+        // several doors can share a tag, they go in order
+        let idx = (0..save_doors.len())
+            .find(|idx| !used[*idx] && save_doors[*idx].tag.eq_ignore_ascii_case(&json.tag))
+            .ok_or_else(|| format!("no door with tag {}", json.tag))?;
+        used[idx] = true;
+
+        let door = &mut save_doors[idx];
+        if let Some(open_state) = json.open_state {
+            if open_state > 2 {
+                return Err(format!("invalid open state {open_state} for {}", json.tag));
+            }
+            door.open_state = open_state;
+        }
+        apply_fields!(json, door, [locked]);
+    }
+
+    Ok(())
+}
+
+pub fn to_json(save: &Save) -> SResult<String> {
+    serde_json::to_string_pretty(&JsonSave::new(save))
+        .map_err(|err| format!("Save::to_json| {err}"))
+}
+
+// goes through Updater, nothing is changed when applying fails half way
+pub fn apply_json(save: &mut Save, json: &str, data: &GameDataMapped) -> ESResult {
+    let json: JsonSave =
+        serde_json::from_str(json).map_err(|err| format!("Save::apply_json| {err}"))?;
+    let mut new = save.clone();
+    json.apply(&mut new, data)
+        .map_err(|err| format!("Save::apply_json| {err}"))?;
+    Updater::new(&mut new, data).update();
+    *save = new;
+
+    Ok(())
+}
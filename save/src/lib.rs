@@ -0,0 +1,788 @@
+use crate::{snapshot::Snapshot, update::Updater};
+use ahash::HashMap;
+use core::{
+    erf::{self, Erf},
+    gff::{self, Gff, Struct},
+    util::{
+        encoding::CodePage,
+        fs::{read_dir_filemap, read_file},
+        Game,
+    },
+    Data, DataDescr, GameDataMapped, Item as DItem, ReadResource as _, ResourceKey,
+};
+use macros::{EnumFromInt, EnumList, EnumToInt, EnumToString};
+use std::{
+    collections::VecDeque,
+    fmt, fs,
+    io::{self, Cursor, Write as _},
+    path::{Path, PathBuf},
+};
+
+mod changes;
+mod error;
+mod history;
+mod json;
+mod read;
+mod snapshot;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
+mod update;
+mod util;
+pub use changes::*;
+pub use error::*;
+pub use history::History;
+pub use json::*;
+pub use util::{calc_hp_fp_offset, find_pc_name};
+
+const GLOBALS_TYPES: &[&str] = &["Number", "Boolean"];
+const NPC_RESOURCE_PREFIX: &str = "availnpc";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartyMember {
+    pub idx: usize,
+    pub leader: bool,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvailablePartyMember {
+    pub available: bool,
+    pub selectable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    pub id: String,
+    pub stage: i32,
+    pub date: u32,
+    pub time: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartyTable {
+    pub journal: Vec<JournalEntry>,
+    pub cheat_used: bool,
+    pub credits: u32,
+    pub members: Vec<PartyMember>,
+    pub available_members: Vec<AvailablePartyMember>,
+    pub influence: Option<Vec<i32>>,
+    pub party_xp: i32,
+    pub components: Option<u32>,
+    pub chemicals: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlobalValue {
+    Boolean(bool),
+    Number(u8),
+}
+
+impl fmt::Display for GlobalValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Boolean(value) => write!(f, "{value}"),
+            Self::Number(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: String,
+    pub value: GlobalValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Nfo {
+    pub save_name: String,
+    pub pc_name: Option<String>,
+    pub area_name: String,
+    pub last_module: String,
+    pub cheat_used: bool,
+    pub time_played: u32,
+}
+
+#[derive(EnumFromInt, EnumToInt, EnumToString, EnumList, Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum Gender {
+    Male = 0,
+    Female = 1,
+    Both = 2,
+    Other = 3,
+    None = 4,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Class {
+    pub id: i32,
+    pub level: i16,
+    pub powers: Option<Vec<u16>>, // IDs
+}
+
+const EQUIPMENT_SLOT_IDS: [u32; 12] = [
+    0x00200, // Implant
+    0x00001, // Head
+    0x00008, // Gloves
+    0x00080, // Arm Left
+    0x00002, // Armor
+    0x00100, // Arm Right
+    0x00400, // Belt
+    0x00010, // Main Hand
+    0x00020, // Offhand
+    0x40000, // Main Hand 2
+    0x80000, // Offhand 2
+    0x20000, // idk, some hidden slot
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Character {
+    pub idx: usize,
+    pub name: String,
+    pub name_ref: u32,
+    pub tag: String,
+    pub hp: i16,
+    pub hp_max: i16,
+    pub fp: i16,
+    pub fp_max: i16,
+    pub min_1_hp: bool,
+    pub good_evil: u8,
+    pub experience: u32,
+    pub attributes: [u8; 6], // STR, DEX, CON, INT, WIS, CHA
+    pub skills: [u8; 8],     // ranks in order of the skill menu
+    pub feats: Vec<u16>,     // IDs
+    pub classes: Vec<Class>,
+    pub gender: Gender,
+    pub portrait: u16,
+    pub appearance: u16,
+    pub soundset: u16,
+    pub equipment: Box<[Option<Item>; 12]>,
+
+    raw: Struct,
+}
+
+impl Character {
+    pub fn get_name(&self) -> &str {
+        if self.name.is_empty() {
+            &self.tag
+        } else {
+            &self.name
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub tag: String,
+    pub base_item: i32,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub stack_size: u16,
+    pub max_charges: u8,
+    pub charges: u8,
+    pub new: bool,
+    pub upgrades: u32,                   // in K1
+    pub upgrade_slots: Option<[i32; 6]>, // in K2
+
+    pub raw: Struct,
+}
+
+impl Data<String> for Item {
+    fn get_id(&self) -> &String {
+        &self.tag
+    }
+
+    fn get_name(&self) -> &str {
+        if let Some(name) = &self.name {
+            name
+        } else {
+            &self.tag
+        }
+    }
+}
+
+impl From<&DItem> for Item {
+    fn from(item: &DItem) -> Self {
+        Self {
+            tag: item.tag.clone(),
+            base_item: item.base_item,
+            name: item.name.clone(),
+            description: item.description.clone(),
+            stack_size: item.stack_size,
+            max_charges: item.charges,
+            charges: item.charges,
+            new: true,
+            upgrades: 0,
+            upgrade_slots: item.upgrade_level.map(|_| [-1; 6]),
+
+            raw: item.raw.clone(),
+        }
+    }
+}
+
+impl DataDescr for Item {
+    fn get_description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Door {
+    pub tag: String,
+    pub locked: bool,
+    pub open_state: u8,
+
+    raw: Struct,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SaveInternals {
+    nfo: Gff,
+    globals: Gff,
+    party_table: Gff,
+    erf: Erf,
+    pifo: Option<Gff>,
+
+    git_key: Option<ResourceKey>,
+    use_pifo: bool,
+    // of all the text in the save, it's decided by the language of the game and can't be detected
+    code_page: CodePage,
+    // the model as it was last read or written, what the changes are made against
+    original: Snapshot,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Save {
+    pub id: u64,
+    pub game: Game,
+    pub globals: Vec<Global>,
+    pub nfo: Nfo,
+    pub party_table: PartyTable,
+    // screen.tga as is, autosaves don't have it
+    pub image: Option<Vec<u8>>,
+    pub characters: Vec<Character>,
+    pub inventory: Vec<Item>,
+    pub doors: Option<Vec<Door>>,
+
+    inner: SaveInternals,
+}
+
+// there isn't much point to printing the image and internals
+impl fmt::Debug for Save {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Save")
+            .field("game", &self.game)
+            .field("globals", &self.globals)
+            .field("nfo", &self.nfo)
+            .field("party_table", &self.party_table)
+            .finish_non_exhaustive()
+    }
+}
+
+const GFFS: &[(bool, &str)] = &[
+    (true, "savenfo.res"),
+    (true, "globalvars.res"),
+    (true, "partytable.res"),
+    (false, "pifo.ifo"),
+];
+const ERF_NAME: &str = "savegame.sav";
+const IMAGE_NAME: &str = "screen.tga";
+
+impl Save {
+    fn read(
+        mut gffs: VecDeque<Gff>,
+        erf: Erf,
+        image: Option<Vec<u8>>,
+        code_page: CodePage,
+    ) -> SaveResult<Save> {
+        let reader = read::Reader::new(
+            gffs.pop_front().unwrap(),
+            gffs.pop_front().unwrap(),
+            gffs.pop_front().unwrap(),
+            erf,
+            gffs.pop_front(),
+            image,
+            code_page,
+        );
+
+        reader.into_save()
+    }
+
+    pub fn read_from_directory(path: &str, code_page: CodePage) -> SaveResult<Self> {
+        // ERF
+        let erf_bytes = read_file(path, ERF_NAME).map_err(|err| SaveError::io(ERF_NAME, &err))?;
+        let erf = Erf::read(&erf_bytes, ()).map_err(|err| err.with_resource(ERF_NAME))?;
+
+        // GFFs
+        let mut gffs = VecDeque::with_capacity(GFFS.len());
+        for (required, name) in GFFS {
+            match read_file(path, name) {
+                Ok(file) => {
+                    let gff =
+                        Gff::read(&file, code_page).map_err(|err| err.with_resource(*name))?;
+                    gffs.push_back(gff);
+                }
+                Err(err) => {
+                    if *required {
+                        return Err(SaveError::io(*name, &err));
+                    }
+                }
+            }
+        }
+        // autosaves don't have screenshots
+        let image = read_file(path, IMAGE_NAME).ok();
+
+        Self::read(gffs, erf, image, code_page)
+    }
+
+    pub fn save_to_directory(
+        path: &str,
+        save: &mut Save,
+        data: &GameDataMapped,
+        code_page: CodePage,
+    ) -> SaveResult<()> {
+        Updater::new(save, data).with_code_page(code_page).update();
+        let file_names = read_dir_filemap(&path.into()).map_err(|err| SaveError::io(path, &err))?;
+        let backup_path = PathBuf::from_iter([path, "backup.zip"]);
+        let backup_handle = &mut fs::File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(backup_path)
+            .map_err(|err| SaveError::io("backup.zip", &err))?;
+        let mut backup = zip::ZipWriter::new(backup_handle);
+
+        for ((_, name), gff) in GFFS.iter().zip([
+            Some(&save.inner.nfo),
+            Some(&save.inner.globals),
+            Some(&save.inner.party_table),
+            save.inner.pifo.as_ref(),
+        ]) {
+            let Some(gff) = gff else {
+                continue;
+            };
+            let gff_name = file_names.get(*name).map_or(*name, |n| n.as_str());
+            let full_path = PathBuf::from_iter([path, gff_name]);
+            add_zip_file(&full_path, &mut backup).ok();
+
+            let bytes = gff::write(Gff {
+                code_page,
+                ..gff.clone()
+            });
+            fs::write(full_path, &bytes).map_err(|err| SaveError::io(*name, &err))?;
+        }
+
+        let erf_name = file_names.get(ERF_NAME).map_or(ERF_NAME, |n| n.as_str());
+        let full_path = PathBuf::from_iter([path, erf_name]);
+        add_zip_file(&full_path, &mut backup).ok();
+        // the game dates every save it writes
+        save.inner.erf.set_build_date();
+        let bytes = erf::write(save.inner.erf.clone());
+
+        fs::write(full_path, bytes).map_err(|err| SaveError::io(ERF_NAME, &err))?;
+        save.inner.original = Snapshot::new(save);
+
+        Ok(())
+    }
+
+    // file names are expected to be lowercase
+    pub fn read_from_files(
+        files: &HashMap<String, Vec<u8>>,
+        code_page: CodePage,
+    ) -> SaveResult<Save> {
+        // ERF
+        let erf_bytes = files
+            .get(ERF_NAME)
+            .ok_or_else(|| SaveError::Missing(ERF_NAME.to_owned()))?;
+        let erf = Erf::read(erf_bytes, ()).map_err(|err| err.with_resource(ERF_NAME))?;
+
+        // GFFs
+        let mut gffs = VecDeque::with_capacity(GFFS.len());
+        for (required, name) in GFFS {
+            let Some(bytes) = files.get(*name) else {
+                if *required {
+                    return Err(SaveError::Missing((*name).to_owned()));
+                }
+                continue;
+            };
+
+            gffs.push_back(Gff::read(bytes, code_page).map_err(|err| err.with_resource(*name))?);
+        }
+        let image = files.get(IMAGE_NAME).cloned();
+
+        Self::read(gffs, erf, image, code_page)
+    }
+
+    pub fn save_to_zip(save: &mut Self, data: &GameDataMapped, code_page: CodePage) -> Vec<u8> {
+        Updater::new(save, data).with_code_page(code_page).update();
+        let buf = vec![];
+        let mut zip = zip::ZipWriter::new(Cursor::new(buf));
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+        for ((_, name), gff) in GFFS.iter().zip([
+            Some(&save.inner.nfo),
+            Some(&save.inner.globals),
+            Some(&save.inner.party_table),
+            save.inner.pifo.as_ref(),
+        ]) {
+            let Some(gff) = gff else {
+                continue;
+            };
+            let bytes = gff::write(Gff {
+                code_page,
+                ..gff.clone()
+            });
+
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(&bytes).unwrap();
+        }
+        save.inner.erf.set_build_date();
+        let erf_bytes = erf::write(save.inner.erf.clone());
+        zip.start_file(ERF_NAME, options).unwrap();
+        zip.write_all(&erf_bytes).unwrap();
+        save.inner.original = Snapshot::new(save);
+
+        zip.finish().unwrap().into_inner()
+    }
+
+    // anything edited since the save was last read or written
+    pub fn is_modified(&self) -> bool {
+        !self.inner.original.matches(self)
+    }
+
+    // drops the edits made since the save was last read or written
+    pub fn reload(self) -> Self {
+        let mut gffs =
+            VecDeque::from_iter([self.inner.nfo, self.inner.globals, self.inner.party_table]);
+        if let Some(pifo) = self.inner.pifo {
+            gffs.push_back(pifo);
+        };
+        Self::read(gffs, self.inner.erf, self.image, self.inner.code_page).unwrap()
+    }
+}
+
+fn add_zip_file(path: &Path, zip: &mut zip::ZipWriter<&mut fs::File>) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let options = zip::write::FileOptions::default();
+
+    let file = fs::read(path)?;
+    zip.start_file(path.file_name().unwrap().to_str().unwrap(), options)?;
+    zip.write_all(&file)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        apply_json, changes,
+        test_support::{add_resource, make_character, make_data, make_files, make_save},
+        to_json,
+        update::Updater,
+        Global, GlobalValue, History, Item, Save, SaveError, ERF_NAME,
+    };
+    use ahash::HashMap;
+    use core::{
+        erf::{self, Erf},
+        gff::{self, Field, Gff, Struct},
+        util::encoding::CodePage,
+        ReadResourceNoArg as _, ResourceType,
+    };
+    use std::io::{Cursor, Read as _};
+
+    #[test]
+    fn json() {
+        let save = make_save();
+        let data = make_data();
+        let json = to_json(&save).unwrap();
+        assert!(json.contains(r#""K_BOOLEAN": true"#));
+        assert!(json.contains(r#""gender": "Male""#));
+
+        let mut applied = save.clone();
+        apply_json(&mut applied, &json, &data).unwrap();
+        assert_eq!(to_json(&applied).unwrap(), json);
+        assert_eq!(applied.inventory, save.inventory);
+
+        let recipe = r#"{
+            "format": 1,
+            "party_table": { "credits": 5000, "journal": [{ "id": "k_swg_helena", "stage": 10 }] },
+            "globals": { "K_BOOLEAN": false, "K_NEW": 3 },
+            "characters": [{ "tag": "", "feats": [1, 2] }],
+            "inventory": [{ "tag": "g_a_clothes" }, { "tag": "g_w_blstrpstl01", "stack_size": 2 }]
+        }"#;
+        apply_json(&mut applied, recipe, &data).unwrap();
+        // goes through the GFFs and back
+        let applied = applied.reload();
+        let pt = &applied.party_table;
+        assert_eq!(pt.credits, 5000);
+        assert_eq!(
+            (pt.journal[0].id.as_str(), pt.journal[0].stage),
+            ("k_swg_helena", 10)
+        );
+        let globals: Vec<_> = applied
+            .globals
+            .iter()
+            .map(|g| (g.name.as_str(), &g.value))
+            .collect();
+        assert_eq!(
+            globals,
+            [
+                ("K_BOOLEAN", &GlobalValue::Boolean(false)),
+                ("K_NEW", &GlobalValue::Number(3)),
+                ("K_NUMBER", &GlobalValue::Number(5)),
+            ]
+        );
+        assert_eq!(applied.characters[0].feats, [1, 2]);
+        assert_eq!(applied.inventory[0], save.inventory[1]);
+        assert_eq!(applied.inventory[1].name.as_deref(), Some("Blaster Pistol"));
+        assert_eq!(applied.inventory[1].stack_size, 2);
+        assert_eq!(applied.nfo.save_name, "Save");
+
+        // nothing changes when something can't be applied
+        let mut failed = applied.clone();
+        for broken in [
+            r#"{ "format": 2 }"#,
+            r#"{ "format": 1, "game": 2 }"#,
+            r#"{ "format": 1, "credits": 1 }"#,
+            r#"{ "format": 1, "party_table": { "components": 1 } }"#,
+            r#"{ "format": 1, "party_table": { "credits": 1 }, "inventory": [{ "tag": "nope" }] }"#,
+            r#"{ "format": 1, "characters": [{ "tag": "nope" }] }"#,
+        ] {
+            assert!(apply_json(&mut failed, broken, &data).is_err(), "{broken}");
+        }
+        assert_eq!(failed, applied);
+
+        // a resref wins over another item's tag
+        let mut data = make_data();
+        let mut other = data.items["g_w_blstrpstl001"].clone();
+        other.id = "g_w_blstrpstl01".to_owned();
+        other.tag = "G_W_BLSTRPSTL02".to_owned();
+        other.raw.insert("Tag", Field::String(other.tag.clone()));
+        data.items.insert(other.id.clone(), other);
+        let recipe = r#"{ "format": 1, "inventory": [{ "tag": "G_W_BLSTRPSTL01" }] }"#;
+        let mut fresh = save.clone();
+        apply_json(&mut fresh, recipe, &data).unwrap();
+        assert_eq!(fresh.inventory[0].tag, "G_W_BLSTRPSTL02");
+    }
+
+    #[test]
+    fn json_same_tag() {
+        // two party members with the same tag, e.g. from a mod
+        let mut files = make_files();
+        let mut party_table = Gff::read(&files["partytable.res"]).unwrap();
+        let member = Struct::new(vec![
+            ("PT_NPC_AVAIL", Field::Byte(1)),
+            ("PT_NPC_SELECT", Field::Byte(1)),
+        ]);
+        party_table
+            .content
+            .insert("PT_AVAIL_NPCS", Field::List(vec![member; 2]));
+        files.insert("partytable.res".to_owned(), gff::write(party_table));
+        let mut erf = Erf::read(&files[ERF_NAME]).unwrap();
+        let mut twin = make_character();
+        twin.insert("Tag", Field::String("twin".to_owned()));
+        for idx in 0..2 {
+            let utc = gff::write(Gff::new(("UTC ", "V3.2").into(), twin.clone()));
+            add_resource(&mut erf, &format!("availnpc{idx}"), ResourceType::Utc, utc);
+        }
+        files.insert(ERF_NAME.to_owned(), erf::write(erf));
+        let mut save = Save::read_from_files(&files, CodePage::default()).unwrap();
+        let data = make_data();
+
+        let recipe = r#"{
+            "format": 1,
+            "characters": [{ "tag": "twin", "feats": [1] }, { "tag": "TWIN", "feats": [2] }]
+        }"#;
+        apply_json(&mut save, recipe, &data).unwrap();
+        let save = save.reload();
+        assert_eq!(save.characters[1].feats, [1]);
+        assert_eq!(save.characters[2].feats, [2]);
+
+        let mut failed = save.clone();
+        let recipe = r#"{ "format": 1, "characters": [{ "tag": "twin" }, { "tag": "twin" }, { "tag": "twin" }] }"#;
+        assert!(apply_json(&mut failed, recipe, &data).is_err());
+        assert_eq!(failed, save);
+    }
+
+    #[test]
+    fn history() {
+        let mut save = make_save();
+        let mut history = History::new(&save);
+        assert!(!history.record(&save));
+        assert!(!history.undo(&mut save));
+
+        save.party_table.credits = 200;
+        assert!(history.record(&save));
+        save.party_table.credits = 300;
+        save.inventory.pop();
+        assert!(history.can_undo());
+
+        // the unrecorded edit is a step of its own
+        assert!(history.undo(&mut save));
+        assert_eq!((save.party_table.credits, save.inventory.len()), (200, 2));
+        assert!(history.undo(&mut save));
+        assert_eq!(save.party_table.credits, 100);
+        assert!(!history.can_undo());
+
+        assert!(history.redo(&mut save));
+        assert_eq!(save.party_table.credits, 200);
+        // editing drops what could be redone
+        save.nfo.save_name = "Edited".to_owned();
+        assert!(!history.redo(&mut save));
+        assert!(!history.can_redo());
+        assert!(history.undo(&mut save));
+        assert_eq!(
+            (save.nfo.save_name.as_str(), save.party_table.credits),
+            ("Save", 200)
+        );
+    }
+
+    #[test]
+    fn dirty_tracking() {
+        let mut save = make_save();
+        let data = make_data();
+        assert!(!save.is_modified());
+        assert!(changes(&save, &data).is_empty());
+
+        save.party_table.credits = 50000;
+        save.globals[1].value = GlobalValue::Number(2);
+        save.characters[0].attributes[1] = 18;
+        save.inventory.remove(0);
+        save.inventory
+            .push(Item::from(&data.items["g_w_blstrpstl001"]));
+        let descriptions = |save: &Save| -> Vec<_> {
+            changes(save, &data)
+                .into_iter()
+                .map(|change| change.description)
+                .collect()
+        };
+        assert_eq!(
+            descriptions(&save),
+            [
+                "Credits 100 → 50000",
+                "global K_NUMBER: 5 → 2",
+                "Revan: Dex 10 → 18",
+                "- item g_w_knife",
+                "+ item G_W_BLSTRPSTL01",
+            ]
+        );
+
+        // only the reverted change goes away
+        let list = changes(&save, &data);
+        list[2].revert(&mut save);
+        list[3].revert(&mut save);
+        assert_eq!(save.characters[0].attributes[1], 10);
+        assert_eq!(save.inventory.len(), 3);
+        assert_eq!(save.inventory[0].tag, "g_w_knife");
+        assert_eq!(descriptions(&save).len(), 3);
+
+        // the character is the same as it was read, so it isn't rewritten
+        Updater::new(&mut save, &data).update();
+        assert!(!save.characters[0].raw.fields.contains_key("HitPoints"));
+        save.characters[0].hp += 1;
+        Updater::new(&mut save, &data).update();
+        assert!(save.characters[0].raw.fields.contains_key("HitPoints"));
+
+        // writing is what the changes are compared against from then on
+        Save::save_to_zip(&mut save, &data, CodePage::default());
+        assert!(!save.is_modified());
+        assert!(descriptions(&save).is_empty());
+
+        // removed entries go back where they were, so reverting everything leaves nothing modified
+        save.characters[0].feats = vec![1, 2, 3];
+        Save::save_to_zip(&mut save, &data, CodePage::default());
+        save.characters[0].feats.drain(..2);
+        let removed = save.globals.remove(0);
+        save.globals.push(Global {
+            name: "K_ADDED".to_owned(),
+            value: GlobalValue::Boolean(true),
+        });
+        let list = changes(&save, &data);
+        assert!(list
+            .iter()
+            .any(|change| change.description == format!("- global {}", removed.name)));
+        assert!(list
+            .iter()
+            .any(|change| change.description == "+ global K_ADDED = true"));
+        for change in list.iter().rev() {
+            change.revert(&mut save);
+        }
+        assert_eq!(save.characters[0].feats, [1, 2, 3]);
+        assert_eq!(save.globals[0], removed);
+        assert!(!save.is_modified());
+    }
+
+    #[test]
+    fn read_errors() {
+        let mut files = make_files();
+        files.remove("globalvars.res");
+        let err = Save::read_from_files(&files, CodePage::default()).unwrap_err();
+        assert_eq!(err, SaveError::Missing("globalvars.res".to_owned()));
+        assert_eq!(err.to_string(), "couldn't find globalvars.res");
+
+        let mut files = make_files();
+        files.get_mut("partytable.res").unwrap().truncate(20);
+        let err = Save::read_from_files(&files, CodePage::default()).unwrap_err();
+        let SaveError::Format(err) = err else {
+            panic!("{err}");
+        };
+        assert_eq!(err.resource.as_deref(), Some("partytable.res"));
+
+        // the inner module has no player list
+        let mut files = make_files();
+        let mut erf = Erf::read(&files[ERF_NAME]).unwrap();
+        let module = erf.get_mut("end_m01aa", ResourceType::Sav).unwrap();
+        let mut module_erf = Erf::read(&module.content).unwrap();
+        let ifo = Gff::new(("IFO ", "V3.2").into(), Struct::new(vec![]));
+        module_erf
+            .get_mut("module", ResourceType::Ifo)
+            .unwrap()
+            .content = gff::write(ifo);
+        module.content = erf::write(module_erf);
+        files.insert(ERF_NAME.to_owned(), erf::write(erf));
+        let err = Save::read_from_files(&files, CodePage::default()).unwrap_err();
+        assert!(matches!(err, SaveError::Invalid(_)), "{err}");
+    }
+
+    fn unzip(bytes: Vec<u8>) -> HashMap<String, Vec<u8>> {
+        let mut zip = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        (0..zip.len())
+            .map(|idx| {
+                let mut file = zip.by_index(idx).unwrap();
+                let mut bytes = vec![];
+                file.read_to_end(&mut bytes).unwrap();
+                (file.name().to_owned(), bytes)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn code_page() {
+        let data = make_data();
+        let mut save = make_save();
+        save.nfo.save_name = "Бастила".to_owned();
+        save.characters[0].name = "Карт".to_owned();
+        let bytes = Save::save_to_zip(&mut save, &data, CodePage::Windows1251);
+
+        let files = unzip(bytes);
+        assert!(files["savenfo.res"]
+            .windows(7)
+            .any(|w| w == CodePage::Windows1251.encode("Бастила")));
+
+        let read = Save::read_from_files(&files, CodePage::Windows1251).unwrap();
+        assert_eq!(read.nfo.save_name, "Бастила");
+        assert_eq!(read.characters[0].name, "Карт");
+        assert!(!read.is_modified());
+        // the same bytes mean something else in another code page
+        let read = Save::read_from_files(&files, CodePage::Windows1252).unwrap();
+        assert_ne!(read.nfo.save_name, "Бастила");
+
+        // nothing is edited, but the module with the character has to be in the new one as well
+        let mut read = Save::read_from_files(&files, CodePage::Windows1251).unwrap();
+        let bytes = Save::save_to_zip(&mut read, &data, CodePage::Gbk);
+        let files = unzip(bytes);
+        let read = Save::read_from_files(&files, CodePage::Gbk).unwrap();
+        assert_eq!(read.nfo.save_name, "Бастила");
+        assert_eq!(read.characters[0].name, "Карт");
+    }
+}
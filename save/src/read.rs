@@ -1,19 +1,15 @@
 use crate::{
-    save::{
-        AvailablePartyMember, Character, Class, Door, Game, Gender, Global, GlobalValue, Item,
-        JournalEntry, Nfo, PartyMember, PartyTable, Save, SaveInternals, EQUIPMENT_SLOT_IDS,
-        GLOBALS_TYPES, NPC_RESOURCE_PREFIX,
-    },
-    util::{calc_hp_fp_offset, SResult},
+    calc_hp_fp_offset, snapshot::Snapshot, AvailablePartyMember, Character, Class, Door, Game,
+    Gender, Global, GlobalValue, Item, JournalEntry, Nfo, PartyMember, PartyTable, Save, SaveError,
+    SaveInternals, SaveResult, EQUIPMENT_SLOT_IDS, GLOBALS_TYPES, NPC_RESOURCE_PREFIX,
 };
 use ahash::HashMap;
 use core::{
     erf::Erf,
     gff::{Field, Gff, Struct},
-    util::prepare_item_name,
-    ReadResourceNoArg as _, ResourceKey, ResourceType,
+    util::{encoding::CodePage, prepare_item_name, SResult},
+    ReadResource as _, ResourceKey, ResourceType,
 };
-use egui::TextureHandle;
 use log::error;
 
 struct LastModuleInfo {
@@ -29,7 +25,8 @@ pub struct Reader {
     erf: Erf,
     pifo: Option<Gff>,
     game: Game,
-    image: Option<TextureHandle>,
+    image: Option<Vec<u8>>,
+    code_page: CodePage,
 }
 
 impl Reader {
@@ -39,7 +36,8 @@ impl Reader {
         party_table: Gff,
         erf: Erf,
         pifo: Option<Gff>,
-        image: Option<TextureHandle>,
+        image: Option<Vec<u8>>,
+        code_page: CodePage,
     ) -> Self {
         let game = if party_table.fields.get("PT_ITEM_CHEMICAL").is_some() {
             Game::Two
@@ -55,10 +53,11 @@ impl Reader {
             pifo,
             game,
             image,
+            code_page,
         }
     }
 
-    pub fn into_save(self) -> SResult<Save> {
+    pub fn into_save(self) -> SaveResult<Save> {
         let mut nfo = self.read_nfo()?;
         let globals = self.read_globals()?;
         let mut party_table = self.read_party_table()?;
@@ -75,6 +74,15 @@ impl Reader {
         nfo.cheat_used = nfo.cheat_used || party_table.cheat_used;
         party_table.cheat_used = nfo.cheat_used;
 
+        let original = Snapshot {
+            globals: globals.clone(),
+            nfo: nfo.clone(),
+            party_table: party_table.clone(),
+            characters: characters.clone(),
+            inventory: inventory.clone(),
+            doors: doors.clone(),
+        };
+
         Ok(Save {
             id: fastrand::u64(..),
             nfo,
@@ -95,6 +103,8 @@ impl Reader {
 
                 git_key,
                 use_pifo: lm_info.use_pifo,
+                code_page: self.code_page,
+                original,
             },
         })
     }
@@ -244,13 +254,15 @@ impl Reader {
         })
     }
 
-    fn read_last_module(&self, last_module: &str) -> SResult<LastModuleInfo> {
+    fn read_last_module(&self, last_module: &str) -> SaveResult<LastModuleInfo> {
         if let Some(module) = self.erf.get(last_module, ResourceType::Sav) {
-            let module_erf = Erf::read(&module.content)?;
+            let module_erf = Erf::read(&module.content, ())
+                .map_err(|err| err.with_resource(format!("{last_module}.sav")))?;
             let module_inner = module_erf
                 .get("module", ResourceType::Ifo)
-                .ok_or("couldn't get inner module resource".to_string())?;
-            let ifo = Gff::read(&module_inner.content)?;
+                .ok_or("couldn't get inner module resource")?;
+            let ifo = Gff::read(&module_inner.content, self.code_page)
+                .map_err(|err| err.with_resource("module.ifo"))?;
 
             let git_key = module_erf
                 .resources
@@ -264,7 +276,7 @@ impl Reader {
                 );
             }
             let module_git = git_key.as_ref().and_then(|k| module_erf.resources.get(k));
-            let git = module_git.and_then(|g| Gff::read(&g.content).ok());
+            let git = module_git.and_then(|g| Gff::read(&g.content, self.code_page).ok());
 
             Ok(LastModuleInfo {
                 use_pifo: false,
@@ -278,16 +290,16 @@ impl Reader {
                 git: None,
             })
         } else {
-            return Err("couldn't get last module resource".to_string());
+            return Err("couldn't get last module resource".into());
         }
     }
 
-    fn read_characters(&self, mut last_module: Gff, count: usize) -> SResult<Vec<Character>> {
+    fn read_characters(&self, mut last_module: Gff, count: usize) -> SaveResult<Vec<Character>> {
         let mut characters = Vec::with_capacity(count + 1);
 
         let mut leader_field = last_module.take("Mod_PlayerList", Field::list_take)?;
         if leader_field.is_empty() {
-            return Err("couldn't get player character struct".to_string());
+            return Err("couldn't get player character struct".into());
         }
         let leader = leader_field.remove(0);
 
@@ -302,8 +314,8 @@ impl Reader {
             let Some(resource) = self.erf.get(&key, ResourceType::Utc) else {
                 continue;
             };
-            let gff = Gff::read(&resource.content)
-                .map_err(|err| format!("couldn't read NPC GFF {idx}: {err}"))?;
+            let gff = Gff::read(&resource.content, self.code_page)
+                .map_err(|err| err.with_resource(format!("{key}.utc")))?;
             let char = Self::read_character(gff.content, idx)
                 .map_err(|err| format!("error parsing character {idx}: {err}"))?;
 
@@ -421,12 +433,13 @@ impl Reader {
         })
     }
 
-    fn read_inventory(&self) -> SResult<Vec<Item>> {
+    fn read_inventory(&self) -> SaveResult<Vec<Item>> {
         let res = self
             .erf
-            .get("inventory", ResourceType::Unknown)
-            .ok_or("couldn't find inventory")?;
-        let gff = Gff::read(&res.content)?;
+            .get("inventory", ResourceType::Res)
+            .ok_or_else(|| SaveError::Missing("inventory.res".to_owned()))?;
+        let gff = Gff::read(&res.content, self.code_page)
+            .map_err(|err| err.with_resource("inventory.res"))?;
         let list = gff.get_ref("ItemList", Field::list)?;
         let mut items = Vec::with_capacity(list.len());
         for item in list {
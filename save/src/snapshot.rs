@@ -0,0 +1,48 @@
+use crate::{Character, Door, Global, Item, Nfo, PartyTable, Save};
+
+// the model without the GFFs and the image, those never change while editing
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Snapshot {
+    pub globals: Vec<Global>,
+    pub nfo: Nfo,
+    pub party_table: PartyTable,
+    pub characters: Vec<Character>,
+    pub inventory: Vec<Item>,
+    pub doors: Option<Vec<Door>>,
+}
+
+impl Snapshot {
+    pub fn new(save: &Save) -> Self {
+        Self {
+            globals: save.globals.clone(),
+            nfo: save.nfo.clone(),
+            party_table: save.party_table.clone(),
+            characters: save.characters.clone(),
+            inventory: save.inventory.clone(),
+            doors: save.doors.clone(),
+        }
+    }
+
+    // checked often, so nothing gets cloned
+    pub fn matches(&self, save: &Save) -> bool {
+        self.globals == save.globals
+            && self.nfo == save.nfo
+            && self.party_table == save.party_table
+            && self.characters == save.characters
+            && self.inventory == save.inventory
+            && self.doors == save.doors
+    }
+
+    pub fn restore(self, save: &mut Save) {
+        save.globals = self.globals;
+        save.nfo = self.nfo;
+        save.party_table = self.party_table;
+        save.characters = self.characters;
+        save.inventory = self.inventory;
+        save.doors = self.doors;
+    }
+
+    pub fn character(&self, idx: usize) -> Option<&Character> {
+        self.characters.iter().find(|char| char.idx == idx)
+    }
+}
@@ -0,0 +1,201 @@
+// saves and game data built in memory, for the tests of this crate and the ones using it
+use crate::{Save, ERF_NAME};
+use ahash::HashMap;
+use core::{
+    erf::{self, Erf, Resource},
+    gff::{self, Field, Gff, Struct},
+    util::encoding::CodePage,
+    GameData, GameDataMapped, Item as DItem, LocString, Quest, QuestStage, ResourceType,
+};
+
+pub fn loc_string(content: &str) -> Field {
+    Field::LocString((
+        u32::MAX,
+        vec![LocString {
+            id: 0,
+            content: content.to_owned(),
+        }],
+    ))
+}
+
+pub fn make_item(tag: &str) -> Struct {
+    Struct::new(vec![
+        ("Tag", Field::String(tag.to_owned())),
+        ("BaseItem", Field::Int(1)),
+        ("LocalizedName", loc_string(tag)),
+        ("DescIdentified", loc_string("")),
+        ("StackSize", Field::Word(1)),
+        ("MaxCharges", Field::Byte(0)),
+        ("Charges", Field::Byte(0)),
+        ("NewItem", Field::Byte(0)),
+        ("Upgrades", Field::Dword(0)),
+    ])
+}
+
+pub fn make_character() -> Struct {
+    let mut fields = vec![
+        ("FirstName", loc_string("Revan")),
+        ("Tag", Field::String(String::new())),
+        ("Gender", Field::Byte(0)),
+        ("Equip_ItemList", Field::List(vec![])),
+        ("FeatList", Field::List(vec![])),
+        (
+            "ClassList",
+            Field::List(vec![Struct::new(vec![
+                ("Class", Field::Int(0)),
+                ("ClassLevel", Field::Short(1)),
+            ])]),
+        ),
+        (
+            "SkillList",
+            Field::List(vec![Struct::new(vec![("Rank", Field::Byte(0))]); 8]),
+        ),
+    ];
+    for name in [
+        "Str", "Dex", "Con", "Int", "Wis", "Cha", "Min1HP", "GoodEvil",
+    ] {
+        fields.push((name, Field::Byte(10)));
+    }
+    for name in [
+        "CurrentHitPoints",
+        "MaxHitPoints",
+        "ForcePoints",
+        "MaxForcePoints",
+    ] {
+        fields.push((name, Field::Short(10)));
+    }
+    for name in ["PortraitId", "Appearance_Type", "SoundSetFile"] {
+        fields.push((name, Field::Word(0)));
+    }
+    fields.push(("Experience", Field::Dword(0)));
+
+    Struct::new(fields)
+}
+
+pub fn add_resource(erf: &mut Erf, name: &str, tp: ResourceType, content: Vec<u8>) {
+    let resource = Resource {
+        name: name.to_owned(),
+        id: erf.resources.len() as u32,
+        content,
+    };
+    erf.resources.insert((name, tp).into(), resource);
+}
+
+pub fn make_files() -> HashMap<String, Vec<u8>> {
+    let gff = |fields| gff::write(Gff::new(("GFF ", "V3.2").into(), Struct::new(fields)));
+    let names = |names: &[&str]| {
+        let list = names
+            .iter()
+            .map(|name| Struct::new(vec![("Name", Field::String((*name).to_owned()))]))
+            .collect();
+        Field::List(list)
+    };
+
+    let nfo = gff(vec![
+        ("SAVEGAMENAME", Field::String("Save".to_owned())),
+        ("AREANAME", Field::String("Endar Spire".to_owned())),
+        ("LASTMODULE", Field::String("end_m01aa".to_owned())),
+        ("CHEATUSED", Field::Byte(0)),
+        ("TIMEPLAYED", Field::Dword(60)),
+    ]);
+    let globals = gff(vec![
+        ("CatNumber", names(&["K_NUMBER"])),
+        ("ValNumber", Field::Void(vec![5])),
+        ("CatBoolean", names(&["K_BOOLEAN"])),
+        ("ValBoolean", Field::Void(vec![0x80])),
+    ]);
+    let party_table = gff(vec![
+        ("PT_MEMBERS", Field::List(vec![])),
+        ("PT_AVAIL_NPCS", Field::List(vec![])),
+        ("PT_XP_POOL", Field::Int(0)),
+        ("PT_CHEAT_USED", Field::Byte(0)),
+        ("PT_GOLD", Field::Dword(100)),
+    ]);
+
+    let module = Struct::new(vec![(
+        "Mod_PlayerList",
+        Field::List(vec![make_character()]),
+    )]);
+    let mut module_erf = Erf::new(("SAV ", "V1.0").into());
+    let module = gff::write(Gff::new(("IFO ", "V3.2").into(), module));
+    add_resource(&mut module_erf, "module", ResourceType::Ifo, module);
+
+    let inventory = Struct::new(vec![(
+        "ItemList",
+        Field::List(vec![make_item("g_w_knife"), make_item("g_a_clothes")]),
+    )]);
+    let mut erf = Erf::new(("SAV ", "V1.0").into());
+    let inventory = gff::write(Gff::new(("INV ", "V3.2").into(), inventory));
+    add_resource(&mut erf, "inventory", ResourceType::Res, inventory);
+    add_resource(
+        &mut erf,
+        "end_m01aa",
+        ResourceType::Sav,
+        erf::write(module_erf),
+    );
+
+    [
+        ("savenfo.res", nfo),
+        ("globalvars.res", globals),
+        ("partytable.res", party_table),
+        (ERF_NAME, erf::write(erf)),
+    ]
+    .into_iter()
+    .map(|(name, bytes)| (name.to_owned(), bytes))
+    .collect()
+}
+
+// Revan with no feats, K_NUMBER = 5 and K_BOOLEAN = true, a knife and clothes in the inventory
+pub fn make_save() -> Save {
+    Save::read_from_files(&make_files(), CodePage::default()).unwrap()
+}
+
+// a blaster pistol and a quest with stages 10 and 20
+pub fn make_data() -> GameDataMapped {
+    // items from the game data only have a StrRef, the name gets added when writing
+    let mut raw = make_item("G_W_BLSTRPSTL01");
+    raw.insert("LocalizedName", Field::LocString((0, vec![])));
+    let item = DItem {
+        id: "g_w_blstrpstl001".to_owned(),
+        tag: "G_W_BLSTRPSTL01".to_owned(),
+        base_item: 1,
+        name: Some("Blaster Pistol".to_owned()),
+        description: None,
+        stack_size: 1,
+        charges: 0,
+        upgrade_level: None,
+        icon: None,
+        raw,
+    };
+    let stage = |id| {
+        let description = format!("stage {id}");
+        (
+            id,
+            QuestStage {
+                id,
+                description,
+                end: id == 20,
+            },
+        )
+    };
+    let quest = Quest {
+        id: "k_swg_helena".to_owned(),
+        name: "Helena".to_owned(),
+        stages: [stage(10), stage(20)].into_iter().collect(),
+    };
+
+    GameData {
+        id: 0,
+        code_page: CodePage::default(),
+        feats: vec![],
+        powers: vec![],
+        classes: vec![],
+        portraits: vec![],
+        appearances: vec![],
+        soundsets: vec![],
+        quests: vec![quest],
+        base_items: HashMap::default(),
+        items: vec![item],
+    }
+    .into()
+}
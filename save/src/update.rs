@@ -1,33 +1,86 @@
 use crate::{
-    save::{Character, Class, GlobalValue, Item, Save, GLOBALS_TYPES, NPC_RESOURCE_PREFIX},
-    util::{calc_hp_fp_offset, find_pc_name},
+    calc_hp_fp_offset, find_pc_name, Character, Class, GlobalValue, Item, Save, GLOBALS_TYPES,
+    NPC_RESOURCE_PREFIX,
 };
 use core::{
     erf::{self, Erf},
     gff::{self, Field, Gff, Struct},
-    GameDataMapped, LocString, ReadResourceNoArg as _, ResourceType,
+    util::encoding::CodePage,
+    FileHead, GameDataMapped, LocString, ReadResource as _, ResourceType,
 };
 
 use super::EQUIPMENT_SLOT_IDS;
 
+// resources that nothing was changed in since the save was last read or written are left as they
+// are, unless it's written in another code page
 pub struct Updater<'a> {
     save: &'a mut Save,
     data: &'a GameDataMapped,
+    changed_characters: Vec<bool>,
+    // the one the resources that aren't rewritten are still in
+    read_code_page: CodePage,
+    rewrite: bool,
 }
 
 impl<'a> Updater<'a> {
     pub fn new(save: &'a mut Save, data: &'a GameDataMapped) -> Self {
-        Self { save, data }
+        let original = &save.inner.original;
+        let changed_characters = save
+            .characters
+            .iter()
+            .map(|char| original.character(char.idx) != Some(char))
+            .collect();
+        let read_code_page = save.inner.code_page;
+
+        Self {
+            save,
+            data,
+            changed_characters,
+            read_code_page,
+            rewrite: false,
+        }
+    }
+
+    // unchanged resources would stay in the old code page, so everything gets rewritten
+    pub fn with_code_page(mut self, code_page: CodePage) -> Self {
+        if code_page != self.read_code_page {
+            self.save.inner.code_page = code_page;
+            self.changed_characters.fill(true);
+            self.rewrite = true;
+        }
+
+        self
     }
 
     pub fn update(mut self) {
-        self.update_nfo();
-        self.update_globals();
-        self.update_party_table();
+        let (save, original) = (&self.save, &self.save.inner.original);
+        let nfo = save.nfo != original.nfo;
+        let globals = save.globals != original.globals;
+        let party_table = save.party_table != original.party_table;
+        let inventory = self.rewrite || save.inventory != original.inventory;
+        let doors = self.rewrite || save.doors != original.doors;
+        let characters = self.changed_characters.contains(&true);
+        let pc = self.changed_characters.first() == Some(&true);
+
+        // has the PC name and the portraits of the party
+        if nfo || party_table || characters {
+            self.update_nfo();
+        }
+        if globals {
+            self.update_globals();
+        }
+        // the cheat flag comes from the NFO
+        if party_table || nfo {
+            self.update_party_table();
+        }
         self.update_characters();
-        self.update_doors();
-        self.update_pifo();
-        self.update_erf();
+        if doors {
+            self.update_doors();
+        }
+        if pc {
+            self.update_pifo();
+        }
+        self.update_erf(inventory, pc || doors);
     }
 
     fn update_nfo(&mut self) {
@@ -171,8 +224,15 @@ impl<'a> Updater<'a> {
     }
 
     fn update_characters(&mut self) {
-        for char in &mut self.save.characters {
-            Self::update_character(char);
+        for (char, changed) in self
+            .save
+            .characters
+            .iter_mut()
+            .zip(&self.changed_characters)
+        {
+            if *changed {
+                Self::update_character(char);
+            }
         }
     }
 
@@ -288,64 +348,56 @@ impl<'a> Updater<'a> {
             return;
         }
         let pifo = self.save.inner.pifo.as_mut().unwrap();
-        let Field::List(list) = pifo.content.fields.get_mut("Mod_PlayerList").unwrap() else {
-            unreachable!()
-        };
-        list[0] = self.save.characters[0].raw.clone();
+        *pifo.get_struct_path_mut("Mod_PlayerList[0]").unwrap() =
+            self.save.characters[0].raw.clone();
     }
 
-    fn update_erf(&mut self) {
-        let inventory = self.make_inventory();
+    fn update_erf(&mut self, inventory: bool, module: bool) {
+        if inventory {
+            let inventory = self.make_inventory();
+            let inventory_res = self.save.inner.erf.get_mut("inventory", ResourceType::Res);
+            inventory_res.unwrap().content = gff::write(inventory);
+        }
+        let (read_code_page, code_page) = (self.read_code_page, self.save.inner.code_page);
         let erf = &mut self.save.inner.erf;
 
-        let inventory_res = erf.get_mut("inventory", ResourceType::Unknown).unwrap();
-        inventory_res.content = gff::write(inventory);
-
         // pain
-        if !self.save.inner.use_pifo {
+        if !self.save.inner.use_pifo && module {
             let last_module = self.save.nfo.last_module.to_lowercase();
             let module = erf.get_mut(&last_module, ResourceType::Sav).unwrap();
-            let mut module_erf = Erf::read(&module.content).unwrap();
+            let mut module_erf = Erf::read(&module.content, ()).unwrap();
             let module_inner = module_erf.get_mut("module", ResourceType::Ifo).unwrap();
-            let mut module_inner_gff = Gff::read(&module_inner.content).unwrap();
-            let Field::List(list) = module_inner_gff
-                .content
-                .fields
-                .get_mut("Mod_PlayerList")
-                .unwrap()
-            else {
-                unreachable!()
-            };
-
-            list[0] = self.save.characters[0].raw.clone();
+            let mut module_inner_gff = Gff::read(&module_inner.content, read_code_page).unwrap();
+            module_inner_gff.code_page = code_page;
+            *module_inner_gff
+                .get_struct_path_mut("Mod_PlayerList[0]")
+                .unwrap() = self.save.characters[0].raw.clone();
             module_inner.content = gff::write(module_inner_gff);
 
             if let Some(doors) = &self.save.doors {
                 let git_key = self.save.inner.git_key.as_ref().unwrap();
                 let git = module_erf.resources.get_mut(git_key).unwrap();
-                let mut gff = Gff::read(&git.content).unwrap();
+                let mut gff = Gff::read(&git.content, read_code_page).unwrap();
+                gff.code_page = code_page;
 
-                let Field::List(list) = gff.content.fields.get_mut("Door List").unwrap() else {
-                    unreachable!()
-                };
-                *list = doors.iter().map(|d| &d.raw).cloned().collect();
+                let list = doors.iter().map(|d| &d.raw).cloned().collect();
+                gff.set_path("Door List", Field::List(list)).unwrap();
                 git.content = gff::write(gff);
             }
 
             module.content = erf::write(module_erf);
         }
 
-        for char in self.save.characters.iter().skip(1) {
+        let characters = self.save.characters.iter().zip(&self.changed_characters);
+        for (char, _) in characters.skip(1).filter(|(_, changed)| **changed) {
             let key = if char.idx == usize::MAX - 1 {
                 "pc".to_owned()
             } else {
                 format!("{NPC_RESOURCE_PREFIX}{}", char.idx)
             };
             let res = erf.get_mut(&key, ResourceType::Utc).unwrap();
-            res.content = gff::write(Gff {
-                file_head: ("UTC ", "V3.2").into(),
-                content: char.raw.clone(),
-            });
+            let gff = Self::make_gff(("UTC ", "V3.2").into(), char.raw.clone(), code_page);
+            res.content = gff::write(gff);
         }
     }
 
@@ -394,9 +446,17 @@ impl<'a> Updater<'a> {
             })
             .collect();
 
+        Self::make_gff(
+            ("INV ", "V3.2").into(),
+            Struct::with_type(u32::MAX, vec![("ItemList", Field::List(items))]),
+            self.save.inner.code_page,
+        )
+    }
+
+    fn make_gff(file_head: FileHead, content: Struct, code_page: CodePage) -> Gff {
         Gff {
-            file_head: ("INV ", "V3.2").into(),
-            content: Struct::with_type(u32::MAX, vec![("ItemList", Field::List(items))]),
+            code_page,
+            ..Gff::new(file_head, content)
         }
     }
 }
@@ -1,6 +1,5 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-mod save;
 mod ui;
 mod util;
 
@@ -24,7 +23,7 @@ fn main() -> eframe::Result<()> {
     }
     let native_options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_app_id("sotor")
+            .with_app_id(util::APP_ID)
             .with_min_inner_size([960., 540.])
             .with_icon(util::load_icon()),
         ..Default::default()
@@ -1,5 +1,4 @@
 use crate::{
-    save::{Door, Save},
     ui::{
         styles::{set_checkbox_styles, set_slider_styles, set_striped_styles, GREEN_DARK},
         widgets::UiExt,
@@ -8,6 +7,7 @@ use crate::{
     util::ColumnCounter,
 };
 use egui::{Frame, Grid, Margin, RichText, ScrollArea};
+use save::{Door, Save};
 
 pub struct Editor<'a> {
     doors: &'a mut Option<Vec<Door>>,
@@ -0,0 +1,55 @@
+use crate::ui::{
+    styles::set_striped_styles,
+    widgets::{Icon, UiExt},
+    UiRef,
+};
+use egui::{Grid, ScrollArea};
+use save::{Change, Save};
+
+pub struct Editor<'a> {
+    save: &'a mut Save,
+    changes: &'a [Change],
+}
+
+impl<'a> Editor<'a> {
+    pub fn new(save: &'a mut Save, changes: &'a [Change]) -> Self {
+        Self { save, changes }
+    }
+
+    pub fn show(&mut self, ui: UiRef) {
+        if self.changes.is_empty() {
+            ui.horizontal_centered(|ui| {
+                ui.s_offset(ui.max_rect().width() / 2. - 150., 0.);
+                ui.label("Nothing has changed since the save was read or written");
+            });
+            return;
+        }
+
+        ScrollArea::vertical()
+            .id_source("ech_scroll")
+            .show(ui, |ui| {
+                set_striped_styles(ui);
+                ui.set_width(ui.available_width());
+
+                Grid::new("ech_grid")
+                    .spacing([5., 5.])
+                    .striped(true)
+                    .show(ui, |ui| self.changes(ui));
+            });
+    }
+
+    fn changes(&mut self, ui: UiRef) {
+        let mut reverted = None;
+        for (idx, change) in self.changes.iter().enumerate() {
+            if ui.s_icon_button(Icon::Undo, "Revert").clicked() {
+                reverted = Some(idx);
+            }
+            ui.s_text(&change.description);
+            ui.end_row();
+        }
+
+        if let Some(idx) = reverted {
+            self.changes[idx].revert(self.save);
+        }
+    }
+}
@@ -1,5 +1,4 @@
 use crate::{
-    save::{Character, Class},
     ui::{
         styles::{
             set_checkbox_styles, set_combobox_styles, set_selectable_styles, set_slider_styles,
@@ -17,6 +16,7 @@ use egui::{
     WidgetText,
 };
 use emath::{vec2, Align};
+use save::{Character, Class};
 use std::{borrow::Cow, collections::HashSet, fmt::Display, hash::Hash};
 
 pub struct CharAbilities<'a> {
@@ -1,5 +1,4 @@
 use crate::{
-    save::{Character, Item},
     ui::{
         styles::{
             set_checkbox_styles, set_radio_styles, set_selectable_styles, set_striped_styles, GREEN,
@@ -11,6 +10,7 @@ use crate::{
 };
 use core::{Data, DataDescr, GameDataMapped, ItemSlot, UsableBy, WeaponType};
 use egui::{ComboBox, Grid, Id, Response};
+use save::{Character, Item};
 use std::{borrow::Cow, mem};
 
 const BASIC_SLOTS_HUMAN: [(&str, ItemSlot); 7] = [
@@ -1,5 +1,4 @@
 use crate::{
-    save::Save,
     ui::{
         styles::{set_combobox_styles, set_selectable_styles, GREEN},
         widgets::{color_text, UiExt},
@@ -9,6 +8,7 @@ use crate::{
 };
 use core::GameDataMapped;
 use egui::{ComboBox, ScrollArea};
+use save::Save;
 
 mod abilities;
 mod equipment;
@@ -1,18 +1,18 @@
 use crate::{
-    save::{Character, Gender, PartyTable},
     ui::{
         styles::{
             set_checkbox_styles, set_combobox_styles, set_drag_value_styles, set_selectable_styles,
             set_striped_styles, GREEN,
         },
-        widgets::{color_text, UiExt},
+        widgets::{color_text, thumbnail, UiExt},
         UiRef,
     },
     util::get_data_name,
 };
 use ahash::HashMap;
-use core::{Appearance, GameDataMapped};
+use core::{Appearance, GameDataMapped, Textures};
 use egui::{ComboBox, DragValue, Grid};
+use save::{Character, Gender, PartyTable};
 
 pub struct CharStats<'a> {
     char: &'a mut Character,
@@ -164,6 +164,7 @@ impl<'a> CharStats<'a> {
                 &mut self.char.portrait,
                 &self.data.portraits,
                 &self.data.inner.portraits,
+                self.data.textures.as_ref(),
             );
             ui.end_row();
             ui.label(color_text("Appearance:", GREEN));
@@ -174,6 +175,7 @@ impl<'a> CharStats<'a> {
                 &mut self.char.appearance,
                 &self.data.appearances,
                 &self.data.inner.appearances,
+                None,
             );
             ui.end_row();
             ui.label(color_text("Soundset:", GREEN));
@@ -184,6 +186,7 @@ impl<'a> CharStats<'a> {
                 &mut self.char.soundset,
                 &self.data.soundsets,
                 &self.data.inner.soundsets,
+                None,
             );
         });
     }
@@ -194,19 +197,28 @@ impl<'a> CharStats<'a> {
         current: &mut u16,
         data: &HashMap<u16, Appearance>,
         data_list: &[Appearance],
+        // names are texture names, used for portraits
+        textures: Option<&Textures>,
     ) {
         let name = get_data_name(data, current);
+        let texture = data.get(current).map(|a| a.name.as_str());
 
-        ComboBox::from_id_source(id)
-            .width(200.)
-            .selected_text(name)
-            .show_ui(ui, |ui| {
-                set_selectable_styles(ui);
-                let mut selected = *current;
-                for item in data_list {
-                    ui.selectable_value(&mut selected, item.id, &item.name);
-                }
-                *current = selected;
-            });
+        ui.horizontal(|ui| {
+            thumbnail(ui, textures, texture, 32.);
+            ComboBox::from_id_source(id)
+                .width(200.)
+                .selected_text(name)
+                .show_ui(ui, |ui| {
+                    set_selectable_styles(ui);
+                    let mut selected = *current;
+                    for item in data_list {
+                        ui.horizontal(|ui| {
+                            thumbnail(ui, textures, Some(&item.name), 32.);
+                            ui.selectable_value(&mut selected, item.id, &item.name);
+                        });
+                    }
+                    *current = selected;
+                });
+        });
     }
 }
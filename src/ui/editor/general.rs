@@ -1,19 +1,19 @@
 use crate::{
-    save::{AvailablePartyMember, Character, Nfo, PartyMember, PartyTable, Save},
     ui::{
         styles::{set_checkbox_styles, set_slider_styles, set_striped_styles, BLUE, GREEN, WHITE},
         widgets::{color_text, Icon, IconButton, UiExt},
         UiRef,
     },
-    util::{find_pc_name, format_seconds, ColumnCounter},
+    util::{format_seconds, load_tga, ColumnCounter, ContextExt as _},
 };
-use egui::{Frame, Grid, Image, Layout, Margin, RichText, TextureHandle};
+use egui::{Frame, Grid, Id, Image, Layout, Margin, RichText, TextureHandle, TextureOptions};
+use save::{find_pc_name, AvailablePartyMember, Character, Nfo, PartyMember, PartyTable, Save};
 
 pub struct Editor<'a> {
     nfo: &'a mut Nfo,
     party_table: &'a mut PartyTable,
     characters: &'a mut [Character],
-    image: &'a Option<TextureHandle>,
+    image: &'a Option<Vec<u8>>,
 }
 
 impl<'a> Editor<'a> {
@@ -63,12 +63,24 @@ impl<'a> Editor<'a> {
     }
 
     fn image(&mut self, ui: UiRef) {
+        let ctx = ui.ctx().clone();
+        let id = Id::new("save_image");
+        // decoded once per loaded save, broken images are cached too
+        let texture = ctx
+            .get_data::<Option<TextureHandle>>(id)
+            .unwrap_or_else(|| {
+                let tga = self.image.as_deref().and_then(|bytes| load_tga(bytes).ok());
+                let texture =
+                    tga.map(|tga| ctx.load_texture("save_image", tga, TextureOptions::NEAREST));
+                ctx.set_data(id, texture.clone());
+                texture
+            });
+        let Some(texture) = texture else {
+            return;
+        };
+
         let scale = 1.3;
-        let image = Image::from((
-            self.image.as_ref().unwrap().id(),
-            (256. * scale, 144. * scale).into(),
-        ))
-        .rounding(5.);
+        let image = Image::from((texture.id(), (256. * scale, 144. * scale).into())).rounding(5.);
         ui.add(image);
     }
 
@@ -1,5 +1,4 @@
 use crate::{
-    save::{Global, GlobalValue, Save},
     ui::{
         styles::{set_checkbox_styles, set_drag_value_styles, set_striped_styles},
         widgets::UiExt,
@@ -8,6 +7,7 @@ use crate::{
     util::ColumnCounter,
 };
 use egui::{DragValue, Grid, ScrollArea};
+use save::{Global, GlobalValue, Save};
 
 pub struct Editor<'a> {
     globals: &'a mut Vec<Global>,
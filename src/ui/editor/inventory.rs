@@ -1,18 +1,18 @@
 use crate::{
-    save::{Item, Save},
     ui::{
         styles::{
             set_checkbox_styles, set_combobox_styles, set_selectable_styles, set_slider_styles,
             set_striped_styles, GREEN, WHITE,
         },
-        widgets::{color_text, on_hover_text_side, Icon, UiExt},
+        widgets::{color_text, on_hover_text_side, thumbnail, Icon, UiExt},
         UiRef,
     },
     util::ContextExt,
 };
-use core::{Data as _, GameDataMapped};
+use core::{gff::Field, Data as _, GameDataMapped};
 use egui::{ComboBox, Grid, Id, Label, ScrollArea, Separator};
 use emath::{vec2, Align};
+use save::{Item, Save};
 
 pub struct Editor<'a> {
     items: &'a mut Vec<Item>,
@@ -82,12 +82,14 @@ impl<'a> Editor<'a> {
         // can't go lower than the first, so go for the second
         let mut prev_idx = get_nth_idx(1);
         for (idx, item, name) in sorted {
+            let icon = self.icon(item);
             ui.horizontal(|ui| {
                 if ui.s_icon_button(Icon::Remove, "Remove").clicked() {
                     removed = Some((idx, prev_idx));
                 }
                 prev_idx = idx;
                 ui.s_offset(5., 0.);
+                thumbnail(ui, self.data.textures.as_ref(), icon.as_deref(), 20.);
                 let r = ui.s_list_item(idx == self.selected, color_text(name, WHITE));
                 on_hover_text_side(ui, &r, &item.tag);
                 if r.clicked() {
@@ -113,6 +115,13 @@ impl<'a> Editor<'a> {
         }
     }
 
+    // saved items don't have to match their template so the icon is built from the item itself
+    fn icon(&self, item: &Item) -> Option<String> {
+        let model_variation = item.raw.get("ModelVariation", Field::byte).unwrap_or(1);
+        let base_item = self.data.inner.base_items.get(&item.base_item)?;
+        base_item.icon(model_variation)
+    }
+
     fn item(&mut self, ui: UiRef) {
         if self.items.is_empty() {
             return;
@@ -181,8 +190,13 @@ impl<'a> Editor<'a> {
                         if !show_all && item.name.is_none() {
                             continue;
                         }
-                        let r = ui.selectable_value(&mut selected, Some(&item.id), item.get_name());
-                        on_hover_text_side(ui, &r, &item.tag);
+                        ui.horizontal(|ui| {
+                            let textures = self.data.textures.as_ref();
+                            thumbnail(ui, textures, item.icon.as_deref(), 20.);
+                            let r =
+                                ui.selectable_value(&mut selected, Some(&item.id), item.get_name());
+                            on_hover_text_side(ui, &r, &item.tag);
+                        });
                     }
                     if let Some(id) = selected {
                         let item = &self.data.items[id];
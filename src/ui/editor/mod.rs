@@ -1,5 +1,4 @@
 use crate::{
-    save::Save,
     ui::{
         styles::set_button_styles,
         widgets::{Icon, UiExt as _},
@@ -8,12 +7,14 @@ use crate::{
     util::{ContextExt, Message},
 };
 use core::GameDataMapped;
-use egui::Layout;
+use egui::{Key, KeyboardShortcut, Layout, Modifiers};
 use emath::Align;
 use macros::{EnumList, EnumToString};
+use save::{Change, History, Save};
 use serde::{Deserialize, Serialize};
 
 mod area;
+mod changes;
 mod characters;
 mod general;
 mod globals;
@@ -78,28 +79,64 @@ pub enum Tab {
     Inventory,
     Quests,
     Area,
+    Changes,
 }
 
 static TAB_ID: &str = "e_id";
+// redo goes first, undo would match it as well since extra shift is ignored
+const REDO_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::Z);
+const UNDO_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::Z);
 
 pub struct Editor<'a> {
     save: &'a mut Save,
     data: &'a GameDataMapped,
+    history: &'a History,
+    changes: &'a [Change],
 }
 
 impl<'a> Editor<'a> {
-    pub fn new(save: &'a mut Save, data: &'a GameDataMapped) -> Self {
-        Self { save, data }
+    pub fn new(
+        save: &'a mut Save,
+        data: &'a GameDataMapped,
+        history: &'a History,
+        changes: &'a [Change],
+    ) -> Self {
+        Self {
+            save,
+            data,
+            history,
+            changes,
+        }
+    }
+
+    fn shortcuts(ui: UiRef) {
+        let ctx = ui.ctx().clone();
+        // text fields have their own undo
+        if ctx.memory(|m| m.focus().is_some()) {
+            return;
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&REDO_SHORTCUT)) {
+            ctx.send_message(Message::Redo);
+        } else if ctx.input_mut(|i| i.consume_shortcut(&UNDO_SHORTCUT)) {
+            ctx.send_message(Message::Undo);
+        }
     }
 
     pub fn show(&mut self, ui: UiRef) {
         let current_tab = ui.ctx().get_data_prs(TAB_ID).unwrap_or_default();
+        let changes = self.changes;
+        Self::shortcuts(ui);
 
         ui.horizontal(|ui| {
             set_button_styles(ui);
 
             for tab in Tab::LIST {
-                let btn = ui.s_button(&tab.to_string(), current_tab == tab, false);
+                let text = match tab {
+                    Tab::Changes if !changes.is_empty() => format!("{tab} ({})", changes.len()),
+                    _ => tab.to_string(),
+                };
+                let btn = ui.s_button(&text, current_tab == tab, false);
                 if btn.clicked() {
                     ui.ctx().set_data_prs(TAB_ID, tab);
                 }
@@ -120,6 +157,20 @@ impl<'a> Editor<'a> {
                 if btn.clicked() {
                     ui.ctx().send_message(Message::ReloadSave);
                 }
+
+                let btn = ui.add_enabled_ui(self.history.can_redo(), |ui| {
+                    ui.s_icon_button(Icon::Redo, "Redo (Ctrl+Shift+Z)")
+                });
+                if btn.inner.clicked() {
+                    ui.ctx().send_message(Message::Redo);
+                }
+
+                let btn = ui.add_enabled_ui(self.history.can_undo(), |ui| {
+                    ui.s_icon_button(Icon::Undo, "Undo (Ctrl+Z)")
+                });
+                if btn.inner.clicked() {
+                    ui.ctx().send_message(Message::Undo);
+                }
             })
         });
 
@@ -132,6 +183,7 @@ impl<'a> Editor<'a> {
             Tab::Quests => quests::Editor::new(self.save, self.data).show(ui),
             Tab::Inventory => inventory::Editor::new(self.save, self.data).show(ui),
             Tab::Area => area::Editor::new(self.save).show(ui),
+            Tab::Changes => changes::Editor::new(self.save, changes).show(ui),
         }
     }
 }
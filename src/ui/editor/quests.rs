@@ -1,5 +1,4 @@
 use crate::{
-    save::{JournalEntry, Save},
     ui::{
         styles::{
             set_combobox_styles, set_drag_value_styles, set_selectable_styles, set_striped_styles,
@@ -12,6 +11,7 @@ use crate::{
 };
 use core::{util::shorten_string, GameDataMapped, Quest, QuestStage};
 use egui::{ComboBox, DragValue, Grid, RichText, ScrollArea};
+use save::{JournalEntry, Save};
 use std::{
     collections::{BTreeMap, HashSet},
     sync::{Arc, Mutex},
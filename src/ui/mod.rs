@@ -1,22 +1,25 @@
 #[cfg(not(target_arch = "wasm32"))]
-use crate::util::{get_extra_save_directories, read_dir_dirs, Directory};
-use crate::{
-    save::Save,
-    util::{load_default_game_data, ContextExt as _, Game, Message},
-};
+use crate::util::{game_data_cache_path, get_extra_save_directories, read_dir_dirs, Directory};
+use crate::util::{ContextExt as _, Game, Message};
 #[cfg(target_arch = "wasm32")]
 use ahash::HashMap;
-use core::{util::fs::read_dir_filemap, GameData, GameDataMapped};
+use core::{
+    util::{encoding::CodePage, fs::read_dir_filemap},
+    GameData, GameDataMapped,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use core::{FResult, LoadProgress, LoadStage, ResourceManager, Textures};
 #[cfg(not(target_arch = "wasm32"))]
 use eframe::APP_KEY;
 use egui::{Context, Ui};
 use egui_toast::Toasts;
 use log::error;
-#[cfg(not(target_arch = "wasm32"))]
-use std::path::PathBuf;
 use std::sync::mpsc::{channel, Receiver, Sender};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{path::PathBuf, thread};
 
 use self::toasts::{init_toasts, make_toast};
+use save::{Change, History, Save};
 
 mod editor;
 #[cfg(not(target_arch = "wasm32"))]
@@ -36,16 +39,31 @@ struct SaveDirectories {
     dirs: Vec<Directory>,
 }
 
+// a load in progress in the background, only the last one started for a game is used
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy)]
+struct GameDataLoading {
+    id: u64,
+    stages: [Option<LoadProgress>; LoadStage::COUNT],
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 #[derive(Default, serde::Serialize, serde::Deserialize)]
 struct PersistentState {
     steam_path: Option<String>,
     game_paths: [Option<String>; Game::COUNT],
+    // None follows the language of the game data
+    #[serde(default)]
+    code_page: Option<CodePage>,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 pub struct SotorApp {
     save: Option<Save>,
+    // undo/redo for the loaded save
+    history: Option<History>,
+    // since the save was read or written, redone with each history step
+    changes: Vec<Change>,
     channel: (Sender<Message>, Receiver<Message>),
     default_game_data: [GameDataMapped; Game::COUNT],
     toasts: Toasts,
@@ -54,12 +72,17 @@ pub struct SotorApp {
     save_list: [Vec<SaveDirectories>; Game::COUNT],
     latest_save: Option<Directory>,
     game_data: [Option<GameDataMapped>; Game::COUNT],
+    game_data_loading: [Option<GameDataLoading>; Game::COUNT],
     prs: PersistentState,
 }
 
 #[cfg(target_arch = "wasm32")]
 pub struct SotorApp {
     save: Option<Save>,
+    // undo/redo for the loaded save
+    history: Option<History>,
+    // since the save was read or written, redone with each history step
+    changes: Vec<Change>,
     channel: (Sender<Message>, Receiver<Message>),
     default_game_data: [GameDataMapped; Game::COUNT],
     toasts: Toasts,
@@ -70,7 +93,7 @@ impl SotorApp {
         styles::set_styles(&cc.egui_ctx);
         let (sender, receiver) = channel();
         cc.egui_ctx.set_channel(sender.clone());
-        let default_game_data = load_default_game_data().map(GameData::into);
+        let default_game_data = bundled::game_data().map(GameData::into);
         let toasts = init_toasts();
 
         #[cfg(not(target_arch = "wasm32"))]
@@ -78,6 +101,8 @@ impl SotorApp {
             let prs = cc.storage.and_then(|s| eframe::get_value(s, APP_KEY));
             let mut app = Self {
                 save: None,
+                history: None,
+                changes: vec![],
                 save_path: None,
                 channel: (sender, receiver),
                 default_game_data,
@@ -86,10 +111,11 @@ impl SotorApp {
                 save_list: [vec![], vec![]],
                 latest_save: None,
                 game_data: [None, None],
+                game_data_loading: [None, None],
                 prs: prs.unwrap_or_default(),
             };
 
-            app.reload_game_data(&cc.egui_ctx, true);
+            app.reload_game_data(&cc.egui_ctx, true, false);
             app.reload_save_list(&cc.egui_ctx, true);
 
             app
@@ -98,6 +124,8 @@ impl SotorApp {
         {
             Self {
                 save: None,
+                history: None,
+                changes: vec![],
                 channel: (sender, receiver),
                 default_game_data,
                 toasts,
@@ -107,6 +135,8 @@ impl SotorApp {
 
     fn close_save(&mut self) {
         self.save = None;
+        self.history = None;
+        self.changes.clear();
         #[cfg(not(target_arch = "wasm32"))]
         {
             self.save_path = None;
@@ -117,6 +147,48 @@ impl SotorApp {
         self.toasts.add(make_toast(text.into(), content, success));
     }
 
+    fn set_save(&mut self, save: Save) {
+        self.history = Some(History::new(&save));
+        self.save = Some(save);
+        self.update_changes();
+    }
+
+    // the diff is too slow to run on every frame
+    fn update_changes(&mut self) {
+        let Some(save) = &self.save else {
+            return;
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let data = self.game_data[save.game.idx()]
+            .as_ref()
+            .unwrap_or(&self.default_game_data[save.game.idx()]);
+        #[cfg(target_arch = "wasm32")]
+        let data = &self.default_game_data[save.game.idx()];
+        self.changes = save::changes(save, data);
+    }
+
+    fn undo(&mut self) {
+        if let (Some(save), Some(history)) = (&mut self.save, &mut self.history) {
+            history.undo(save);
+        }
+        self.update_changes();
+    }
+
+    fn redo(&mut self) {
+        if let (Some(save), Some(history)) = (&mut self.save, &mut self.history) {
+            history.redo(save);
+        }
+        self.update_changes();
+    }
+
+    // writing fills in the raw data, which isn't something to undo
+    fn rebase_history(&mut self) {
+        if let (Some(save), Some(history)) = (&self.save, &mut self.history) {
+            history.rebase(save);
+        }
+        self.update_changes();
+    }
+
     fn reload_save(&mut self, ctx: &Context) {
         // platform-specific
         let success = self._reload_save(ctx);
@@ -137,13 +209,12 @@ impl SotorApp {
     }
 
     fn load_save(&mut self, files: &HashMap<String, Vec<u8>>, ctx: &Context) {
-        match Save::read_from_files(files, ctx) {
-            Ok(save) => {
-                self.save = Some(save);
-            }
+        // there are no settings in the browser and the bundled game data is English
+        match Save::read_from_files(files, CodePage::default()) {
+            Ok(save) => self.set_save(save),
             Err(err) => {
                 error!("{err}");
-                self.add_toast("Couldn't load save:", Some(err), false);
+                self.add_toast("Couldn't load save:", Some(err.to_string()), false);
             }
         }
         self.set_meta_id(ctx);
@@ -153,12 +224,19 @@ impl SotorApp {
         let Some(save) = &mut self.save else {
             return;
         };
-        let bytes = Save::save_to_zip(save, &self.default_game_data[save.game.idx()]);
+        let bytes = Save::save_to_zip(
+            save,
+            &self.default_game_data[save.game.idx()],
+            CodePage::default(),
+        );
         crate::util::download_save(bytes);
+        self.rebase_history();
     }
 
     fn _reload_save(&mut self, _ctx: &Context) -> bool {
-        self.save = self.save.clone().map(Save::reload);
+        if let Some(save) = self.save.clone() {
+            self.set_save(save.reload());
+        }
         true
     }
 }
@@ -177,7 +255,23 @@ impl SotorApp {
         ctx.set_meta_id(game_data, save);
     }
 
+    // a save has to be decoded before its game is known, so both installs are expected to share
+    // the language
+    fn code_page(&self) -> CodePage {
+        self.prs
+            .code_page
+            .or_else(|| {
+                self.game_data
+                    .iter()
+                    .flatten()
+                    .map(|d| d.inner.code_page)
+                    .next()
+            })
+            .unwrap_or_default()
+    }
+
     fn save(&mut self) {
+        let code_page = self.code_page();
         let game = self.save.as_ref().unwrap().game.idx();
         let game_data = if let Some(data) = &self.game_data[game] {
             data
@@ -188,27 +282,31 @@ impl SotorApp {
             self.save_path.as_ref().unwrap(),
             self.save.as_mut().unwrap(),
             game_data,
+            code_page,
         );
         match res {
-            Ok(_) => self.add_toast("Saved successfully", None, true),
+            Ok(()) => {
+                self.rebase_history();
+                self.add_toast("Saved successfully", None, true);
+            }
             Err(err) => {
                 error!("{err}");
-                self.add_toast("Couldn't save: ", Some(err), true);
+                self.add_toast("Couldn't save: ", Some(err.to_string()), true);
             }
         }
     }
 
     fn load_save(&mut self, path: String, ctx: &Context, silent: bool) -> bool {
-        let success = match Save::read_from_directory(&path, ctx) {
+        let success = match Save::read_from_directory(&path, self.code_page()) {
             Ok(save) => {
-                self.save = Some(save);
+                self.set_save(save);
                 self.save_path = Some(path);
                 true
             }
             Err(err) => {
                 error!("{err}");
                 if !silent {
-                    self.add_toast("Couldn't load save:", Some(err), false);
+                    self.add_toast("Couldn't load save:", Some(err.to_string()), false);
                 }
                 false
             }
@@ -317,40 +415,103 @@ impl SotorApp {
         }
     }
 
-    fn load_game_data(&mut self, game: Game, ctx: &Context, silent: bool) -> bool {
+    // reading takes a while, so it's done in the background and the result comes as a message
+    fn load_game_data(&mut self, game: Game, ctx: &Context, silent: bool, rebuild: bool) {
         let idx = game.idx();
-        let success = if let Some(game_path) = self.prs.game_paths[idx].as_ref() {
-            let game_data = GameData::read(game, game_path, self.prs.steam_path.as_ref());
-            match game_data {
-                Ok(data) => {
-                    self.game_data[idx] = Some(data.into());
-                    true
+        let Some(game_path) = self.prs.game_paths[idx].clone() else {
+            self.game_data[idx] = None;
+            self.game_data_loading[idx] = None;
+            self.update_changes();
+            self.set_meta_id(ctx);
+            return;
+        };
+        let steam_path = self.prs.steam_path.clone();
+        let id = fastrand::u64(..);
+        self.game_data_loading[idx] = Some(GameDataLoading {
+            id,
+            stages: [None; LoadStage::COUNT],
+        });
+
+        let sender = self.channel.0.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let send = |message| {
+                // the app is closed already if this fails
+                if sender.send(message).is_ok() {
+                    ctx.request_repaint();
                 }
-                Err(err) => {
-                    self.game_data[idx] = None;
-                    error!("KotOR {game}: {err:?}");
-                    if !silent {
-                        self.add_toast(
-                            format!("Couldn't load game data for KotOR {game}:"),
-                            Some(err),
-                            false,
-                        );
-                    }
-                    false
+            };
+            let progress = |progress| send(Message::GameDataProgress(game, id, progress));
+            let cache = game_data_cache_path(game);
+            let data =
+                ResourceManager::new(game, &game_path, steam_path.as_ref()).and_then(|resources| {
+                    let data =
+                        GameData::read_cached(&resources, cache.as_deref(), rebuild, &progress)?;
+                    let mut data: GameDataMapped = data.into();
+                    // thumbnails come from the same install, no need to look through it again
+                    data.textures = Some(Textures::new(resources));
+                    Ok(data)
+                });
+            send(Message::GameDataLoaded {
+                game,
+                id,
+                data: Box::new(data),
+                silent,
+            });
+        });
+    }
+
+    fn game_data_progress(&mut self, game: Game, id: u64, progress: LoadProgress) {
+        if let Some(loading) = self.game_data_loading[game.idx()]
+            .as_mut()
+            .filter(|loading| loading.id == id)
+        {
+            loading.stages[progress.stage as usize] = Some(progress);
+        }
+    }
+
+    fn game_data_loaded(
+        &mut self,
+        game: Game,
+        id: u64,
+        data: FResult<GameDataMapped>,
+        silent: bool,
+        ctx: &Context,
+    ) {
+        let idx = game.idx();
+        // a newer load has been started since
+        if self.game_data_loading[idx].map(|loading| loading.id) != Some(id) {
+            return;
+        }
+        self.game_data_loading[idx] = None;
+
+        match data {
+            Ok(data) => {
+                self.game_data[idx] = Some(data);
+                if !silent {
+                    self.add_toast(format!("Loaded game data for KotOR {game}"), None, true);
                 }
             }
-        } else {
-            self.game_data[idx] = None;
-            true
-        };
+            Err(err) => {
+                self.game_data[idx] = None;
+                error!("KotOR {game}: {err}");
+                if !silent {
+                    self.add_toast(
+                        format!("Couldn't load game data for KotOR {game}:"),
+                        Some(err.to_string()),
+                        false,
+                    );
+                }
+            }
+        }
+        // the names come from the game data
+        self.update_changes();
         self.set_meta_id(ctx);
-        success
     }
 
-    fn reload_game_data(&mut self, ctx: &Context, silent: bool) {
-        let res = Game::LIST.map(|game| self.load_game_data(game, ctx, silent));
-        if !silent && res.iter().all(|s| *s) {
-            self.add_toast("Reloaded successfully", None, true);
+    fn reload_game_data(&mut self, ctx: &Context, silent: bool, rebuild: bool) {
+        for game in Game::LIST {
+            self.load_game_data(game, ctx, silent, rebuild);
         }
     }
 
@@ -371,7 +532,7 @@ impl SotorApp {
                 *game_path = Some(new_path.to_str().unwrap().to_owned());
             }
             self.load_save_list(game);
-            self.load_game_data(game, ctx, false);
+            self.load_game_data(game, ctx, false, false);
         }
         self.load_latest_save(ctx);
     }
@@ -379,10 +540,18 @@ impl SotorApp {
     fn set_game_path(&mut self, game: Game, path: Option<String>, ctx: &Context) {
         self.prs.game_paths[game.idx()] = path;
         self.load_save_list(game);
-        self.load_game_data(game, ctx, false);
+        self.load_game_data(game, ctx, false, false);
         self.load_latest_save(ctx);
     }
 
+    fn set_code_page(&mut self, code_page: Option<CodePage>, ctx: &Context) {
+        self.prs.code_page = code_page;
+        // the text of the open save was decoded with the previous one
+        if self.save.as_ref().is_some_and(|save| !save.is_modified()) {
+            self.reload_save(ctx);
+        }
+    }
+
     fn toggle_settings_open(&mut self) {
         self.settings_open = !self.settings_open;
     }
@@ -401,20 +570,35 @@ impl eframe::App for SotorApp {
                 Message::Save => self.save(),
                 Message::CloseSave => self.close_save(),
                 Message::ReloadSave => self.reload_save(ctx),
+                Message::Undo => self.undo(),
+                Message::Redo => self.redo(),
                 Message::LoadSaveFromDir(path) => {
                     self.load_save(path.to_string(), ctx, false);
                 }
                 Message::ToggleSettingsOpen => self.toggle_settings_open(),
                 Message::SetSteamPath(path) => self.set_steam_path(path, ctx),
                 Message::SetGamePath(game, path) => self.set_game_path(game, path, ctx),
+                Message::SetCodePage(code_page) => self.set_code_page(code_page, ctx),
                 Message::ReloadSaveList => self.reload_save_list(ctx, false),
-                Message::ReloadGameData => self.reload_game_data(ctx, false),
+                Message::ReloadGameData => self.reload_game_data(ctx, false, false),
+                Message::RebuildGameData => self.reload_game_data(ctx, false, true),
+                Message::GameDataProgress(game, id, progress) => {
+                    self.game_data_progress(game, id, progress);
+                }
+                Message::GameDataLoaded {
+                    game,
+                    id,
+                    data,
+                    silent,
+                } => self.game_data_loaded(game, id, *data, silent, ctx),
             }
             #[cfg(target_arch = "wasm32")]
             match message {
                 Message::Save => self.save(),
                 Message::CloseSave => self.close_save(),
                 Message::ReloadSave => self.reload_save(ctx),
+                Message::Undo => self.undo(),
+                Message::Redo => self.redo(),
                 Message::LoadSaveFromFiles(files) => self.load_save(&files, ctx),
             }
         }
@@ -425,6 +609,7 @@ impl eframe::App for SotorApp {
                 || self.channel.0.send(Message::ToggleSettingsOpen).unwrap(),
                 &self.prs.steam_path,
                 &self.prs.game_paths,
+                self.prs.code_page,
             )
             .show(ctx);
         }
@@ -437,12 +622,17 @@ impl eframe::App for SotorApp {
             .min_width(160.)
             .max_width(ctx.screen_rect().width() - 760.)
             .show(ctx, |ui| {
-                side_panel::SidePanel::new(&self.save_path, &self.game_data, &self.save_list)
-                    .show(ui);
+                side_panel::SidePanel::new(
+                    &self.save_path,
+                    &self.game_data,
+                    &self.game_data_loading,
+                    &self.save_list,
+                )
+                .show(ui);
             });
 
         egui::CentralPanel::default().show(ctx, |ui| {
-            if let Some(save) = &mut self.save {
+            if let (Some(save), Some(history)) = (&mut self.save, &mut self.history) {
                 #[cfg(not(target_arch = "wasm32"))]
                 let current_data = if let Some(data) = &self.game_data[save.game.idx()] {
                     data
@@ -451,7 +641,14 @@ impl eframe::App for SotorApp {
                 };
                 #[cfg(target_arch = "wasm32")]
                 let current_data = &self.default_game_data[save.game.idx()];
-                editor::Editor::new(save, current_data).show(ui);
+                editor::Editor::new(save, current_data, history, &self.changes).show(ui);
+
+                // a step is made once an edit is done, not on every frame of dragging or typing
+                let editing =
+                    ctx.input(|i| i.pointer.any_down()) || ctx.memory(|m| m.focus().is_some());
+                if !editing && history.record(save) {
+                    self.changes = save::changes(save, current_data);
+                }
             } else {
                 editor::editor_placeholder(ui);
             }
@@ -1,14 +1,16 @@
 use crate::{
     ui::{
         styles::{
-            set_button_styles, set_striped_styles, BLACK, BLACK_TRANSPARENT, GREEN, RED, WHITE,
+            set_button_styles, set_combobox_styles, set_selectable_styles, set_striped_styles,
+            BLACK, BLACK_TRANSPARENT, GREEN, RED, WHITE,
         },
         widgets::{color_text, Icon, IconButton, UiExt},
         UiRef,
     },
     util::{select_directory, ContextExt, Game, Message},
 };
-use egui::{Area, Context, Frame, Grid, Label, Layout, Margin, Rounding, Sense, Window};
+use core::util::encoding::CodePage;
+use egui::{Area, ComboBox, Context, Frame, Grid, Label, Layout, Margin, Rounding, Sense, Window};
 use emath::{Align2, Pos2, Vec2};
 use std::path::PathBuf;
 
@@ -16,20 +18,23 @@ pub struct Settings<'a, F: Fn()> {
     toggle_open: F,
     steam_path: &'a Option<String>,
     game_paths: &'a [Option<String>; 2],
+    code_page: Option<CodePage>,
 }
 
-const WINDOW_SIZE: [f32; 2] = [400., 200.];
+const WINDOW_SIZE: [f32; 2] = [400., 270.];
 
 impl<'a, F: Fn()> Settings<'a, F> {
     pub fn new(
         toggle_open: F,
         steam_path: &'a Option<String>,
         game_paths: &'a [Option<String>; 2],
+        code_page: Option<CodePage>,
     ) -> Self {
         Self {
             toggle_open,
             steam_path,
             game_paths,
+            code_page,
         }
     }
 
@@ -77,6 +82,8 @@ impl<'a, F: Fn()> Settings<'a, F> {
 
                             Self::title_bar(ui, &self.toggle_open);
                             Self::paths(ui, self.steam_path, self.game_paths);
+                            Self::code_page(ui, self.code_page);
+                            Self::cache(ui);
                         });
                 });
             });
@@ -128,6 +135,42 @@ impl<'a, F: Fn()> Settings<'a, F> {
         }
     }
 
+    fn code_page(ui: UiRef, code_page: Option<CodePage>) {
+        let text = |code_page: Option<CodePage>| {
+            code_page.map_or_else(|| "Game language".to_owned(), |c| c.to_string())
+        };
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            set_combobox_styles(ui);
+            let mut selected = code_page;
+            ComboBox::from_id_source("s_code_page")
+                .selected_text(text(code_page))
+                .show_ui(ui, |ui| {
+                    set_selectable_styles(ui);
+                    ui.selectable_value(&mut selected, None, text(None));
+                    for code_page in CodePage::LIST {
+                        ui.selectable_value(&mut selected, Some(code_page), text(Some(code_page)));
+                    }
+                });
+            if selected != code_page {
+                ui.ctx().send_message(Message::SetCodePage(selected));
+            }
+            ui.label("Save text encoding, some translations need windows-1251");
+        });
+    }
+
+    fn cache(ui: UiRef) {
+        ui.separator();
+        ui.horizontal(|ui| {
+            set_button_styles(ui);
+            if ui.s_button_basic("Rebuild").clicked() {
+                ui.ctx().send_message(Message::RebuildGameData);
+            }
+            ui.label("Game data is cached until the game files change");
+        });
+    }
+
     fn paths(ui: UiRef, steam_path: &Option<String>, game_paths: &[Option<String>; 2]) {
         ui.label("Set paths if you want automatically loaded save lists and non-vanilla game data");
         ui.separator();
@@ -2,16 +2,20 @@ use crate::{
     ui::{
         styles::{BLUE, GREY, RED, WHITE},
         widgets::{color_text, Icon, UiExt},
-        SaveDirectories, UiRef,
+        GameDataLoading, SaveDirectories, UiRef,
     },
     util::{open_file_manager, ContextExt, Game, Message},
 };
-use core::GameDataMapped;
-use egui::{collapsing_header::CollapsingState, Frame, Layout, Margin, PointerButton, ScrollArea};
+use core::{GameDataMapped, LoadProgress, LoadStage};
+use egui::{
+    collapsing_header::CollapsingState, Frame, Layout, Margin, PointerButton, ProgressBar,
+    ScrollArea,
+};
 
 pub struct SidePanel<'a> {
     current_save: &'a Option<String>,
     game_data: &'a [Option<GameDataMapped>; Game::COUNT],
+    game_data_loading: &'a [Option<GameDataLoading>; Game::COUNT],
     save_list: &'a [Vec<SaveDirectories>; Game::COUNT],
 }
 
@@ -19,11 +23,13 @@ impl<'a> SidePanel<'a> {
     pub fn new(
         current_save: &'a Option<String>,
         game_data: &'a [Option<GameDataMapped>; Game::COUNT],
+        game_data_loading: &'a [Option<GameDataLoading>; Game::COUNT],
         save_list: &'a [Vec<SaveDirectories>; Game::COUNT],
     ) -> Self {
         Self {
             current_save,
             game_data,
+            game_data_loading,
             save_list,
         }
     }
@@ -31,6 +37,9 @@ impl<'a> SidePanel<'a> {
     pub fn show(&self, ui: UiRef) {
         Self::padding_frame(ui, |ui| {
             ui.horizontal(|ui| self.header(ui));
+            for game in Game::LIST {
+                self.loading_progress(ui, game);
+            }
             ui.separator();
         });
 
@@ -38,7 +47,9 @@ impl<'a> SidePanel<'a> {
     }
 
     fn header_game_label(&self, ui: UiRef, game: Game) {
-        let (color, tooltip) = if self.game_data[game.idx()].is_some() {
+        let (color, tooltip) = if self.game_data_loading[game.idx()].is_some() {
+            (GREY, "Loading game data")
+        } else if self.game_data[game.idx()].is_some() {
             (BLUE, "Game data loaded")
         } else {
             (
@@ -53,6 +64,35 @@ impl<'a> SidePanel<'a> {
         });
     }
 
+    fn loading_progress(&self, ui: UiRef, game: Game) {
+        let Some(loading) = &self.game_data_loading[game.idx()] else {
+            return;
+        };
+        // every stage counts the same, the ones that haven't reported anything yet are at 0
+        #[allow(clippy::cast_precision_loss)]
+        let fraction = |progress: &Option<LoadProgress>| {
+            progress.map_or(0., |p| p.done as f32 / p.total.max(1) as f32)
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let total = loading.stages.iter().map(fraction).sum::<f32>() / LoadStage::COUNT as f32;
+        let current = LoadStage::LIST
+            .iter()
+            .rev()
+            .find_map(|stage| loading.stages[*stage as usize].filter(|p| p.done < p.total))
+            .map_or_else(String::new, |p| format!(": {}", p.stage));
+
+        let tooltip = LoadStage::LIST
+            .iter()
+            .map(|stage| match loading.stages[*stage as usize] {
+                Some(p) => format!("{stage}: {}/{}", p.done, p.total),
+                None => format!("{stage}: waiting"),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        ui.add(ProgressBar::new(total).text(format!("K{game}{current}")))
+            .on_hover_text(tooltip);
+    }
+
     fn header(&self, ui: UiRef) {
         Game::LIST.map(|game| self.header_game_label(ui, game));
 
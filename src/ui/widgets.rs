@@ -2,11 +2,13 @@ use super::{
     styles::{BLACK, BLUE, GREEN, GREEN_DARK, GREY, GREY_DARK, WHITE},
     UiRef,
 };
-use core::util::shorten_string;
+use crate::util::ContextExt as _;
+use core::{util::shorten_string, Textures};
 use egui::{
-    epaint::TextShape, style::HandleShape, Area, Button, Color32, CursorIcon, FontSelection, Frame,
-    Order, Response, RichText, Rounding, Sense, Slider, Stroke, TextBuffer, TextEdit, TextStyle,
-    Ui, Widget, WidgetInfo, WidgetText, WidgetType,
+    epaint::TextShape, style::HandleShape, Area, Button, Color32, ColorImage, CursorIcon,
+    FontSelection, Frame, Id, Order, Response, RichText, Rounding, Sense, Slider, Stroke,
+    TextBuffer, TextEdit, TextStyle, TextureHandle, TextureOptions, Ui, Widget, WidgetInfo,
+    WidgetText, WidgetType,
 };
 use emath::{pos2, vec2, Align, Numeric, Rect};
 use std::ops::RangeInclusive;
@@ -129,6 +131,8 @@ pub enum Icon {
     Save,
     #[allow(dead_code)]
     Triangle,
+    Undo,
+    Redo,
 }
 
 impl Icon {
@@ -148,6 +152,8 @@ impl Icon {
             Self::Remove => "\u{f2ed}",
             Self::Save => "\u{f0c7}",
             Self::Triangle => "\u{f0d7}",
+            Self::Undo => "\u{f0e2}",
+            Self::Redo => "\u{f01e}",
         }
     }
 }
@@ -356,3 +362,36 @@ pub fn on_hover_text_side(ui: UiRef, r: &Response, text: &str) {
                 });
         });
 }
+
+// icon or portrait from the game files, takes no space when textures aren't available
+pub fn thumbnail(ui: UiRef, textures: Option<&Textures>, name: Option<&str>, size: f32) {
+    let Some(textures) = textures else {
+        return;
+    };
+    let (rect, _) = ui.allocate_exact_size(vec2(size, size), Sense::hover());
+    // lists can be long, only what's on the screen gets decoded
+    let Some(name) = name.filter(|_| ui.is_rect_visible(rect)) else {
+        return;
+    };
+
+    let ctx = ui.ctx().clone();
+    let id = Id::new("thumbnail").with(name);
+    // missing ones are cached too so they aren't looked up every frame
+    let texture = ctx
+        .get_data::<Option<TextureHandle>>(id)
+        .unwrap_or_else(|| {
+            let texture = textures.get(name).map(|tpc| {
+                let image = tpc.image_for_size(size as usize);
+                let image =
+                    ColorImage::from_rgba_unmultiplied([image.width, image.height], &image.pixels);
+                ctx.load_texture(name, image, TextureOptions::LINEAR)
+            });
+            ctx.set_data(id, texture.clone());
+            texture
+        });
+
+    if let Some(texture) = texture {
+        let uv = Rect::from_min_max(pos2(0., 0.), pos2(1., 1.));
+        ui.painter().image(texture.id(), rect, uv, Color32::WHITE);
+    }
+}
@@ -1,13 +1,14 @@
-use zip::ZipWriter;
-
 use crate::util::Game;
 pub use core::util::fs::*;
-use std::{
-    fs::{self, File},
-    io::{self, Write},
-    path::PathBuf,
-    process::Command,
-};
+use std::{path::PathBuf, process::Command};
+
+// eframe keeps its storage in a dir named after this
+pub const APP_ID: &str = "sotor";
+
+// game data read from the install is kept here until the install changes
+pub fn game_data_cache_path(game: Game) -> Option<PathBuf> {
+    eframe::storage_dir(APP_ID).map(|dir| dir.join(format!("game_data_k{game}.bin")))
+}
 
 pub fn open_file_manager(path: &str) {
     let program = if cfg!(target_os = "windows") {
@@ -31,19 +32,6 @@ pub fn open_file_manager(path: &str) {
     command.arg(path).spawn().unwrap();
 }
 
-pub fn add_zip_file(path: &PathBuf, zip: &mut ZipWriter<&mut File>) -> io::Result<()> {
-    if !path.exists() {
-        return Ok(());
-    }
-    let options = zip::write::FileOptions::default();
-
-    let file = fs::read(path)?;
-    zip.start_file(path.file_name().unwrap().to_str().unwrap(), options)?;
-    zip.write_all(&file)?;
-
-    Ok(())
-}
-
 pub fn get_extra_save_directories(game: Game) -> Vec<PathBuf> {
     let mut paths = vec![];
 
@@ -1,14 +1,17 @@
-use crate::{save::Save, util::SResult};
+use crate::util::SResult;
 use ahash::HashMap;
 use core::{util::bytes::Cursor, Data, GameDataMapped};
 use egui::{util::id_type_map::SerializableAny, ColorImage, Context, Id, Ui};
 use image::{io::Reader as ImageReader, ImageFormat};
+use save::Save;
 use std::{any::Any, borrow::Cow, fmt::Display, hash::Hash, sync::mpsc::Sender};
 
 pub enum Message {
     Save,
     CloseSave,
     ReloadSave,
+    Undo,
+    Redo,
     #[cfg(target_arch = "wasm32")]
     LoadSaveFromFiles(HashMap<String, Vec<u8>>),
     #[cfg(not(target_arch = "wasm32"))]
@@ -20,9 +23,24 @@ pub enum Message {
     #[cfg(not(target_arch = "wasm32"))]
     SetGamePath(super::Game, Option<String>),
     #[cfg(not(target_arch = "wasm32"))]
+    SetCodePage(Option<core::util::encoding::CodePage>),
+    #[cfg(not(target_arch = "wasm32"))]
     ReloadSaveList,
     #[cfg(not(target_arch = "wasm32"))]
     ReloadGameData,
+    // reads the game data again even if the install hasn't changed
+    #[cfg(not(target_arch = "wasm32"))]
+    RebuildGameData,
+    // sent by the game data loading thread, the id tells apart loads of the same game
+    #[cfg(not(target_arch = "wasm32"))]
+    GameDataProgress(super::Game, u64, core::LoadProgress),
+    #[cfg(not(target_arch = "wasm32"))]
+    GameDataLoaded {
+        game: super::Game,
+        id: u64,
+        data: Box<core::FResult<GameDataMapped>>,
+        silent: bool,
+    },
 }
 
 pub trait ContextExt {